@@ -7,24 +7,37 @@ use std::{
 use anyhow::{anyhow, Context, Result};
 use dunce::canonicalize;
 use next_core::{
-    self, next_config::load_next_config, pages_structure::find_pages_structure,
-    turbopack::ecmascript::utils::StringifyJs, url_node::get_sorted_routes,
+    self,
+    manifest::production_manifest_path,
+    mode::NextMode,
+    next_config::load_next_config,
+    next_server::{get_build_asset_context, report_externals_summary},
+    optimize_css::{minify_css, optimize_css, CssNotMinifiedIssue},
+    pages_structure::find_pages_structure,
+    turbopack::ecmascript::utils::StringifyJs,
+    url_node::get_sorted_routes,
 };
 use serde::Serialize;
 use turbo_tasks::{
     graph::{AdjacencyMap, GraphTraversal},
-    CollectiblesSource, CompletionVc, RawVc, TransientInstance, TransientValue, TryJoinIterExt,
-    ValueToString,
+    CollectiblesSource, CompletionVc, CompletionsVc, RawVc, TransientInstance, TransientValue,
+    TryJoinIterExt, Value, ValueToString,
 };
 use turbopack_binding::{
-    turbo::tasks_fs::{DiskFileSystemVc, FileContent, FileSystem, FileSystemPathVc, FileSystemVc},
+    turbo::tasks_fs::{
+        DiskFileSystemVc, File, FileContent, FileSystem, FileSystemEntryType, FileSystemPathVc,
+        FileSystemVc,
+    },
     turbopack::{
         cli_utils::issue::{ConsoleUiVc, LogOptions},
         core::{
-            asset::{Asset, AssetVc, AssetsVc},
+            asset::{Asset, AssetContent, AssetVc, AssetsVc},
+            context::AssetContext,
             environment::ServerAddrVc,
             issue::{IssueReporter, IssueReporterVc, IssueSeverity, IssueVc},
             reference::AssetReference,
+            reference_type::{EntryReferenceSubType, ReferenceType},
+            source_asset::SourceAssetVc,
             virtual_fs::VirtualFileSystemVc,
         },
         dev::DevChunkingContextVc,
@@ -80,9 +93,10 @@ pub(crate) async fn next_build(options: TransientInstance<BuildOptions>) -> Resu
     let issue_reporter: IssueReporterVc =
         ConsoleUiVc::new(TransientInstance::new(log_options)).into();
     let node_fs = node_fs(&project_root, issue_reporter);
-    let node_root = node_fs.root().join(".next");
+    // The `distDir` is only known once the next.config.js is loaded, so it's
+    // evaluated from `.next`.
+    let execution_root = node_fs.root().join(".next");
     let client_fs = client_fs(&project_root, issue_reporter);
-    let client_root = client_fs.root().join(".next");
     // TODO(alexkirsz) This should accept a URL for assetPrefix.
     // let client_public_fs = VirtualFileSystemVc::new();
     // let client_public_root = client_public_fs.root();
@@ -99,9 +113,9 @@ pub(crate) async fn next_build(options: TransientInstance<BuildOptions>) -> Resu
 
     let build_chunking_context = DevChunkingContextVc::builder(
         project_root,
-        node_root,
-        node_root.join("chunks"),
-        node_root.join("assets"),
+        execution_root,
+        execution_root.join("chunks"),
+        execution_root.join("assets"),
         node_build_environment(),
     )
     .build();
@@ -111,6 +125,24 @@ pub(crate) async fn next_build(options: TransientInstance<BuildOptions>) -> Resu
     let execution_context = ExecutionContextVc::new(project_root, build_chunking_context, env);
     let next_config = load_next_config(execution_context.with_layer("next_config"));
 
+    // Next.js can't evaluate a `next.config.ts` yet, but it's still compiled, so
+    // errors in it and its imports are reported.
+    let next_config_ts = project_root.join("next.config.ts");
+    if matches!(*next_config_ts.get_type().await?, FileSystemEntryType::File) {
+        let next_config_ts = get_build_asset_context(project_root).process(
+            SourceAssetVc::new(next_config_ts).into(),
+            Value::new(ReferenceType::Entry(EntryReferenceSubType::Undefined)),
+        );
+        handle_issues(all_assets_from_entry(next_config_ts), issue_reporter).await?;
+    }
+
+    // All build output, including the manifests, is written to the `distDir`.
+    let node_root = next_config.dist_root(node_fs.root());
+    let client_root = next_config.dist_root(client_fs.root());
+    let optimize_css = optimize_css(project_root, next_config, NextMode::Build);
+    handle_issues(optimize_css, issue_reporter).await?;
+    let optimize_css = *optimize_css.await?;
+
     let pages_structure = find_pages_structure(project_root, next_router_root, next_config);
 
     let page_chunks = get_page_chunks(
@@ -290,14 +322,12 @@ pub(crate) async fn next_build(options: TransientInstance<BuildOptions>) -> Resu
                     .await?
                     .join("\n")
             );
-            deduplicated_node_assets
-                .into_values()
-                .map(|asset| async move {
-                    emit(asset).await?;
-                    Ok(())
-                })
-                .try_join()
-                .await?;
+            emit_assets(
+                deduplicated_node_assets.into_values().collect(),
+                optimize_css,
+                issue_reporter,
+            )
+            .await?;
 
             tracing::debug!(
                 "all client assets: {}",
@@ -310,14 +340,12 @@ pub(crate) async fn next_build(options: TransientInstance<BuildOptions>) -> Resu
                     .await?
                     .join("\n")
             );
-            deduplicated_client_assets
-                .into_values()
-                .map(|asset| async move {
-                    emit(asset).await?;
-                    Ok(())
-                })
-                .try_join()
-                .await?;
+            emit_assets(
+                deduplicated_client_assets.into_values().collect(),
+                optimize_css,
+                issue_reporter,
+            )
+            .await?;
         }
 
         write_placeholder_manifest(
@@ -380,7 +408,7 @@ pub(crate) async fn next_build(options: TransientInstance<BuildOptions>) -> Resu
 
             tracing::debug!("writing _ssgManifest.js for build id: {}", build_id);
 
-            let ssg_manifest_path = format!("static/{build_id}/_ssgManifest.js");
+            let ssg_manifest_path = production_manifest_path(build_id, "_ssgManifest.js");
 
             let ssg_manifest_fs_path = node_root.join(&ssg_manifest_path);
             ssg_manifest_fs_path
@@ -432,7 +460,7 @@ pub(crate) async fn next_build(options: TransientInstance<BuildOptions>) -> Resu
                 pages,
             };
 
-            let client_manifest_path = format!("static/{build_id}/_buildManifest.js");
+            let client_manifest_path = production_manifest_path(build_id, "_buildManifest.js");
 
             let client_manifest_fs_path = node_root.join(&client_manifest_path);
             client_manifest_fs_path
@@ -464,6 +492,30 @@ pub(crate) async fn next_build(options: TransientInstance<BuildOptions>) -> Resu
             .await?;
     }
 
+    // The externals decisions are collected from resolving the modules of the
+    // server chunks.
+    let server_assets = page_chunks
+        .await?
+        .iter()
+        .map(|page_chunk| async move {
+            let page_chunk = page_chunk.await?;
+            let included = match filter_pages {
+                Some(filter_pages) => filter_pages.contains(page_chunk.pathname.await?.as_str()),
+                None => true,
+            };
+            Ok(included.then(|| all_assets_from_entry(page_chunk.node_chunk)))
+        })
+        .try_join()
+        .await?
+        .into_iter()
+        .flatten()
+        .collect();
+    handle_issues(
+        report_externals_summary(project_root, server_assets),
+        issue_reporter,
+    )
+    .await?;
+
     Ok(CompletionVc::immutable())
 }
 
@@ -472,6 +524,126 @@ fn emit(asset: AssetVc) -> CompletionVc {
     asset.content().write(asset.ident().path())
 }
 
+/// Returns the source map of the stylesheet `asset`, which it references.
+async fn css_source_map(asset: AssetVc) -> Result<Option<AssetVc>> {
+    let source_map_path = format!("{}.map", asset.ident().path().await?.path);
+    for reference in asset.references().await?.iter() {
+        for referenced in reference.resolve_reference().primary_assets().await?.iter() {
+            if referenced.ident().path().await?.path == source_map_path {
+                return Ok(Some(*referenced));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Returns the content of `asset` if it's a file.
+async fn file_content(asset: AssetVc) -> Result<Option<File>> {
+    let AssetContent::File(file) = &*asset.content().await? else {
+        return Ok(None);
+    };
+    let FileContent::Content(file) = &*file.await? else {
+        return Ok(None);
+    };
+    Ok(Some(file.clone()))
+}
+
+/// Emits `assets`, reporting the issues of emitting them. With
+/// `experimental.optimizeCss`, the source map of a stylesheet is emitted along
+/// with the stylesheet, so it's only emitted on its own when no stylesheet
+/// references it.
+async fn emit_assets(
+    assets: Vec<AssetVc>,
+    optimize_css: bool,
+    issue_reporter: IssueReporterVc,
+) -> Result<()> {
+    let mut stylesheet_source_maps = HashSet::new();
+    if optimize_css {
+        for asset in &assets {
+            if asset.ident().path().await?.extension() != Some("css") {
+                continue;
+            }
+            if let Some(source_map) = css_source_map(*asset).await? {
+                stylesheet_source_maps.insert(source_map.ident().path().await?.path.clone());
+            }
+        }
+    }
+    let stylesheet_source_maps = &stylesheet_source_maps;
+    assets
+        .into_iter()
+        .map(|asset| async move {
+            if stylesheet_source_maps.contains(&asset.ident().path().await?.path) {
+                return Ok(());
+            }
+            let completion = emit_optimized(asset, optimize_css);
+            handle_issues(completion, issue_reporter).await?;
+            completion.await?;
+            Ok(())
+        })
+        .try_join()
+        .await?;
+    Ok(())
+}
+
+/// Emits `asset`. With `experimental.optimizeCss`, stylesheets are minified
+/// and their source maps, which are then emitted with them, are regenerated.
+/// A stylesheet which can't be minified, e.g. because it isn't UTF-8, is
+/// emitted as is, and a warning is reported for it.
+#[turbo_tasks::function]
+async fn emit_optimized(asset: AssetVc, optimize_css: bool) -> Result<CompletionVc> {
+    if !optimize_css {
+        return Ok(emit(asset));
+    }
+    let path = asset.ident().path();
+    let path_value = path.await?;
+    if path_value.extension() != Some("css") {
+        return Ok(emit(asset));
+    }
+
+    let source_map = css_source_map(asset).await?;
+    let emit_unchanged = |reason: Option<String>| {
+        if let Some(reason) = reason {
+            CssNotMinifiedIssue { path, reason }
+                .cell()
+                .as_issue()
+                .emit();
+        }
+        CompletionsVc::all([asset].into_iter().chain(source_map).map(emit).collect())
+    };
+    let Some(file) = file_content(asset).await? else {
+        return Ok(emit_unchanged(None));
+    };
+    let Ok(css) = file.content().to_str() else {
+        return Ok(emit_unchanged(Some("it isn't valid UTF-8".to_string())));
+    };
+    let input_source_map = match source_map {
+        Some(source_map) => match file_content(source_map).await? {
+            Some(source_map) => Some(source_map.content().to_bytes()?.into_owned()),
+            None => None,
+        },
+        None => None,
+    };
+    let file_name = path_value.file_name();
+    let minified = match minify_css(file_name, &css, input_source_map.as_deref()) {
+        Ok(minified) => minified,
+        Err(err) => return Ok(emit_unchanged(Some(err.to_string()))),
+    };
+
+    let mut code = minified.code;
+    let mut completions = Vec::with_capacity(2);
+    if let Some(source_map) = source_map {
+        code.push_str(&format!("\n/*# sourceMappingURL={file_name}.map*/"));
+        completions.push(
+            source_map
+                .ident()
+                .path()
+                .write(FileContent::Content(File::from(minified.source_map)).cell()),
+        );
+    }
+    completions.push(path.write(FileContent::Content(File::from(code)).cell()));
+    Ok(CompletionsVc::all(completions))
+}
+
 #[turbo_tasks::function]
 async fn workspace_fs(
     workspace_root: &str,
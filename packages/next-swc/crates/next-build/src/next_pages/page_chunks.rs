@@ -11,7 +11,7 @@ use next_core::{
     next_config::NextConfigVc,
     next_server::{
         get_server_compile_time_info, get_server_module_options_context,
-        get_server_resolve_options_context, ServerContextType,
+        get_server_resolve_options_context, ResolvePluginsVc, ServerContextType,
     },
     pages_structure::{
         PagesDirectoryStructure, PagesDirectoryStructureVc, PagesStructure, PagesStructureItem,
@@ -125,13 +125,15 @@ pub async fn get_page_chunks(
     )
     .into();
 
-    let node_compile_time_info = get_server_compile_time_info(mode, env, node_addr);
+    let node_compile_time_info =
+        get_server_compile_time_info(project_root, mode, env, node_addr, next_config);
     let node_resolve_options_context = get_server_resolve_options_context(
         project_root,
         node_ty,
         mode,
         next_config,
         execution_context,
+        ResolvePluginsVc::empty(),
     );
     let node_module_options_context = get_server_module_options_context(
         project_root,
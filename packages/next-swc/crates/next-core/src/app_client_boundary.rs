@@ -0,0 +1,133 @@
+use anyhow::Result;
+use turbo_tasks::{
+    primitives::{StringVc, StringsVc},
+    CompletionVc,
+};
+use turbopack_binding::{
+    turbo::tasks_fs::{FileContent, FileSystemEntryType, FileSystemPathVc},
+    turbopack::core::issue::{Issue, IssueSeverity, IssueSeverityVc, IssueVc},
+};
+
+/// Returns whether the directive prologue of `source` contains a
+/// `"use client"` directive. Comments before and between directives are
+/// skipped.
+fn has_use_client_directive(source: &str) -> bool {
+    let mut rest = source;
+    loop {
+        rest = rest.trim_start();
+        if let Some(comment) = rest.strip_prefix("//") {
+            rest = comment.split_once('\n').map_or("", |(_, rest)| rest);
+        } else if let Some(comment) = rest.strip_prefix("/*") {
+            rest = comment.split_once("*/").map_or("", |(_, rest)| rest);
+        } else if let Some(quote @ ('"' | '\'')) = rest.chars().next() {
+            let Some((directive, after)) = rest[1..].split_once(quote) else {
+                return false;
+            };
+            if directive == "use client" {
+                return true;
+            }
+            rest = after.trim_start();
+            rest = rest.strip_prefix(';').unwrap_or(rest);
+        } else {
+            return false;
+        }
+    }
+}
+
+/// Checks that a special App Router file which is rendered as a React error
+/// boundary, like `error.tsx` or `global-error.tsx`, is a Client Component,
+/// and emits an issue explaining how to fix it if it isn't.
+#[turbo_tasks::function]
+pub async fn validate_client_boundary(path: FileSystemPathVc) -> Result<CompletionVc> {
+    let FileContent::Content(content) = &*path.read().await? else {
+        return Ok(CompletionVc::new());
+    };
+    if !has_use_client_directive(&content.content().to_str()?) {
+        MissingClientDirectiveIssue {
+            path,
+            file: path.await?.file_name().to_string(),
+        }
+        .cell()
+        .as_issue()
+        .emit();
+    }
+    Ok(CompletionVc::new())
+}
+
+/// Validates the `global-error` file in the root of the app directory, if
+/// there is one, like [validate_client_boundary].
+#[turbo_tasks::function]
+pub async fn validate_global_error(
+    app_dir: FileSystemPathVc,
+    page_extensions: StringsVc,
+) -> Result<CompletionVc> {
+    for extension in page_extensions.await?.iter() {
+        let path = app_dir.join(&format!("global-error.{extension}"));
+        if matches!(&*path.get_type().await?, FileSystemEntryType::File) {
+            validate_client_boundary(path).await?;
+        }
+    }
+    Ok(CompletionVc::new())
+}
+
+#[turbo_tasks::value(shared)]
+struct MissingClientDirectiveIssue {
+    path: FileSystemPathVc,
+    file: String,
+}
+
+#[turbo_tasks::value_impl]
+impl Issue for MissingClientDirectiveIssue {
+    #[turbo_tasks::function]
+    fn severity(&self) -> IssueSeverityVc {
+        IssueSeverity::Error.into()
+    }
+
+    #[turbo_tasks::function]
+    fn category(&self) -> StringVc {
+        StringVc::cell("app".to_string())
+    }
+
+    #[turbo_tasks::function]
+    fn context(&self) -> FileSystemPathVc {
+        self.path
+    }
+
+    #[turbo_tasks::function]
+    fn title(&self) -> StringVc {
+        StringVc::cell(format!("{} must be a Client Component", self.file))
+    }
+
+    #[turbo_tasks::function]
+    fn description(&self) -> StringVc {
+        StringVc::cell(format!(
+            "{} is rendered as a React error boundary, which needs to run on the client to catch \
+             errors and be reset. Add the \"use client\" directive at the top of the file, before \
+             any imports.",
+            self.file
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::has_use_client_directive;
+
+    #[test]
+    fn test_use_client_directive() {
+        assert!(has_use_client_directive(
+            "'use client'\n\nexport default function Error() {}"
+        ));
+        assert!(has_use_client_directive(
+            "// Error boundary\n/* license */\n\"use strict\";\n\"use client\";\nexport {}"
+        ));
+        // An `error.tsx` without the directive gets the guidance issue.
+        assert!(!has_use_client_directive(
+            "import { useEffect } from 'react'\n'use client'\nexport default function Error() {}"
+        ));
+        assert!(!has_use_client_directive(
+            "export default function Error() { return 'use client' }"
+        ));
+        assert!(!has_use_client_directive(""));
+    }
+}
@@ -70,7 +70,11 @@ pub struct NextSegmentConfig {
     pub revalidate: Option<NextRevalidate>,
     pub fetch_cache: Option<NextSegmentFetchCache>,
     pub runtime: Option<NextRuntime>,
-    pub preferred_region: Option<String>,
+    /// The preferred regions of the function, exported as a string or an
+    /// array of strings.
+    pub preferred_region: Option<Vec<String>>,
+    /// The maximum duration of the function in seconds.
+    pub max_duration: Option<u32>,
 }
 
 #[turbo_tasks::value_impl]
@@ -92,6 +96,7 @@ impl NextSegmentConfig {
             fetch_cache,
             runtime,
             preferred_region,
+            max_duration,
         } = self;
         *dynamic = dynamic.or(parent.dynamic);
         *dynamic_params = dynamic_params.or(parent.dynamic_params);
@@ -99,6 +104,7 @@ impl NextSegmentConfig {
         *fetch_cache = fetch_cache.or(parent.fetch_cache);
         *runtime = runtime.or(parent.runtime);
         *preferred_region = preferred_region.take().or(parent.preferred_region.clone());
+        *max_duration = max_duration.or(parent.max_duration);
     }
 
     /// Applies a config from a paralllel route to this config, returning an
@@ -132,6 +138,7 @@ impl NextSegmentConfig {
             fetch_cache,
             runtime,
             preferred_region,
+            max_duration,
         } = self;
         merge_parallel(dynamic, &parallel_config.dynamic, "dynamic")?;
         merge_parallel(
@@ -147,6 +154,7 @@ impl NextSegmentConfig {
             &parallel_config.preferred_region,
             "referredRegion",
         )?;
+        merge_parallel(max_duration, &parallel_config.max_duration, "maxDuration")?;
         Ok(())
     }
 }
@@ -358,12 +366,31 @@ fn parse_config_value(
         }
         "preferredRegion" => {
             let value = eval_context.eval(init);
-            let Some(val) = value.as_str() else {
-                invalid_config("`preferredRegion` needs to be a static string", &value);
+            let regions = match &value {
+                JsValue::Array { items, .. } => items
+                    .iter()
+                    .map(|item| item.as_str().map(ToString::to_string))
+                    .collect::<Option<Vec<_>>>(),
+                _ => value.as_str().map(|val| vec![val.to_string()]),
+            };
+            let Some(regions) = regions else {
+                invalid_config(
+                    "`preferredRegion` needs to be a static string or array of strings",
+                    &value,
+                );
                 return;
             };
 
-            config.preferred_region = Some(val.to_string());
+            config.preferred_region = Some(regions);
+        }
+        "maxDuration" => {
+            let value = eval_context.eval(init);
+            match value {
+                JsValue::Constant(ConstantValue::Num(ConstantNumber(val))) if val > 0.0 => {
+                    config.max_duration = Some(val as u32);
+                }
+                _ => invalid_config("`maxDuration` needs to be a static positive number", &value),
+            }
         }
         _ => {}
     }
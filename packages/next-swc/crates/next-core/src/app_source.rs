@@ -41,7 +41,6 @@ use turbopack_binding::{
             text::TextContentSourceAssetVc,
             utils::{FormatIter, StringifyJs},
         },
-        env::ProcessEnvAssetVc,
         node::{
             debug::should_debug,
             execution_context::ExecutionContextVc,
@@ -60,6 +59,7 @@ use turbopack_binding::{
 };
 
 use crate::{
+    app_client_boundary::{validate_client_boundary, validate_global_error},
     app_render::next_server_component_transition::NextServerComponentTransition,
     app_segment_config::{parse_segment_config_from_loader_tree, parse_segment_config_from_source},
     app_structure::{
@@ -67,9 +67,11 @@ use crate::{
         LoaderTreeVc, Metadata, MetadataItem, MetadataWithAltItem, OptionAppDirVc,
     },
     bootstrap::{route_bootstrap, BootstrapConfigVc},
+    cache_markers::CacheMarkersContentSourceVc,
     embed_js::{next_asset, next_js_file_path},
     env::env_for_js,
     fallback::get_fallback_page,
+    functions_config_manifest::FunctionsConfigManifestContentSourceVc,
     mode::NextMode,
     next_client::{
         context::{
@@ -94,7 +96,8 @@ use crate::{
     next_route_matcher::{NextFallbackMatcherVc, NextParamsMatcherVc},
     next_server::context::{
         get_server_compile_time_info, get_server_module_options_context,
-        get_server_resolve_options_context, ServerContextType,
+        get_server_resolve_options_context, get_server_runtime_entries, ResolvePluginsVc,
+        ServerContextType,
     },
     util::{render_data, NextRuntime},
 };
@@ -196,8 +199,15 @@ fn next_ssr_client_module_transition(
             mode,
             next_config,
             execution_context,
+            ResolvePluginsVc::empty(),
+        ),
+        ssr_environment: get_server_compile_time_info(
+            project_path,
+            mode,
+            process_env,
+            server_addr,
+            next_config,
         ),
-        ssr_environment: get_server_compile_time_info(mode, process_env, server_addr),
     }
     .cell()
     .into()
@@ -215,9 +225,16 @@ fn next_server_component_transition(
 ) -> TransitionVc {
     let ty = Value::new(ServerContextType::AppRSC { app_dir });
     let mode = NextMode::Development;
-    let rsc_compile_time_info = get_server_compile_time_info(mode, process_env, server_addr);
-    let rsc_resolve_options_context =
-        get_server_resolve_options_context(project_path, ty, mode, next_config, execution_context);
+    let rsc_compile_time_info =
+        get_server_compile_time_info(project_path, mode, process_env, server_addr, next_config);
+    let rsc_resolve_options_context = get_server_resolve_options_context(
+        project_path,
+        ty,
+        mode,
+        next_config,
+        execution_context,
+        ResolvePluginsVc::empty(),
+    );
     let rsc_module_options_context =
         get_server_module_options_context(project_path, execution_context, ty, mode, next_config);
 
@@ -293,6 +310,7 @@ fn next_edge_route_transition(
         base_path: app_dir,
         bootstrap_asset: next_asset("entry/app/edge-route-bootstrap.ts"),
         entry_name: "edge".to_string(),
+        polyfills: AssetsVc::cell(vec![]),
     }
     .cell()
     .into()
@@ -447,7 +465,7 @@ fn app_context(
     let ssr_ty = Value::new(ServerContextType::AppSSR { app_dir });
     ModuleAssetContextVc::new(
         TransitionsByNameVc::cell(transitions),
-        get_server_compile_time_info(mode, env, server_addr),
+        get_server_compile_time_info(project_path, mode, env, server_addr, next_config),
         get_server_module_options_context(
             project_path,
             execution_context,
@@ -461,6 +479,7 @@ fn app_context(
             mode,
             next_config,
             execution_context,
+            ResolvePluginsVc::empty(),
         ),
     )
 }
@@ -484,6 +503,7 @@ pub async fn create_app_source(
     };
     let entrypoints = get_entrypoints(app_dir, next_config.page_extensions());
     let metadata = get_global_metadata(app_dir, next_config.page_extensions());
+    validate_global_error(app_dir, next_config.page_extensions()).await?;
 
     let client_compile_time_info =
         get_client_compile_time_info(NextMode::Development, browserslist_query);
@@ -517,9 +537,7 @@ pub async fn create_app_source(
     let env = CustomProcessEnvVc::new(env, next_config.env()).as_process_env();
 
     let server_runtime_entries =
-        AssetsVc::cell(vec![
-            ProcessEnvAssetVc::new(project_path, injected_env).into()
-        ]);
+        get_server_runtime_entries(project_path, injected_env, next_config);
 
     let fallback_page = get_fallback_page(
         project_path,
@@ -531,6 +549,10 @@ pub async fn create_app_source(
     );
     let render_data = render_data(next_config, server_addr);
 
+    let functions_config_manifest_source =
+        FunctionsConfigManifestContentSourceVc::new(entrypoints, context_ssr);
+    let cache_markers_source =
+        CacheMarkersContentSourceVc::new(project_path, entrypoints, context_ssr);
     let entrypoints = entrypoints.await?;
     let mut sources: Vec<_> = entrypoints
         .iter()
@@ -567,6 +589,8 @@ pub async fn create_app_source(
             metadata,
             server_root,
         )))
+        .chain(once(functions_config_manifest_source.into()))
+        .chain(once(cache_markers_source.into()))
         .collect();
 
     if let Some(&Entrypoint::AppPage { loader_tree }) = entrypoints.get("/") {
@@ -1053,6 +1077,9 @@ import {}, {{ chunks as {} }} from "COMPONENT_{}";
             } = &*components.await?;
             write_component(state, "page", *page)?;
             write_component(state, "defaultPage", *default)?;
+            if let Some(error) = *error {
+                validate_client_boundary(error).await?;
+            }
             write_component(state, "error", *error)?;
             write_component(state, "layout", *layout)?;
             write_component(state, "loading", *loading)?;
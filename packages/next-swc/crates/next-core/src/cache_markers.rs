@@ -0,0 +1,731 @@
+use std::collections::{BTreeMap, HashSet};
+
+use anyhow::{bail, Result};
+use mime::APPLICATION_JSON;
+use serde::{Deserialize, Serialize};
+use swc_core::ecma::{
+    ast::{
+        BlockStmtOrExpr, ClassDecl, ClassExpr, ClassMethod, Decl, DefaultDecl, ExportDecl,
+        ExportDefaultDecl, ExportDefaultExpr, Expr, ExprStmt, FnDecl, Function, Lit, MethodProp,
+        ModuleDecl, ModuleItem, Pat, Program, PropName, Stmt, VarDeclarator,
+    },
+    visit::{Visit, VisitWith},
+};
+use turbo_tasks::{
+    debug::ValueDebugFormat,
+    graph::{AdjacencyMap, GraphTraversal},
+    primitives::StringVc,
+    trace::TraceRawVcs,
+    Value,
+};
+use turbopack_binding::{
+    turbo::{
+        tasks::TryJoinIterExt,
+        tasks_fs::{File, FileSystemPathVc},
+        tasks_hash::hash_xxh3_hash64,
+    },
+    turbopack::{
+        core::{
+            asset::{Asset, AssetContentVc, AssetVc, AssetsVc},
+            context::AssetContext,
+            introspect::{Introspectable, IntrospectableVc},
+            issue::{Issue, IssueSeverity, IssueSeverityVc, IssueVc},
+            reference_type::{
+                EcmaScriptModulesReferenceSubType, EntryReferenceSubType, ReferenceType,
+            },
+            source_asset::SourceAssetVc,
+        },
+        dev_server::source::{
+            route_tree::{BaseSegment, RouteTreeVc, RouteType},
+            ContentSource, ContentSourceContentVc, ContentSourceData, GetContentSourceContent,
+            GetContentSourceContentVc,
+        },
+        ecmascript::{parse::ParseResult, EcmascriptModuleAssetVc},
+        turbopack::ModuleAssetContextVc,
+    },
+};
+
+use crate::app_structure::{Entrypoint, EntrypointsVc, LoaderTreeVc};
+
+const CACHE_MARKERS_MANIFEST_PATHNAME: &str = "_next/static/development/_cacheMarkersManifest.json";
+
+/// Returns whether the directive prologue of `stmts` contains a
+/// `"use cache"` directive.
+fn has_use_cache_directive<'a>(stmts: impl IntoIterator<Item = &'a Stmt>) -> bool {
+    stmts
+        .into_iter()
+        .map_while(|stmt| match stmt {
+            Stmt::Expr(ExprStmt { expr, .. }) => match &**expr {
+                Expr::Lit(Lit::Str(directive)) => Some(directive),
+                _ => None,
+            },
+            _ => None,
+        })
+        .any(|directive| &*directive.value == "use cache")
+}
+
+fn is_cached_function(function: &Function) -> bool {
+    function
+        .body
+        .as_ref()
+        .map_or(false, |body| has_use_cache_directive(&body.stmts))
+}
+
+/// Returns whether `expr` is a function or arrow function, and whether its
+/// body starts with a `"use cache"` directive.
+fn function_expr(expr: &Expr) -> Option<bool> {
+    match expr {
+        Expr::Paren(paren) => function_expr(&paren.expr),
+        Expr::Fn(function) => Some(is_cached_function(&function.function)),
+        Expr::Arrow(arrow) => Some(match &*arrow.body {
+            BlockStmtOrExpr::BlockStmt(body) => has_use_cache_directive(&body.stmts),
+            BlockStmtOrExpr::Expr(_) => false,
+        }),
+        _ => None,
+    }
+}
+
+fn prop_name(name: &PropName) -> Option<String> {
+    match name {
+        PropName::Ident(ident) => Some(ident.sym.to_string()),
+        PropName::Str(name) => Some(name.value.to_string()),
+        _ => None,
+    }
+}
+
+/// Collects the names of the functions with a `"use cache"` directive of their
+/// own. Methods are named after their class, like `Posts.load`, and default
+/// exports `default`.
+#[derive(Default)]
+struct CachedFunctionsVisitor {
+    /// The names of the classes the visitor is in, innermost last.
+    classes: Vec<Option<String>>,
+    functions: Vec<String>,
+}
+
+impl Visit for CachedFunctionsVisitor {
+    fn visit_fn_decl(&mut self, decl: &FnDecl) {
+        if is_cached_function(&decl.function) {
+            self.functions.push(decl.ident.sym.to_string());
+        }
+        decl.visit_children_with(self);
+    }
+
+    fn visit_var_declarator(&mut self, declarator: &VarDeclarator) {
+        if let (Pat::Ident(name), Some(init)) = (&declarator.name, &declarator.init) {
+            if function_expr(init) == Some(true) {
+                self.functions.push(name.id.sym.to_string());
+            }
+        }
+        declarator.visit_children_with(self);
+    }
+
+    fn visit_export_default_decl(&mut self, decl: &ExportDefaultDecl) {
+        if let DefaultDecl::Fn(function) = &decl.decl {
+            if is_cached_function(&function.function) {
+                self.functions.push("default".to_string());
+            }
+        }
+        decl.visit_children_with(self);
+    }
+
+    fn visit_export_default_expr(&mut self, expr: &ExportDefaultExpr) {
+        if function_expr(&expr.expr) == Some(true) {
+            self.functions.push("default".to_string());
+        }
+        expr.visit_children_with(self);
+    }
+
+    fn visit_class_decl(&mut self, decl: &ClassDecl) {
+        self.classes.push(Some(decl.ident.sym.to_string()));
+        decl.visit_children_with(self);
+        self.classes.pop();
+    }
+
+    fn visit_class_expr(&mut self, expr: &ClassExpr) {
+        self.classes
+            .push(expr.ident.as_ref().map(|ident| ident.sym.to_string()));
+        expr.visit_children_with(self);
+        self.classes.pop();
+    }
+
+    fn visit_class_method(&mut self, method: &ClassMethod) {
+        if is_cached_function(&method.function) {
+            if let Some(name) = prop_name(&method.key) {
+                self.functions.push(match self.classes.last() {
+                    Some(Some(class)) => format!("{class}.{name}"),
+                    _ => name,
+                });
+            }
+        }
+        method.visit_children_with(self);
+    }
+
+    fn visit_method_prop(&mut self, method: &MethodProp) {
+        if is_cached_function(&method.function) {
+            self.functions.extend(prop_name(&method.key));
+        }
+        method.visit_children_with(self);
+    }
+}
+
+/// Returns the names of the exported functions of `items`, which are all
+/// cached by a file-level `"use cache"` directive.
+fn exported_functions(items: &[ModuleItem]) -> Vec<String> {
+    let mut functions = Vec::new();
+    for item in items {
+        match item {
+            ModuleItem::ModuleDecl(ModuleDecl::ExportDecl(ExportDecl { decl, .. })) => match decl {
+                Decl::Fn(decl) => functions.push(decl.ident.sym.to_string()),
+                Decl::Var(var) => {
+                    for declarator in &var.decls {
+                        if let (Pat::Ident(name), Some(init)) = (&declarator.name, &declarator.init)
+                        {
+                            if function_expr(init).is_some() {
+                                functions.push(name.id.sym.to_string());
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            },
+            ModuleItem::ModuleDecl(ModuleDecl::ExportDefaultDecl(ExportDefaultDecl {
+                decl: DefaultDecl::Fn(_),
+                ..
+            })) => functions.push("default".to_string()),
+            ModuleItem::ModuleDecl(ModuleDecl::ExportDefaultExpr(expr))
+                if function_expr(&expr.expr).is_some() =>
+            {
+                functions.push("default".to_string())
+            }
+            _ => {}
+        }
+    }
+    functions
+}
+
+/// Returns the names of the functions in `program` which are marked with a
+/// `"use cache"` directive, either their own or a file-level one.
+fn cached_functions(program: &Program) -> Vec<String> {
+    let mut visitor = CachedFunctionsVisitor::default();
+    program.visit_with(&mut visitor);
+    let mut functions = visitor.functions;
+    if let Program::Module(module) = program {
+        let file_level = has_use_cache_directive(module.body.iter().map_while(ModuleItem::as_stmt));
+        if file_level {
+            functions.extend(exported_functions(&module.body));
+        }
+    }
+    let mut seen = HashSet::new();
+    functions.retain(|name| seen.insert(name.clone()));
+    functions
+}
+
+/// A function marked with a `"use cache"` directive.
+#[derive(
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    TraceRawVcs,
+    ValueDebugFormat,
+    Debug,
+)]
+pub struct CacheMarker {
+    /// The id the function is referenced by at runtime.
+    pub id: String,
+    /// The path of the module defining the function, relative to the project
+    /// root.
+    pub module: String,
+    /// The name of the function.
+    pub name: String,
+}
+
+impl CacheMarker {
+    fn new(module: &str, name: &str) -> Self {
+        CacheMarker {
+            id: format!(
+                "{:016x}",
+                hash_xxh3_hash64(format!("{module}#{name}").as_str())
+            ),
+            module: module.to_string(),
+            name: name.to_string(),
+        }
+    }
+}
+
+#[turbo_tasks::value(transparent)]
+pub struct CacheMarkers(Vec<CacheMarker>);
+
+/// An entry of the cache markers manifest.
+#[derive(Debug, PartialEq, Serialize)]
+struct CacheMarkerEntry<'a> {
+    module: &'a str,
+    name: &'a str,
+}
+
+/// Returns the cache markers manifest mapping the ids of the `markers` to the
+/// functions they reference, and the pairs of markers whose ids collide. The
+/// same function found twice is only listed once, and of colliding markers
+/// the first one is kept.
+fn cache_markers_manifest(
+    markers: &[CacheMarker],
+) -> (
+    BTreeMap<&str, CacheMarkerEntry<'_>>,
+    Vec<(&CacheMarker, &CacheMarker)>,
+) {
+    let mut manifest = BTreeMap::new();
+    let mut kept = BTreeMap::new();
+    let mut collisions = Vec::new();
+    for marker in markers {
+        match kept.get(marker.id.as_str()) {
+            Some(&existing) if existing == marker => {}
+            Some(&existing) => collisions.push((existing, marker)),
+            None => {
+                kept.insert(marker.id.as_str(), marker);
+                manifest.insert(
+                    marker.id.as_str(),
+                    CacheMarkerEntry {
+                        module: &marker.module,
+                        name: &marker.name,
+                    },
+                );
+            }
+        }
+    }
+    (manifest, collisions)
+}
+
+/// Returns the functions marked with `"use cache"` in `module`, if it is an
+/// ecmascript module of the project.
+#[turbo_tasks::function]
+async fn module_cache_markers(
+    project_path: FileSystemPathVc,
+    module: AssetVc,
+) -> Result<CacheMarkersVc> {
+    let Some(ecmascript_asset) = EcmascriptModuleAssetVc::resolve_from(module).await? else {
+        return Ok(CacheMarkersVc::cell(vec![]));
+    };
+    let ParseResult::Ok { program, .. } = &*ecmascript_asset.parse().await? else {
+        return Ok(CacheMarkersVc::cell(vec![]));
+    };
+    let project_path = project_path.await?;
+    let path = module.ident().path().await?;
+    let Some(module) = project_path.get_path_to(&path) else {
+        return Ok(CacheMarkersVc::cell(vec![]));
+    };
+    Ok(CacheMarkersVc::cell(
+        cached_functions(program)
+            .iter()
+            .map(|name| CacheMarker::new(module, name))
+            .collect(),
+    ))
+}
+
+/// Returns the modules referenced by `module`. The graph is only followed
+/// through ecmascript modules, so it stops at the boundaries to client
+/// components and at other assets.
+async fn get_referenced_modules(module: AssetVc) -> Result<Vec<AssetVc>> {
+    if EcmascriptModuleAssetVc::resolve_from(module)
+        .await?
+        .is_none()
+    {
+        return Ok(vec![]);
+    }
+    Ok(module
+        .references()
+        .await?
+        .iter()
+        .map(|reference| async move {
+            Ok(reference
+                .resolve_reference()
+                .primary_assets()
+                .await?
+                .clone_value())
+        })
+        .try_join()
+        .await?
+        .into_iter()
+        .flatten()
+        .collect())
+}
+
+/// Finds the functions marked with `"use cache"` in the modules of the server
+/// graph reachable from `entries`.
+#[turbo_tasks::function]
+async fn find_cache_markers(
+    project_path: FileSystemPathVc,
+    entries: AssetsVc,
+) -> Result<CacheMarkersVc> {
+    let modules = AdjacencyMap::new()
+        .skip_duplicates()
+        .visit(entries.await?.iter().copied(), get_referenced_modules)
+        .await
+        .completed()?
+        .into_inner()
+        .into_reverse_topological()
+        .collect::<Vec<_>>();
+    let module_markers = modules
+        .into_iter()
+        .map(|module| module_cache_markers(project_path, module))
+        .try_join()
+        .await?;
+    let mut markers = module_markers
+        .iter()
+        .flat_map(|markers| markers.iter().cloned())
+        .collect::<Vec<_>>();
+    // Ensure deterministic order since the traversal is not deterministic
+    markers.sort();
+    Ok(CacheMarkersVc::cell(markers))
+}
+
+/// Returns the modules of the components of `loader_tree` and its parallel
+/// routes, processed in `context`.
+#[turbo_tasks::function]
+async fn loader_tree_modules(
+    loader_tree: LoaderTreeVc,
+    context: ModuleAssetContextVc,
+) -> Result<AssetsVc> {
+    let loader_tree = loader_tree.await?;
+    let components = loader_tree.components.await?;
+    let mut modules = [
+        components.page,
+        components.default,
+        components.layout,
+        components.error,
+        components.loading,
+        components.template,
+        components.not_found,
+    ]
+    .into_iter()
+    .flatten()
+    .map(|component| {
+        context.process(
+            SourceAssetVc::new(component).into(),
+            Value::new(ReferenceType::EcmaScriptModules(
+                EcmaScriptModulesReferenceSubType::Undefined,
+            )),
+        )
+    })
+    .collect::<Vec<_>>();
+    let parallel_modules = loader_tree
+        .parallel_routes
+        .values()
+        .map(|tree| loader_tree_modules(*tree, context))
+        .try_join()
+        .await?;
+    for parallel in parallel_modules {
+        modules.extend(parallel.iter().copied());
+    }
+    Ok(AssetsVc::cell(modules))
+}
+
+/// A content source serving `_cacheMarkersManifest.json`, which maps the ids
+/// of the functions marked with `"use cache"` in the server graph of the app
+/// directory to the modules and names of the functions, like the server
+/// actions manifest does for server actions.
+#[turbo_tasks::value(shared)]
+pub struct CacheMarkersContentSource {
+    project_path: FileSystemPathVc,
+    entrypoints: EntrypointsVc,
+    context: ModuleAssetContextVc,
+}
+
+#[turbo_tasks::value_impl]
+impl CacheMarkersContentSourceVc {
+    #[turbo_tasks::function]
+    pub fn new(
+        project_path: FileSystemPathVc,
+        entrypoints: EntrypointsVc,
+        context: ModuleAssetContextVc,
+    ) -> Self {
+        CacheMarkersContentSource {
+            project_path,
+            entrypoints,
+            context,
+        }
+        .cell()
+    }
+
+    #[turbo_tasks::function]
+    async fn markers(self) -> Result<CacheMarkersVc> {
+        let this = self.await?;
+        let context = this.context;
+        let mut entries = Vec::new();
+        for entrypoint in this.entrypoints.await?.values() {
+            match *entrypoint {
+                Entrypoint::AppPage { loader_tree } => entries.extend(
+                    loader_tree_modules(loader_tree, context)
+                        .await?
+                        .iter()
+                        .copied(),
+                ),
+                Entrypoint::AppRoute { path } => entries.push(context.process(
+                    SourceAssetVc::new(path).into(),
+                    Value::new(ReferenceType::Entry(EntryReferenceSubType::AppRoute)),
+                )),
+            }
+        }
+        Ok(find_cache_markers(
+            this.project_path,
+            AssetsVc::cell(entries),
+        ))
+    }
+}
+
+#[turbo_tasks::value_impl]
+impl ContentSource for CacheMarkersContentSource {
+    #[turbo_tasks::function]
+    fn get_routes(self_vc: CacheMarkersContentSourceVc) -> RouteTreeVc {
+        RouteTreeVc::new_route(
+            BaseSegment::from_static_pathname(CACHE_MARKERS_MANIFEST_PATHNAME).collect(),
+            RouteType::Exact,
+            self_vc.into(),
+        )
+    }
+}
+
+#[turbo_tasks::value_impl]
+impl GetContentSourceContent for CacheMarkersContentSource {
+    #[turbo_tasks::function]
+    async fn get(
+        self_vc: CacheMarkersContentSourceVc,
+        path: &str,
+        _data: Value<ContentSourceData>,
+    ) -> Result<ContentSourceContentVc> {
+        if path != CACHE_MARKERS_MANIFEST_PATHNAME {
+            bail!("unknown path: {}", path);
+        }
+
+        let markers = self_vc.markers().await?;
+        let (manifest, collisions) = cache_markers_manifest(&*markers);
+        for (kept, removed) in collisions {
+            CacheMarkerCollisionIssue {
+                path: self_vc.await?.project_path,
+                id: kept.id.clone(),
+                kept: format!("{} in {}", kept.name, kept.module),
+                removed: format!("{} in {}", removed.name, removed.module),
+            }
+            .cell()
+            .as_issue()
+            .emit();
+        }
+
+        let file =
+            File::from(serde_json::to_string(&manifest)?).with_content_type(APPLICATION_JSON);
+        Ok(ContentSourceContentVc::static_content(
+            AssetContentVc::from(file).into(),
+        ))
+    }
+}
+
+#[turbo_tasks::value_impl]
+impl Introspectable for CacheMarkersContentSource {
+    #[turbo_tasks::function]
+    fn ty(&self) -> StringVc {
+        StringVc::cell("cache markers source".to_string())
+    }
+
+    #[turbo_tasks::function]
+    fn details(&self) -> StringVc {
+        StringVc::cell(
+            "provides _cacheMarkersManifest.json, which maps the ids of functions marked with \
+             \"use cache\" to their modules."
+                .to_string(),
+        )
+    }
+}
+
+#[turbo_tasks::value(shared)]
+struct CacheMarkerCollisionIssue {
+    path: FileSystemPathVc,
+    id: String,
+    kept: String,
+    removed: String,
+}
+
+#[turbo_tasks::value_impl]
+impl Issue for CacheMarkerCollisionIssue {
+    #[turbo_tasks::function]
+    fn severity(&self) -> IssueSeverityVc {
+        IssueSeverity::Error.into()
+    }
+
+    #[turbo_tasks::function]
+    fn category(&self) -> StringVc {
+        StringVc::cell("cache".to_string())
+    }
+
+    #[turbo_tasks::function]
+    fn context(&self) -> FileSystemPathVc {
+        self.path
+    }
+
+    #[turbo_tasks::function]
+    fn title(&self) -> StringVc {
+        StringVc::cell(format!("Cached functions share the id {}", self.id))
+    }
+
+    #[turbo_tasks::function]
+    fn description(&self) -> StringVc {
+        StringVc::cell(format!(
+            "{} and {} are both marked with \"use cache\" and have the same id, so only {} is \
+             listed in the cache markers manifest. Rename one of the functions.",
+            self.kept, self.removed, self.kept
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use anyhow::Result;
+    use turbo_tasks::Value;
+    use turbopack_binding::turbopack::{
+        core::{
+            asset::AssetsVc, compile_time_info::CompileTimeInfo, context::AssetContext,
+            reference_type::ReferenceType, source_asset::SourceAssetVc,
+        },
+        turbopack::{
+            evaluate_context::node_build_environment, module_options::ModuleOptionsContext,
+            resolve_options_context::ResolveOptionsContext, transition::TransitionsByNameVc,
+            ModuleAssetContextVc,
+        },
+    };
+
+    use super::{cache_markers_manifest, find_cache_markers, CacheMarker};
+    use crate::test_util::run_in_project;
+
+    #[tokio::test]
+    async fn test_find_cache_markers() -> Result<()> {
+        run_in_project(
+            &[
+                (
+                    "app/page.js",
+                    r#"
+                import { getPosts } from '../lib/data.ts'
+                import getTags from '../lib/tags.js'
+
+                // function getComments() { 'use cache' }
+                const source = "function getLikes() { 'use cache' }"
+
+                export default async function Page() {
+                  return getPosts(await getTags())
+                }
+                "#,
+                ),
+                (
+                    "lib/data.ts",
+                    r#"
+                export async function getPosts(filter = normalize(')')) {
+                  'use cache'
+                  return fetch(`/posts?${filter}`)
+                }
+
+                export const getUser = async (id: string): Promise<User> => {
+                  "use cache";
+                  return fetch(`/users/${id}`)
+                }
+
+                export class Posts {
+                  static async load() {
+                    'use cache'
+                    return getPosts()
+                  }
+                }
+
+                export const api = {
+                  async prefetch() {
+                    'use cache'
+                  },
+                }
+
+                export async function getComments() {
+                  return fetch('/comments')
+                }
+                "#,
+                ),
+                (
+                    "lib/tags.js",
+                    r#"
+                'use cache'
+
+                export default async function () {
+                  return getTags()
+                }
+
+                export async function getTags() {
+                  return fetch('/tags')
+                }
+
+                async function helper() {}
+                "#,
+                ),
+            ],
+            |root| async move {
+                let context = ModuleAssetContextVc::new(
+                    TransitionsByNameVc::cell(HashMap::new()),
+                    CompileTimeInfo::builder(node_build_environment()).cell(),
+                    ModuleOptionsContext::default().cell(),
+                    ResolveOptionsContext::default().cell(),
+                );
+                let page = context.process(
+                    SourceAssetVc::new(root.join("app/page.js")).into(),
+                    Value::new(ReferenceType::Undefined),
+                );
+
+                let markers = find_cache_markers(root, AssetsVc::cell(vec![page])).await?;
+                let mut functions = markers
+                    .iter()
+                    .map(|marker| (marker.module.as_str(), marker.name.as_str()))
+                    .collect::<Vec<_>>();
+                functions.sort();
+                // Functions in comments and strings aren't cached, and the imported
+                // modules outside of the app directory are scanned too.
+                assert_eq!(
+                    functions,
+                    [
+                        ("lib/data.ts", "Posts.load"),
+                        ("lib/data.ts", "getPosts"),
+                        ("lib/data.ts", "getUser"),
+                        ("lib/data.ts", "prefetch"),
+                        ("lib/tags.js", "default"),
+                        ("lib/tags.js", "getTags"),
+                    ]
+                );
+                Ok(())
+            },
+        )
+        .await
+    }
+
+    #[test]
+    fn test_cache_markers_manifest() {
+        let markers = [
+            CacheMarker::new("app/data.ts", "getPosts"),
+            CacheMarker::new("app/data.ts", "getUser"),
+            CacheMarker::new("app/data.ts", "getPosts"),
+        ];
+        let (manifest, collisions) = cache_markers_manifest(&markers);
+        assert!(collisions.is_empty());
+        // Two cached functions get distinct ids, the duplicate is listed once.
+        assert_ne!(markers[0].id, markers[1].id);
+        assert_eq!(manifest.len(), 2);
+        assert_eq!(manifest[markers[0].id.as_str()].name, "getPosts");
+        assert_eq!(manifest[markers[1].id.as_str()].name, "getUser");
+
+        let colliding = CacheMarker {
+            id: markers[0].id.clone(),
+            module: "app/other.ts".to_string(),
+            name: "getPosts".to_string(),
+        };
+        let markers = [markers[0].clone(), colliding];
+        let (manifest, collisions) = cache_markers_manifest(&markers);
+        assert_eq!(manifest.len(), 1);
+        assert_eq!(collisions, [(&markers[0], &markers[1])]);
+    }
+}
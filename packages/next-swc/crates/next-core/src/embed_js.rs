@@ -11,17 +11,47 @@ pub(crate) fn next_js_fs() -> FileSystemVc {
     turbo_tasks_fs::embed_directory!("next", "$CARGO_MANIFEST_DIR/js/src")
 }
 
+/// Returns the root of the file system embedding the internal Next.js code,
+/// like the entries and runtime modules. Modules under this path are internal
+/// code, as opposed to user code or packages from `node_modules`.
+#[turbo_tasks::function]
+pub fn internal_code_root() -> FileSystemPathVc {
+    next_js_fs().root()
+}
+
 #[turbo_tasks::function]
 pub(crate) fn next_js_file(path: &str) -> FileContentVc {
-    next_js_fs().root().join(path).read()
+    internal_code_root().join(path).read()
 }
 
 #[turbo_tasks::function]
 pub(crate) fn next_js_file_path(path: &str) -> FileSystemPathVc {
-    next_js_fs().root().join(path)
+    internal_code_root().join(path)
 }
 
 #[turbo_tasks::function]
 pub(crate) fn next_asset(path: &str) -> AssetVc {
     SourceAssetVc::new(next_js_file_path(path)).into()
 }
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use turbopack_binding::turbo::{tasks::TurboTasks, tasks_memory::MemoryBackend};
+
+    use super::{internal_code_root, next_js_file_path};
+
+    #[tokio::test]
+    async fn test_internal_code_root() -> Result<()> {
+        crate::register();
+        let tt = TurboTasks::new(MemoryBackend::default());
+        tt.run_once(async move {
+            let root = internal_code_root().await?;
+            let path = next_js_file_path("entry/edge-bootstrap.ts").await?;
+            assert!(path.is_inside(&root));
+            assert_eq!(path.path, "entry/edge-bootstrap.ts");
+            Ok(())
+        })
+        .await
+    }
+}
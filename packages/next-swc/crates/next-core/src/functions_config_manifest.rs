@@ -0,0 +1,284 @@
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Result};
+use mime::APPLICATION_JSON;
+use serde::Serialize;
+use turbo_tasks::{primitives::StringVc, TryJoinIterExt, Value};
+use turbopack_binding::{
+    turbo::tasks_fs::File,
+    turbopack::{
+        core::{
+            asset::AssetContentVc,
+            context::AssetContext,
+            introspect::{Introspectable, IntrospectableVc},
+            reference_type::{EntryReferenceSubType, ReferenceType},
+            source_asset::SourceAssetVc,
+        },
+        dev_server::source::{
+            route_tree::{BaseSegment, RouteTreeVc, RouteType},
+            ContentSource, ContentSourceContentVc, ContentSourceData, ContentSourceVc,
+            GetContentSourceContent, GetContentSourceContentVc,
+        },
+        turbopack::ModuleAssetContextVc,
+    },
+};
+
+use crate::{
+    app_segment_config::{
+        parse_segment_config_from_loader_tree, parse_segment_config_from_source, NextSegmentConfig,
+    },
+    app_structure::{Entrypoint, EntrypointsVc},
+    util::NextRuntime,
+};
+
+const FUNCTIONS_CONFIG_MANIFEST_PATHNAME: &str =
+    "_next/static/development/functions-config-manifest.json";
+
+/// The `functions-config-manifest.json`, which deployment platforms read the
+/// runtime configuration of app pages and routes from.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct FunctionsConfigManifest {
+    pub version: u32,
+    /// The configs by page, like `/api/hello/route`.
+    pub functions: BTreeMap<String, FunctionConfig>,
+}
+
+#[derive(Serialize, Default, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FunctionConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub runtime: Option<NextRuntime>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub regions: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_duration: Option<u32>,
+}
+
+impl FunctionConfig {
+    /// Returns the function config of a segment config, or `None` if it
+    /// doesn't export any of the options, so the platform's defaults apply.
+    pub fn from_segment_config(config: &NextSegmentConfig) -> Option<Self> {
+        let function_config = FunctionConfig {
+            runtime: config.runtime,
+            regions: config.preferred_region.clone(),
+            max_duration: config.max_duration,
+        };
+        (function_config != FunctionConfig::default()).then_some(function_config)
+    }
+}
+
+/// Returns the page an app entrypoint for `pathname` is keyed by, e.g.
+/// `/blog/page` or `/api/hello/route`.
+fn function_page(pathname: &str, entrypoint: &Entrypoint) -> String {
+    let suffix = match entrypoint {
+        Entrypoint::AppPage { .. } => "page",
+        Entrypoint::AppRoute { .. } => "route",
+    };
+    format!("{}/{}", pathname.trim_end_matches('/'), suffix)
+}
+
+impl FunctionsConfigManifest {
+    /// Creates the manifest from the function config of each page, skipping
+    /// pages without one.
+    pub fn new(configs: impl IntoIterator<Item = (String, Option<FunctionConfig>)>) -> Self {
+        FunctionsConfigManifest {
+            version: 1,
+            functions: configs
+                .into_iter()
+                .filter_map(|(page, config)| Some((page, config?)))
+                .collect(),
+        }
+    }
+}
+
+/// A content source serving the `functions-config-manifest.json` of the app
+/// directory, built from the segment configs exported by its pages and routes.
+#[turbo_tasks::value(shared)]
+pub struct FunctionsConfigManifestContentSource {
+    entrypoints: EntrypointsVc,
+    context: ModuleAssetContextVc,
+}
+
+#[turbo_tasks::value_impl]
+impl FunctionsConfigManifestContentSourceVc {
+    #[turbo_tasks::function]
+    pub fn new(entrypoints: EntrypointsVc, context: ModuleAssetContextVc) -> Self {
+        FunctionsConfigManifestContentSource {
+            entrypoints,
+            context,
+        }
+        .cell()
+    }
+
+    #[turbo_tasks::function]
+    async fn manifest(self) -> Result<StringVc> {
+        let this = self.await?;
+        let context = this.context;
+        let configs = this
+            .entrypoints
+            .await?
+            .iter()
+            .map(|(pathname, entrypoint)| async move {
+                let config = match *entrypoint {
+                    Entrypoint::AppPage { loader_tree } => {
+                        parse_segment_config_from_loader_tree(loader_tree, context.into())
+                    }
+                    Entrypoint::AppRoute { path } => {
+                        parse_segment_config_from_source(context.process(
+                            SourceAssetVc::new(path).into(),
+                            Value::new(ReferenceType::Entry(EntryReferenceSubType::AppRoute)),
+                        ))
+                    }
+                }
+                .await?;
+                Ok((
+                    function_page(pathname, entrypoint),
+                    FunctionConfig::from_segment_config(&config),
+                ))
+            })
+            .try_join()
+            .await?;
+
+        Ok(StringVc::cell(serde_json::to_string(
+            &FunctionsConfigManifest::new(configs),
+        )?))
+    }
+}
+
+#[turbo_tasks::value_impl]
+impl ContentSource for FunctionsConfigManifestContentSource {
+    #[turbo_tasks::function]
+    fn get_routes(self_vc: FunctionsConfigManifestContentSourceVc) -> RouteTreeVc {
+        RouteTreeVc::new_route(
+            BaseSegment::from_static_pathname(FUNCTIONS_CONFIG_MANIFEST_PATHNAME).collect(),
+            RouteType::Exact,
+            self_vc.into(),
+        )
+    }
+}
+
+#[turbo_tasks::value_impl]
+impl GetContentSourceContent for FunctionsConfigManifestContentSource {
+    #[turbo_tasks::function]
+    async fn get(
+        self_vc: FunctionsConfigManifestContentSourceVc,
+        path: &str,
+        _data: Value<ContentSourceData>,
+    ) -> Result<ContentSourceContentVc> {
+        if path != FUNCTIONS_CONFIG_MANIFEST_PATHNAME {
+            bail!("unknown path: {}", path);
+        }
+
+        let manifest = self_vc.manifest().await?;
+        let manifest_file = File::from(manifest.as_str()).with_content_type(APPLICATION_JSON);
+
+        Ok(ContentSourceContentVc::static_content(
+            AssetContentVc::from(manifest_file).into(),
+        ))
+    }
+}
+
+#[turbo_tasks::value_impl]
+impl Introspectable for FunctionsConfigManifestContentSource {
+    #[turbo_tasks::function]
+    fn ty(&self) -> StringVc {
+        StringVc::cell("functions config manifest source".to_string())
+    }
+
+    #[turbo_tasks::function]
+    fn details(&self) -> StringVc {
+        StringVc::cell(
+            "provides functions-config-manifest.json with the runtime, preferredRegion and \
+             maxDuration exported by app pages and routes."
+                .to_string(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use anyhow::Result;
+    use serde_json::json;
+    use turbo_tasks::Value;
+    use turbopack_binding::turbopack::{
+        core::{
+            compile_time_info::CompileTimeInfo,
+            context::AssetContext,
+            reference_type::{EntryReferenceSubType, ReferenceType},
+            source_asset::SourceAssetVc,
+        },
+        turbopack::{
+            evaluate_context::node_build_environment, module_options::ModuleOptionsContext,
+            resolve_options_context::ResolveOptionsContext, transition::TransitionsByNameVc,
+            ModuleAssetContextVc,
+        },
+    };
+
+    use super::{FunctionConfig, FunctionsConfigManifest};
+    use crate::{
+        app_segment_config::{parse_segment_config_from_source, NextSegmentConfig},
+        test_util::run_in_project,
+    };
+
+    #[test]
+    fn test_preferred_region() {
+        let manifest = FunctionsConfigManifest::new([
+            (
+                "/api/hello/route".to_string(),
+                FunctionConfig::from_segment_config(&NextSegmentConfig {
+                    preferred_region: Some(vec!["iad1".to_string()]),
+                    ..Default::default()
+                }),
+            ),
+            (
+                "/blog/page".to_string(),
+                FunctionConfig::from_segment_config(&NextSegmentConfig::default()),
+            ),
+        ]);
+
+        assert_eq!(
+            serde_json::to_value(&manifest).unwrap(),
+            json!({
+                "version": 1,
+                "functions": {
+                    "/api/hello/route": { "regions": ["iad1"] },
+                },
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_preferred_region_array() -> Result<()> {
+        run_in_project(
+            &[(
+                "route.js",
+                "export const preferredRegion = ['iad1', 'sfo1']\nexport function GET() {}\n",
+            )],
+            |root| async move {
+                let context = ModuleAssetContextVc::new(
+                    TransitionsByNameVc::cell(HashMap::new()),
+                    CompileTimeInfo::builder(node_build_environment()).cell(),
+                    ModuleOptionsContext::default().cell(),
+                    ResolveOptionsContext::default().cell(),
+                );
+                let config = parse_segment_config_from_source(context.process(
+                    SourceAssetVc::new(root.join("route.js")).into(),
+                    Value::new(ReferenceType::Entry(EntryReferenceSubType::AppRoute)),
+                ))
+                .await?;
+
+                assert_eq!(
+                    FunctionConfig::from_segment_config(&config),
+                    Some(FunctionConfig {
+                        regions: Some(vec!["iad1".to_string(), "sfo1".to_string()]),
+                        ..Default::default()
+                    })
+                );
+                Ok(())
+            },
+        )
+        .await
+    }
+}
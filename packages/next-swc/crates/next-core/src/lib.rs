@@ -3,15 +3,18 @@
 #![feature(box_syntax)]
 #![feature(str_split_remainder)]
 
+mod app_client_boundary;
 mod app_render;
 mod app_segment_config;
 mod app_source;
 pub mod app_structure;
 mod babel;
 mod bootstrap;
+pub mod cache_markers;
 mod embed_js;
 pub mod env;
 mod fallback;
+mod functions_config_manifest;
 pub mod manifest;
 pub mod mode;
 mod next_build;
@@ -26,19 +29,25 @@ mod next_import_map;
 mod next_route_matcher;
 pub mod next_server;
 pub mod next_shared;
+pub mod optimize_css;
 mod page_loader;
 mod page_source;
 pub mod pages_structure;
+pub mod resolve_conditions_source;
 pub mod router;
 pub mod router_source;
 mod runtime;
 mod sass;
+#[cfg(test)]
+mod test_util;
 mod transform_options;
 pub mod url_node;
 mod util;
 mod web_entry_source;
 
 pub use app_source::create_app_source;
+pub use embed_js::internal_code_root;
+pub use next_route_matcher::matcher_source::{matcher_source_regex, matcher_source_to_regex};
 pub use page_loader::create_page_loader_entry_asset;
 pub use page_source::create_page_source;
 pub use turbopack_binding::{turbopack::node::source_map, *};
@@ -1,107 +1,1084 @@
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap, HashSet},
+    future::Future,
+    hash::Hash,
+};
+
 use anyhow::{bail, Context, Result};
+use futures::{
+    future::try_join,
+    stream::{self, FuturesUnordered, StreamExt, TryStreamExt},
+};
 use indexmap::IndexMap;
 use mime::{APPLICATION_JAVASCRIPT_UTF_8, APPLICATION_JSON};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use swc_core::ecma::ast::{Decl, ExportSpecifier, Module, ModuleDecl, ModuleExportName, Program};
+use tracing::Instrument;
 use turbo_tasks::{
-    graph::{GraphTraversal, NonDeterministic},
-    primitives::{StringReadRef, StringVc, StringsVc},
+    debug::ValueDebugFormat,
+    primitives::{BoolVc, StringReadRef, StringVc, StringsVc},
+    trace::TraceRawVcs,
+    Value,
 };
 use turbopack_binding::{
-    turbo::{tasks::TryJoinIterExt, tasks_fs::File},
+    turbo::{
+        tasks::TryJoinIterExt,
+        tasks_fs::{File, FileSystemEntryType, FileSystemPathVc},
+        tasks_hash::hash_xxh3_hash64,
+    },
     turbopack::{
         core::{
             asset::AssetContentVc,
+            compile_time_info::CompileTimeInfo,
+            context::{AssetContext, AssetContextVc},
             introspect::{Introspectable, IntrospectableVc},
+            issue::{Issue, IssueSeverity, IssueSeverityVc, IssueVc},
+            reference_type::ReferenceType,
+            source_asset::SourceAssetVc,
         },
         dev_server::source::{
             route_tree::{BaseSegment, RouteTreeVc, RouteTreesVc, RouteType},
-            ContentSource, ContentSourceContentVc, ContentSourceData, ContentSourceVc,
-            GetContentSourceContent, GetContentSourceContentVc,
+            ContentSource, ContentSourceContent, ContentSourceContentVc, ContentSourceData,
+            ContentSourceVc, GetContentSourceContent, GetContentSourceContentVc,
         },
+        ecmascript::{parse::ParseResult, EcmascriptModuleAssetVc},
         node::render::{
             node_api_source::NodeApiContentSourceVc, rendered_source::NodeRenderContentSourceVc,
         },
+        turbopack::{
+            evaluate_context::node_build_environment, module_options::ModuleOptionsContext,
+            resolve_options_context::ResolveOptionsContext, transition::TransitionsByNameVc,
+            ModuleAssetContextVc,
+        },
     },
 };
 
 use crate::{
+    app_segment_config::{parse_segment_config_from_source, NextSegmentDynamic},
+    app_structure::OptionAppDirVc,
     embed_js::next_js_file,
-    next_config::{NextConfigVc, RewritesReadRef},
-    util::get_asset_path_from_pathname,
+    next_config::{Header, ManifestChunkValidation, NextConfigVc, Redirect, Rewrites, RouteHas},
+    page_loader::page_loader_path,
+    pages_structure::PagesStructureVc,
+    util::{get_asset_path_from_pathname, join_asset_url},
 };
 
 /// A content source which creates the next.js `_devPagesManifest.json` and
 /// `_devMiddlewareManifest.json` which are used for client side navigation.
 #[turbo_tasks::value(shared)]
+#[derive(Clone)]
 pub struct DevManifestContentSource {
-    pub page_roots: Vec<ContentSourceVc>,
-    pub next_config: NextConfigVc,
+    /// The project root, which issues about the routes are reported for.
+    project_path: FileSystemPathVc,
+    page_roots: Vec<ContentSourceVc>,
+    app_roots: Vec<ContentSourceVc>,
+    /// The pages the page routes are defined by, to find the file of a route.
+    pages_structure: PagesStructureVc,
+    /// The directory the app routes are defined in, to find the file of a
+    /// route.
+    app_dir: OptionAppDirVc,
+    next_config: NextConfigVc,
+    /// The build id the `_buildManifest.js` is served for, see
+    /// [DEV_BUILD_ID].
+    build_id: String,
+    /// Identifies the dev server session the manifests are served for. It's
+    /// part of the manifest version, so a client which still references the
+    /// manifests of an earlier session always gets them rebuilt.
+    session: String,
+}
+
+/// The router a route in the manifest was defined by.
+#[derive(
+    Copy,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+    TraceRawVcs,
+    ValueDebugFormat,
+    Debug,
+)]
+pub enum RouteOrigin {
+    Pages,
+    App,
+}
+
+impl RouteOrigin {
+    /// Returns the URL pathname of a route of this origin which content
+    /// sources serve at `pathname`.
+    fn normalize_pathname(self, pathname: &str) -> Cow<'_, str> {
+        match self {
+            RouteOrigin::Pages => Cow::Borrowed(pathname),
+            RouteOrigin::App => normalize_app_pathname(pathname),
+        }
+    }
+}
+
+/// Removes the App Router segments which aren't part of the URL from
+/// `pathname`: route groups like `(marketing)` and parallel route slots like
+/// `@modal`. Intercepting routes like `(.)photo` keep their marker, which is
+/// part of the segment rather than a group of its own.
+fn normalize_app_pathname(pathname: &str) -> Cow<'_, str> {
+    let is_hidden = |segment: &str| {
+        segment.starts_with('@')
+            || (segment.starts_with('(') && segment.ends_with(')') && !segment.starts_with("(."))
+    };
+    if !pathname.split('/').any(is_hidden) {
+        return Cow::Borrowed(pathname);
+    }
+
+    let normalized = pathname
+        .split('/')
+        .filter(|segment| !segment.is_empty() && !is_hidden(segment))
+        .map(|segment| format!("/{segment}"))
+        .collect::<String>();
+    if normalized.is_empty() {
+        Cow::Borrowed("/")
+    } else {
+        Cow::Owned(normalized)
+    }
+}
+
+/// What kind of route a content source serves.
+#[derive(
+    Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, TraceRawVcs, ValueDebugFormat, Debug,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum RouteKind {
+    /// An API route or route handler.
+    Api,
+    /// A page.
+    Page,
+}
+
+/// How a route is rendered, as returned by
+/// [DevManifestContentSourceVc::classified_routes].
+#[derive(
+    Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, TraceRawVcs, ValueDebugFormat, Debug,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum RenderMode {
+    /// An API route or route handler.
+    Api,
+    /// A page which can be prerendered: a pages router page without
+    /// `getServerSideProps`, e.g. one with `getStaticProps`, or an app router
+    /// page which doesn't opt into dynamic rendering.
+    Static,
+    /// A page which is rendered per request: a pages router page with
+    /// `getServerSideProps`, or an app router page exporting
+    /// `dynamic = 'force-dynamic'`. Pages whose source file can't be found
+    /// are assumed to be dynamic as well.
+    Dynamic,
+}
+
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, TraceRawVcs, ValueDebugFormat, Debug)]
+pub struct ManifestRoute {
+    /// The URL pathname of the route.
+    pub pathname: String,
+    pub origin: RouteOrigin,
+    pub kind: RouteKind,
+    /// The pathname the content source serves the route at, if it differs
+    /// from `pathname`, e.g. because it's in a route group.
+    pub source_pathname: Option<String>,
+}
+
+impl ManifestRoute {
+    /// The pathname the content source serves the route at.
+    fn source_pathname(&self) -> &str {
+        self.source_pathname.as_deref().unwrap_or(&self.pathname)
+    }
+}
+
+#[turbo_tasks::value(transparent)]
+pub struct ManifestRoutes(Vec<ManifestRoute>);
+
+#[turbo_tasks::value(transparent)]
+pub struct ClassifiedRoutes(Vec<(String, RenderMode)>);
+
+/// A segment of a route's pathname, classified for the typed routes codegen.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, TraceRawVcs, ValueDebugFormat, Debug)]
+#[serde(tag = "type", content = "name", rename_all = "camelCase")]
+pub enum RouteSegment {
+    /// A static segment, e.g. `posts`.
+    Static(String),
+    /// A dynamic segment, e.g. `[id]`.
+    Dynamic(String),
+    /// A catch-all segment, e.g. `[...rest]`.
+    CatchAll(String),
+    /// An optional catch-all segment, e.g. `[[...rest]]`.
+    OptionalCatchAll(String),
+}
+
+impl From<&str> for RouteSegment {
+    fn from(segment: &str) -> Self {
+        match PageSortKey::from(segment) {
+            PageSortKey::Static(segment) => RouteSegment::Static(segment),
+            PageSortKey::Slug => {
+                let param = &segment[1..segment.len() - 1];
+                match param.strip_prefix("...") {
+                    Some(name) => RouteSegment::CatchAll(name.to_string()),
+                    None => RouteSegment::Dynamic(param.to_string()),
+                }
+            }
+            PageSortKey::CatchAll => {
+                let param = &segment[2..segment.len() - 2];
+                RouteSegment::OptionalCatchAll(param.trim_start_matches("...").to_string())
+            }
+        }
+    }
+}
+
+/// Returns the classified segments of `pathname`.
+pub fn route_segments(pathname: &str) -> Vec<RouteSegment> {
+    pathname
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(RouteSegment::from)
+        .collect()
+}
+
+#[turbo_tasks::value(transparent)]
+pub struct TypedRoutes(IndexMap<String, Vec<RouteSegment>>);
+
+async fn content_source_to_route(
+    content_source: ContentSourceVc,
+) -> Result<Option<(StringReadRef, RouteKind)>> {
+    // TODO This shouldn't use casts but an public api instead
+    if let Some(api_source) = NodeApiContentSourceVc::resolve_from(content_source).await? {
+        return Ok(Some((api_source.get_pathname().await?, RouteKind::Api)));
+    }
+
+    if let Some(page_source) = NodeRenderContentSourceVc::resolve_from(content_source).await? {
+        return Ok(Some((page_source.get_pathname().await?, RouteKind::Page)));
+    }
+
+    Ok(None)
+}
+
+async fn get_content_source_children(
+    content_source: ContentSourceVc,
+) -> Result<Vec<ContentSourceVc>> {
+    Ok(content_source.get_children().await?.clone_value())
+}
+
+/// Visits the graph reachable from `roots`, with at most `limit` calls to
+/// `visit` in flight at once. `visit` returns the children of a node along
+/// with a value, which is passed to `emit` as soon as it's available. Each
+/// node is visited once, in no particular order.
+async fn visit_bounded<T, R, F, Fut>(
+    roots: impl IntoIterator<Item = T>,
+    visit: F,
+    limit: usize,
+    mut emit: impl FnMut(R),
+) -> Result<()>
+where
+    T: Copy + Eq + Hash,
+    F: Fn(T) -> Fut,
+    Fut: Future<Output = Result<(Vec<T>, R)>>,
+{
+    let limit = limit.max(1);
+    let mut visited = HashSet::new();
+    let mut pending = roots
+        .into_iter()
+        .filter(|root| visited.insert(*root))
+        .collect::<Vec<_>>();
+    let mut in_flight = FuturesUnordered::new();
+    loop {
+        while in_flight.len() < limit {
+            let Some(node) = pending.pop() else {
+                break;
+            };
+            in_flight.push(visit(node));
+        }
+        let Some((children, value)) = in_flight.try_next().await? else {
+            break;
+        };
+        emit(value);
+        pending.extend(children.into_iter().filter(|child| visited.insert(*child)));
+    }
+    Ok(())
+}
+
+/// Recursively find all routes in the `roots` content sources, tag them with
+/// `origin` and add them to `routes`. Content sources are resolved to a route
+/// while their children are visited, and the route is added right away, so
+/// neither the content sources nor the routes are collected in between.
+///
+/// With a `concurrency` limit, at most that many content sources are visited
+/// at once.
+async fn find_routes_in(
+    roots: &[ContentSourceVc],
+    origin: RouteOrigin,
+    concurrency: Option<usize>,
+    routes: &mut RouteSet,
+) -> Result<()> {
+    visit_bounded(
+        roots.iter().copied(),
+        |source| {
+            try_join(
+                get_content_source_children(source),
+                content_source_to_route(source),
+            )
+        },
+        concurrency.unwrap_or(usize::MAX),
+        |route| {
+            if let Some((pathname, kind)) = route {
+                let source_pathname = pathname.as_str();
+                let pathname = origin.normalize_pathname(source_pathname);
+                routes.insert(ManifestRoute {
+                    source_pathname: (pathname != source_pathname)
+                        .then(|| source_pathname.to_string()),
+                    pathname: pathname.into_owned(),
+                    origin,
+                    kind,
+                });
+            }
+        },
+    )
+    .await
+}
+
+/// Accumulates routes in the order the next.js client expects, removing
+/// duplicate pathnames. Routes with equal [PageSortKey]s are ordered by their
+/// full pathname and origin, so the result doesn't depend on the
+/// (non-deterministic) order the routes were inserted in.
+///
+/// Routes are grouped by pathname as they are inserted, so the pathname and
+/// its sort key are only held once, identical routes are only stored once, and
+/// no unsorted list of all routes is needed.
+#[derive(Default)]
+struct RouteSet {
+    routes: BTreeMap<(Vec<PageSortKey>, String), Vec<RouteDefinition>>,
+}
+
+/// A route of a [RouteSet], without the pathname it's keyed by.
+#[derive(Clone, PartialEq, Eq)]
+struct RouteDefinition {
+    origin: RouteOrigin,
+    kind: RouteKind,
+    source_pathname: Option<String>,
+}
+
+impl RouteDefinition {
+    fn into_route(self, pathname: String) -> ManifestRoute {
+        ManifestRoute {
+            pathname,
+            origin: self.origin,
+            kind: self.kind,
+            source_pathname: self.source_pathname,
+        }
+    }
+}
+
+impl RouteSet {
+    fn insert(&mut self, route: ManifestRoute) {
+        let ManifestRoute {
+            pathname,
+            origin,
+            kind,
+            source_pathname,
+        } = route;
+        let definition = RouteDefinition {
+            origin,
+            kind,
+            source_pathname,
+        };
+        let duplicates = self
+            .routes
+            .entry((route_sort_key(&pathname), pathname))
+            .or_default();
+        if duplicates.contains(&definition) {
+            return;
+        }
+        // Keep duplicates ordered by origin, and in insertion order otherwise.
+        let index = duplicates.partition_point(|duplicate| duplicate.origin <= origin);
+        duplicates.insert(index, definition);
+    }
+
+    /// Returns the sorted routes, and the pairs of kept and removed routes
+    /// which define the same pathname in both the pages and app directories.
+    fn into_sorted(self) -> (Vec<ManifestRoute>, Vec<(ManifestRoute, ManifestRoute)>) {
+        let mut routes = Vec::with_capacity(self.routes.len());
+        let mut conflicts = Vec::new();
+        for ((_, pathname), duplicates) in self.routes {
+            let mut duplicates = duplicates.into_iter();
+            let Some(kept) = duplicates.next() else {
+                continue;
+            };
+            conflicts.extend(
+                duplicates
+                    .filter(|removed| removed.origin != kept.origin)
+                    .map(|removed| {
+                        (
+                            kept.clone().into_route(pathname.clone()),
+                            removed.into_route(pathname.clone()),
+                        )
+                    }),
+            );
+            routes.push(kept.into_route(pathname));
+        }
+        (routes, conflicts)
+    }
+}
+
+impl FromIterator<ManifestRoute> for RouteSet {
+    fn from_iter<T: IntoIterator<Item = ManifestRoute>>(iter: T) -> Self {
+        let mut routes = RouteSet::default();
+        for route in iter {
+            routes.insert(route);
+        }
+        routes
+    }
+}
+
+/// Returns the paths of the files which can define `route`, relative to the
+/// routes directory of its origin and without a page extension, in the order
+/// Next.js looks them up.
+fn route_source_stems(route: &ManifestRoute) -> Vec<String> {
+    let pathname = route.source_pathname().trim_start_matches('/');
+    match route.origin {
+        RouteOrigin::Pages if pathname.is_empty() => vec!["index".to_string()],
+        RouteOrigin::Pages => vec![pathname.to_string(), format!("{pathname}/index")],
+        RouteOrigin::App => {
+            let file = if route.kind == RouteKind::Api {
+                "route"
+            } else {
+                "page"
+            };
+            if pathname.is_empty() {
+                vec![file.to_string()]
+            } else {
+                vec![format!("{pathname}/{file}")]
+            }
+        }
+    }
+}
+
+/// Returns the file defining `route` in `routes_dir`, if there is one with one
+/// of the `page_extensions`.
+async fn find_route_source_file(
+    routes_dir: FileSystemPathVc,
+    page_extensions: &[String],
+    route: &ManifestRoute,
+) -> Result<Option<FileSystemPathVc>> {
+    for stem in route_source_stems(route) {
+        for extension in page_extensions {
+            let file = routes_dir.join(&format!("{stem}.{extension}"));
+            if *file.get_type().await? == FileSystemEntryType::File {
+                return Ok(Some(file));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Returns the routes directory of `origin` in the project of `source`, if it
+/// has one.
+async fn routes_dir(
+    source: &DevManifestContentSource,
+    origin: RouteOrigin,
+) -> Result<Option<FileSystemPathVc>> {
+    Ok(match origin {
+        RouteOrigin::Pages => source
+            .pages_structure
+            .await?
+            .pages
+            .map(|pages| pages.project_path()),
+        RouteOrigin::App => *source.app_dir.await?,
+    })
+}
+
+/// Returns the path of the file defining `route` in the project of `source`.
+/// The file is looked up in the pages or app directory, depending on the
+/// origin of the route. Without one, it's the path Next.js would look up
+/// first, without a page extension.
+async fn route_source_file(
+    source: &DevManifestContentSource,
+    route: &ManifestRoute,
+) -> Result<String> {
+    let stem = &route_source_stems(route)[0];
+    let Some(routes_dir) = routes_dir(source, route.origin).await? else {
+        let dir = match route.origin {
+            RouteOrigin::Pages => "pages",
+            RouteOrigin::App => "app",
+        };
+        return Ok(format!("{dir}/{stem}"));
+    };
+
+    let page_extensions = source.next_config.page_extensions().await?;
+    let project_path = source.project_path.await?;
+    if let Some(file) = find_route_source_file(routes_dir, &page_extensions, route).await? {
+        if let Some(file) = project_path.get_path_to(&*file.await?) {
+            return Ok(file.to_string());
+        }
+    }
+    let routes_dir = routes_dir.await?;
+    let dir = project_path
+        .get_path_to(&routes_dir)
+        .unwrap_or(&routes_dir.path);
+    Ok(format!("{dir}/{stem}"))
+}
+
+/// Returns whether `module` exports a binding called `name`, declared in the
+/// export or exported by name.
+fn exports_binding(module: &Module, name: &str) -> bool {
+    module
+        .body
+        .iter()
+        .filter_map(|item| item.as_module_decl())
+        .any(|decl| match decl {
+            ModuleDecl::ExportDecl(export) => match &export.decl {
+                Decl::Fn(function) => &*function.ident.sym == name,
+                Decl::Var(var) => var.decls.iter().any(|decl| {
+                    decl.name
+                        .as_ident()
+                        .map_or(false, |ident| &*ident.sym == name)
+                }),
+                _ => false,
+            },
+            ModuleDecl::ExportNamed(export) => {
+                export.specifiers.iter().any(|specifier| match specifier {
+                    ExportSpecifier::Named(specifier) => {
+                        match specifier.exported.as_ref().unwrap_or(&specifier.orig) {
+                            ModuleExportName::Ident(ident) => &*ident.sym == name,
+                            ModuleExportName::Str(str) => &*str.value == name,
+                        }
+                    }
+                    _ => false,
+                })
+            }
+            _ => false,
+        })
+}
+
+/// The context the source files of routes are parsed in to classify them.
+#[turbo_tasks::function]
+fn route_source_context() -> AssetContextVc {
+    ModuleAssetContextVc::new(
+        TransitionsByNameVc::cell(HashMap::new()),
+        CompileTimeInfo::builder(node_build_environment()).cell(),
+        ModuleOptionsContext::default().cell(),
+        ResolveOptionsContext::default().cell(),
+    )
+    .into()
+}
+
+/// Returns whether the pages router page `file` exports
+/// `getServerSideProps`, which makes it render per request.
+#[turbo_tasks::function]
+async fn exports_server_side_props(file: FileSystemPathVc) -> Result<BoolVc> {
+    let module = route_source_context().process(
+        SourceAssetVc::new(file).into(),
+        Value::new(ReferenceType::Undefined),
+    );
+    let Some(module) = EcmascriptModuleAssetVc::resolve_from(module).await? else {
+        return Ok(BoolVc::cell(false));
+    };
+    let ParseResult::Ok {
+        program: Program::Module(module),
+        ..
+    } = &*module.parse().await?
+    else {
+        return Ok(BoolVc::cell(false));
+    };
+    Ok(BoolVc::cell(exports_binding(module, "getServerSideProps")))
+}
+
+/// Returns how the page defined by `file` is rendered, depending on the
+/// router it's in.
+async fn page_render_mode(file: FileSystemPathVc, origin: RouteOrigin) -> Result<RenderMode> {
+    let dynamic = match origin {
+        RouteOrigin::Pages => *exports_server_side_props(file).await?,
+        RouteOrigin::App => {
+            let module = route_source_context().process(
+                SourceAssetVc::new(file).into(),
+                Value::new(ReferenceType::Undefined),
+            );
+            parse_segment_config_from_source(module).await?.dynamic
+                == Some(NextSegmentDynamic::ForceDynamic)
+        }
+    };
+    Ok(if dynamic {
+        RenderMode::Dynamic
+    } else {
+        RenderMode::Static
+    })
+}
+
+/// Returns the path of the production manifest `file`, like
+/// `_buildManifest.js`, relative to the output directory `distDir`. Like in
+/// Next.js, the files are keyed by `build_id`, the result of
+/// `generateBuildId`. The same path is served below `/_next/`, whatever the
+/// `distDir`.
+pub fn production_manifest_path(build_id: &str, file: &str) -> String {
+    format!("static/{build_id}/{file}")
+}
+
+/// The build id in development, which the client loads the build manifest
+/// for, like in Next.js.
+pub const DEV_BUILD_ID: &str = "development";
+
+/// Returns the pathname the `_buildManifest.js` of `build_id` is served at.
+fn build_manifest_pathname(build_id: &str) -> String {
+    format!(
+        "_next/{}",
+        production_manifest_path(build_id, "_buildManifest.js")
+    )
+}
+
+/// Returns the path of the `ext` chunk the client loads for the route at
+/// `pathname`. Like Next.js, the `asset_prefix` takes precedence over the
+/// `base_path`. Without either, the path is relative to the server root.
+pub fn manifest_chunk_path(
+    pathname: &str,
+    router_kind: RouteOrigin,
+    ext: &str,
+    asset_prefix: &str,
+    base_path: &str,
+) -> String {
+    let chunk_dir = match router_kind {
+        RouteOrigin::Pages => "pages",
+        RouteOrigin::App => "app",
+    };
+    let chunk_path = format!(
+        "_next/static/chunks/{}{}",
+        chunk_dir,
+        get_asset_path_from_pathname(pathname, ext)
+    );
+    join_asset_url(asset_prefix, base_path, &chunk_path)
+}
+
+/// Returns the path of the chunk the client loads for `route`. The client
+/// runtime applies the asset prefix itself, so none is added here.
+fn route_chunk_path(route: &ManifestRoute) -> String {
+    manifest_chunk_path(
+        &normalize_trailing_slash(route.source_pathname(), false),
+        route.origin,
+        ".js",
+        "",
+        "",
+    )
+}
+
+/// Returns why `template` can't be used as `experimental.chunkUrlTemplate`,
+/// if it can't.
+fn chunk_url_template_error(template: &str) -> Option<&'static str> {
+    if !template.contains("[name]") {
+        return Some(
+            "The template must contain the [name] placeholder, so every chunk gets its own URL.",
+        );
+    }
+    None
+}
+
+/// Returns the chunk URL of `route` according to a valid
+/// `experimental.chunkUrlTemplate`. The chunk name is the path of the chunk
+/// below `_next/static/chunks` without extension, like `pages/blog`.
+fn templated_chunk_path(template: &str, route: &ManifestRoute) -> String {
+    let chunk_path = route_chunk_path(route);
+    let name = chunk_path
+        .strip_prefix("_next/static/chunks/")
+        .and_then(|path| path.strip_suffix(".js"))
+        .unwrap_or(&chunk_path);
+    let hash = format!("{:016x}", hash_xxh3_hash64(name));
+    template
+        .replace("[name]", name)
+        .replace("[hash]", &hash[..8])
+}
+
+/// Returns whether the segments `a` and `b` of two dynamic routes can match the
+/// same path, with each route being more specific than the other in some
+/// segment. Routes with catch-all segments always have a lower precedence and
+/// aren't considered ambiguous.
+fn is_ambiguous(a: &[RouteSegment], b: &[RouteSegment]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut a_more_specific = false;
+    let mut b_more_specific = false;
+    for (a, b) in a.iter().zip(b) {
+        match (a, b) {
+            (RouteSegment::Static(a), RouteSegment::Static(b)) => {
+                if a != b {
+                    return false;
+                }
+            }
+            (RouteSegment::Static(_), RouteSegment::Dynamic(_)) => a_more_specific = true,
+            (RouteSegment::Dynamic(_), RouteSegment::Static(_)) => b_more_specific = true,
+            (RouteSegment::Dynamic(_), RouteSegment::Dynamic(_)) => {}
+            _ => return false,
+        }
+    }
+    a_more_specific && b_more_specific
+}
+
+/// Returns all pairs of `pathnames` which are ambiguous, like `/[a]/b` and
+/// `/c/[b]`, which both match `/c/b`.
+fn ambiguous_routes<'a>(pathnames: &[&'a str]) -> Vec<(&'a str, &'a str)> {
+    let segments = pathnames
+        .iter()
+        .map(|pathname| (*pathname, route_segments(pathname)))
+        .collect::<Vec<_>>();
+    let mut conflicts = Vec::new();
+    for (index, (a, a_segments)) in segments.iter().enumerate() {
+        for (b, b_segments) in &segments[index + 1..] {
+            if is_ambiguous(a_segments, b_segments) {
+                conflicts.push((*a, *b));
+            }
+        }
+    }
+    conflicts
+}
+
+/// Returns a hash of the dev server `session` and the pathnames of the
+/// (sorted) `routes`. Within a session, only adding or removing routes
+/// changes it.
+fn routes_version(session: &str, routes: &[ManifestRoute]) -> String {
+    let pathnames = routes
+        .iter()
+        .map(|route| route.pathname.as_str())
+        .collect::<Vec<_>>();
+    format!("{:016x}", hash_xxh3_hash64((session, pathnames)))
+}
+
+/// Makes `pathname` end with a slash when `trailing_slash` is set and strips
+/// it otherwise, matching the `trailingSlash` config. The root `/` is kept as
+/// is.
+fn normalize_trailing_slash(pathname: &str, trailing_slash: bool) -> String {
+    let pathname = pathname.trim_end_matches('/');
+    if pathname.is_empty() {
+        "/".to_string()
+    } else if trailing_slash {
+        format!("{}/", pathname)
+    } else {
+        pathname.to_string()
+    }
+}
+
+/// Returns whether `roots` serve an asset at `path`. Only the existence of
+/// the asset is checked, its content isn't computed.
+async fn serves_asset(roots: &[ContentSourceVc], path: &str) -> Result<bool> {
+    for root in roots {
+        for source in root.get_routes().get(path).await?.iter() {
+            let content = source
+                .get(path, Value::new(ContentSourceData::default()))
+                .await?;
+            if let ContentSourceContent::Static(_) = *content {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
+
+/// Returns the pathnames of the pages router `routes` whose page loader, the
+/// chunk the client loads for them, isn't served by `page_roots`. App router
+/// routes have no chunk of their own, their client chunks are referenced by
+/// the RSC payload instead, so they aren't checked.
+async fn routes_without_chunks(
+    routes: &[ManifestRoute],
+    page_roots: &[ContentSourceVc],
+) -> Result<Vec<String>> {
+    Ok(routes
+        .iter()
+        .filter(|route| route.origin == RouteOrigin::Pages)
+        .map(|route| async move {
+            let path = page_loader_path(&normalize_trailing_slash(route.source_pathname(), false));
+            Ok((!serves_asset(page_roots, &path).await?).then(|| route.pathname.clone()))
+        })
+        .try_join()
+        .await?
+        .into_iter()
+        .flatten()
+        .collect())
+}
+
+/// Builder for [DevManifestContentSource].
+pub struct DevManifestContentSourceBuilder {
+    source: DevManifestContentSource,
+}
+
+impl DevManifestContentSourceBuilder {
+    /// Creates a builder for a source without routes, which serves the
+    /// manifests for the [DEV_BUILD_ID]. A session has to be set before it's
+    /// built.
+    pub fn new(
+        project_path: FileSystemPathVc,
+        pages_structure: PagesStructureVc,
+        app_dir: OptionAppDirVc,
+        next_config: NextConfigVc,
+    ) -> Self {
+        Self {
+            source: DevManifestContentSource {
+                project_path,
+                page_roots: vec![],
+                app_roots: vec![],
+                pages_structure,
+                app_dir,
+                next_config,
+                build_id: DEV_BUILD_ID.to_string(),
+                session: String::new(),
+            },
+        }
+    }
+
+    /// The content sources the pages router routes are found in.
+    pub fn page_roots(mut self, page_roots: Vec<ContentSourceVc>) -> Self {
+        self.source.page_roots = page_roots;
+        self
+    }
+
+    /// The content sources the app router routes are found in.
+    pub fn app_roots(mut self, app_roots: Vec<ContentSourceVc>) -> Self {
+        self.source.app_roots = app_roots;
+        self
+    }
+
+    /// The build id the `_buildManifest.js` is served for.
+    pub fn build_id(mut self, build_id: impl Into<String>) -> Self {
+        self.source.build_id = build_id.into();
+        self
+    }
+
+    /// Identifies the dev server session the manifests are served for.
+    pub fn session(mut self, session: impl Into<String>) -> Self {
+        self.source.session = session.into();
+        self
+    }
+
+    /// Builds the source. Fails without a session, or when the build id can't
+    /// be part of the `_buildManifest.js` pathname.
+    pub fn build(self) -> Result<DevManifestContentSourceVc> {
+        let DevManifestContentSource {
+            build_id, session, ..
+        } = &self.source;
+        if session.is_empty() {
+            bail!("a session is required to serve the dev manifests");
+        }
+        if build_id.is_empty() || build_id.contains('/') {
+            bail!("the build id {:?} isn't a valid path segment", build_id);
+        }
+        Ok(self.source.cell())
+    }
+}
+
+impl DevManifestContentSourceVc {
+    /// Creates a [DevManifestContentSourceBuilder].
+    pub fn builder(
+        project_path: FileSystemPathVc,
+        pages_structure: PagesStructureVc,
+        app_dir: OptionAppDirVc,
+        next_config: NextConfigVc,
+    ) -> DevManifestContentSourceBuilder {
+        DevManifestContentSourceBuilder::new(project_path, pages_structure, app_dir, next_config)
+    }
 }
 
 #[turbo_tasks::value_impl]
 impl DevManifestContentSourceVc {
-    /// Recursively find all routes in the `page_roots` content sources.
+    /// Merges manifest sources, e.g. ones built separately for the pages and
+    /// app routers, into one source which serves the manifests for the routes
+    /// of all of them. Routes are sorted and deduplicated across sources like
+    /// they are within one. The pages structure and app directory of the first
+    /// source are used. All sources need to have the same `next_config`, build
+    /// id and session, as the manifests are served for those.
+    #[turbo_tasks::function]
+    pub async fn merge(sources: Vec<DevManifestContentSourceVc>) -> Result<Self> {
+        let mut sources = sources.into_iter();
+        let Some(first) = sources.next() else {
+            bail!("at least one manifest source is required to merge");
+        };
+        let mut merged = (*first.await?).clone();
+        let next_config = merged.next_config.await?;
+        for source in sources {
+            let source = source.await?;
+            if source.build_id != merged.build_id {
+                bail!(
+                    "manifest sources for the build ids {:?} and {:?} can't be merged",
+                    merged.build_id,
+                    source.build_id
+                );
+            }
+            if source.session != merged.session {
+                bail!("manifest sources of different dev server sessions can't be merged");
+            }
+            if *source.next_config.await? != *next_config {
+                bail!("manifest sources with different next.config.js can't be merged");
+            }
+            merged.page_roots.extend(source.page_roots.iter().copied());
+            merged.app_roots.extend(source.app_roots.iter().copied());
+        }
+        Ok(merged.cell())
+    }
+
+    /// Recursively find all routes in the `page_roots` and `app_roots`
+    /// content sources.
+    ///
+    /// All manifests are derived from this memoized result, so the content
+    /// sources are only traversed once (once per `find routes` span in traces)
+    /// however many manifests are requested.
     #[turbo_tasks::function]
-    async fn find_routes(self) -> Result<StringsVc> {
+    async fn find_routes(self) -> Result<ManifestRoutesVc> {
         let this = &*self.await?;
 
-        async fn content_source_to_pathname(
-            content_source: ContentSourceVc,
-        ) -> Result<Option<StringReadRef>> {
-            // TODO This shouldn't use casts but an public api instead
-            if let Some(api_source) = NodeApiContentSourceVc::resolve_from(content_source).await? {
-                return Ok(Some(api_source.get_pathname().await?));
-            }
+        let concurrency = this
+            .next_config
+            .await?
+            .experimental
+            .route_traversal_concurrency;
+        let mut route_set = RouteSet::default();
+        async {
+            find_routes_in(
+                &this.page_roots,
+                RouteOrigin::Pages,
+                concurrency,
+                &mut route_set,
+            )
+            .await?;
+            find_routes_in(
+                &this.app_roots,
+                RouteOrigin::App,
+                concurrency,
+                &mut route_set,
+            )
+            .await
+        }
+        .instrument(tracing::info_span!("find routes"))
+        .await?;
 
-            if let Some(page_source) =
-                NodeRenderContentSourceVc::resolve_from(content_source).await?
-            {
-                return Ok(Some(page_source.get_pathname().await?));
+        let (routes, conflicts) = route_set.into_sorted();
+        for (kept, removed) in conflicts {
+            let mut files = Vec::with_capacity(2);
+            for route in [&kept, &removed] {
+                files.push(route_source_file(this, route).await?);
             }
-
-            Ok(None)
+            ConflictingRouteIssue {
+                path: this.project_path,
+                pathname: kept.pathname.clone(),
+                files,
+            }
+            .cell()
+            .as_issue()
+            .emit();
         }
 
-        async fn get_content_source_children(
-            content_source: ContentSourceVc,
-        ) -> Result<Vec<ContentSourceVc>> {
-            Ok(content_source.get_children().await?.clone_value())
+        if *this.next_config.strict_route_precedence().await? {
+            let pathnames = routes
+                .iter()
+                .map(|route| route.pathname.as_str())
+                .collect::<Vec<_>>();
+            let conflicts = ambiguous_routes(&pathnames);
+            if !conflicts.is_empty() {
+                AmbiguousRoutesIssue {
+                    path: this.project_path,
+                    conflicts: conflicts
+                        .into_iter()
+                        .map(|(a, b)| format!("{} and {}", a, b))
+                        .collect(),
+                }
+                .cell()
+                .as_issue()
+                .emit();
+            }
         }
 
-        let routes = NonDeterministic::new()
-            .visit(this.page_roots.iter().copied(), get_content_source_children)
-            .await
-            .completed()?
-            .into_iter()
-            .map(content_source_to_pathname)
+        Ok(ManifestRoutesVc::cell(routes))
+    }
+
+    /// All routes found by `find_routes` with how they are rendered. Pages are
+    /// classified by the data fetching methods and segment config their source
+    /// files export.
+    #[turbo_tasks::function]
+    pub async fn classified_routes(self) -> Result<ClassifiedRoutesVc> {
+        let this = &*self.await?;
+        let page_extensions = this.next_config.page_extensions().await?;
+        let routes = self.find_routes().await?;
+        let classified = routes
+            .iter()
+            .map(|route| {
+                let page_extensions = &page_extensions;
+                async move {
+                    if route.kind == RouteKind::Api {
+                        return Ok((route.pathname.clone(), RenderMode::Api));
+                    }
+                    let file = match routes_dir(this, route.origin).await? {
+                        Some(routes_dir) => {
+                            find_route_source_file(routes_dir, page_extensions, route).await?
+                        }
+                        None => None,
+                    };
+                    let render_mode = match file {
+                        Some(file) => page_render_mode(file, route.origin).await?,
+                        None => RenderMode::Dynamic,
+                    };
+                    Ok((route.pathname.clone(), render_mode))
+                }
+            })
             .try_join()
             .await?;
-        let mut routes = routes
-            .into_iter()
-            .flatten()
-            .map(|route| route.clone_value())
-            .collect::<Vec<_>>();
+        Ok(ClassifiedRoutesVc::cell(classified))
+    }
 
-        routes.sort_by_cached_key(|s| s.split('/').map(PageSortKey::from).collect::<Vec<_>>());
-        routes.dedup();
+    /// The pathnames of all routes found by `find_routes`.
+    #[turbo_tasks::function]
+    async fn find_route_pathnames(self) -> Result<StringsVc> {
+        Ok(StringsVc::cell(
+            self.find_routes()
+                .await?
+                .iter()
+                .map(|route| route.pathname.clone())
+                .collect(),
+        ))
+    }
+
+    /// A token which changes whenever routes are added or removed, so the
+    /// client only needs to refetch the manifests when it does.
+    #[turbo_tasks::function]
+    async fn manifest_version(self) -> Result<StringVc> {
+        Ok(StringVc::cell(routes_version(
+            &self.await?.session,
+            &self.find_routes().await?,
+        )))
+    }
+
+    /// The segments of all pages found by `find_pages`, for the typed routes
+    /// codegen. Empty unless `experimental.typedRoutes` is enabled.
+    #[turbo_tasks::function]
+    pub async fn typed_routes(self) -> Result<TypedRoutesVc> {
+        let this = self.await?;
+        if !*this.next_config.typed_routes().await? {
+            return Ok(TypedRoutesVc::cell(IndexMap::new()));
+        }
 
-        Ok(StringsVc::cell(routes))
+        Ok(TypedRoutesVc::cell(
+            self.find_pages()
+                .await?
+                .iter()
+                .map(|route| (route.pathname.clone(), route_segments(&route.pathname)))
+                .collect(),
+        ))
     }
 
-    /// Recursively find all pages in the `page_roots` content sources
-    /// (excluding api routes).
+    /// Recursively find all pages in the `page_roots` and `app_roots` content
+    /// sources (excluding api routes).
     #[turbo_tasks::function]
-    async fn find_pages(self) -> Result<StringsVc> {
+    async fn find_pages(self) -> Result<ManifestRoutesVc> {
         let routes = &*self.find_routes().await?;
 
         // we don't need to sort as it's already sorted by `find_routes`
         let pages = routes
             .iter()
-            .filter(|s| !s.starts_with("/api"))
+            .filter(|route| !route.pathname.starts_with("/api"))
             .cloned()
             .collect();
 
-        Ok(StringsVc::cell(pages))
+        Ok(ManifestRoutesVc::cell(pages))
     }
 
     /// Create a build manifest with all pages.
@@ -109,33 +1086,134 @@ impl DevManifestContentSourceVc {
     async fn create_build_manifest(self) -> Result<StringVc> {
         let this = &*self.await?;
 
-        let sorted_pages = &*self.find_pages().await?;
-        let routes = sorted_pages
+        let mut sorted_pages = self.find_pages().await?.clone_value();
+        if let Some(validation) = this
+            .next_config
+            .await?
+            .experimental
+            .manifest_chunk_validation
+        {
+            let missing = routes_without_chunks(&sorted_pages, &this.page_roots).await?;
+            if !missing.is_empty() {
+                let omitted = validation == ManifestChunkValidation::Omit;
+                MissingRouteChunksIssue {
+                    path: this.project_path,
+                    pathnames: missing.clone(),
+                    omitted,
+                }
+                .cell()
+                .as_issue()
+                .emit();
+                if omitted {
+                    sorted_pages.retain(|route| !missing.contains(&route.pathname));
+                }
+            }
+        }
+
+        let chunk_url_template = match &this.next_config.await?.experimental.chunk_url_template {
+            Some(template) => match chunk_url_template_error(template) {
+                Some(reason) => {
+                    InvalidChunkUrlTemplateIssue {
+                        path: this.project_path,
+                        template: template.clone(),
+                        reason: reason.to_string(),
+                    }
+                    .cell()
+                    .as_issue()
+                    .emit();
+                    None
+                }
+                None => Some(template.clone()),
+            },
+            None => None,
+        };
+        let routes: IndexMap<_, _> = sorted_pages
             .iter()
-            .map(|pathname| {
-                (
-                    pathname,
-                    vec![format!(
-                        "_next/static/chunks/pages{}",
-                        get_asset_path_from_pathname(pathname, ".js")
-                    )],
-                )
+            .map(|route| {
+                let chunk_path = match &chunk_url_template {
+                    Some(template) => templated_chunk_path(template, route),
+                    None => route_chunk_path(route),
+                };
+                (route.pathname.as_str(), vec![chunk_path])
             })
             .collect();
+        let route_trie = if *this.next_config.route_trie_manifest().await? {
+            Some(
+                routes
+                    .iter()
+                    .map(|(pathname, files)| (*pathname, files.clone()))
+                    .collect(),
+            )
+        } else {
+            None
+        };
+
+        let dynamic_routes = if *this.next_config.dynamic_route_fallbacks().await? {
+            Some(dynamic_route_entries(&sorted_pages))
+        } else {
+            None
+        };
 
+        let route_hints = this
+            .next_config
+            .await?
+            .experimental
+            .preload_routes
+            .as_ref()
+            .map(|preload_routes| route_hints(&sorted_pages, preload_routes));
+
+        let rewrites = this.next_config.rewrites().await?;
         let manifest = BuildManifest {
-            rewrites: this.next_config.rewrites().await?,
-            sorted_pages,
+            rewrites: &rewrites,
+            sorted_pages: sorted_pages
+                .iter()
+                .map(|route| route.pathname.as_str())
+                .collect(),
+            route_trie,
+            dynamic_routes,
+            route_hints,
             routes,
         };
 
-        let manifest = next_js_file("entry/manifest/buildManifest.js")
-            .await?
+        let next_config = this.next_config.await?;
+        let (template, placeholder) = match &next_config.experimental.build_manifest_template {
+            Some(template) => (
+                this.project_path.join(&template.path).read(),
+                template
+                    .placeholder
+                    .as_deref()
+                    .unwrap_or(BUILD_MANIFEST_PLACEHOLDER),
+            ),
+            None => (
+                next_js_file("entry/manifest/buildManifest.js"),
+                BUILD_MANIFEST_PLACEHOLDER,
+            ),
+        };
+        let template = template.await?;
+        let template = template
             .as_content()
-            .context("embedded buildManifest file missing")?
+            .context("buildManifest template missing")?
             .content()
-            .to_str()?
-            .replace("$$MANIFEST$$", &serde_json::to_string(&manifest)?);
+            .to_str()?;
+
+        let manifest =
+            render_build_manifest(&template, placeholder, &build_manifest_json(&manifest)?)?;
+
+        let threshold = next_config
+            .experimental
+            .build_manifest_size_warning
+            .unwrap_or(DEFAULT_BUILD_MANIFEST_SIZE_WARNING);
+        if let Some(description) =
+            large_build_manifest_description(manifest.len(), sorted_pages.len(), threshold)
+        {
+            LargeBuildManifestIssue {
+                path: this.project_path,
+                description,
+            }
+            .cell()
+            .as_issue()
+            .emit();
+        }
 
         Ok(StringVc::cell(manifest))
     }
@@ -145,72 +1223,418 @@ impl DevManifestContentSourceVc {
 #[serde(rename_all = "camelCase")]
 struct BuildManifest<'a> {
     #[serde(rename = "__rewrites")]
-    rewrites: RewritesReadRef,
-    sorted_pages: &'a Vec<String>,
+    rewrites: &'a Rewrites,
+    sorted_pages: Vec<&'a str>,
+    #[serde(rename = "__routeTrie", skip_serializing_if = "Option::is_none")]
+    route_trie: Option<RouteTrie>,
+    #[serde(rename = "__dynamicRoutes", skip_serializing_if = "Option::is_none")]
+    dynamic_routes: Option<Vec<DynamicRouteEntry<'a>>>,
+    #[serde(rename = "__routeHints", skip_serializing_if = "Option::is_none")]
+    route_hints: Option<IndexMap<&'a str, RouteHints>>,
 
     #[serde(flatten)]
-    routes: IndexMap<&'a String, Vec<String>>,
+    routes: IndexMap<&'a str, Vec<String>>,
 }
 
-const DEV_MANIFEST_PATHNAME: &str = "_next/static/development/_devPagesManifest.json";
-const BUILD_MANIFEST_PATHNAME: &str = "_next/static/development/_buildManifest.js";
-const DEV_MIDDLEWARE_MANIFEST_PATHNAME: &str =
-    "_next/static/development/_devMiddlewareManifest.json";
+/// Serializes `manifest` with the keys of every object sorted, so the output
+/// only depends on its contents, not on the order maps were filled in.
+fn build_manifest_json(manifest: &BuildManifest) -> Result<String> {
+    Ok(serde_json::to_string(&sort_json_keys(
+        serde_json::to_value(manifest)?,
+    ))?)
+}
 
-#[turbo_tasks::value_impl]
-impl ContentSource for DevManifestContentSource {
-    #[turbo_tasks::function]
-    fn get_routes(self_vc: DevManifestContentSourceVc) -> RouteTreeVc {
-        RouteTreesVc::cell(vec![
-            RouteTreeVc::new_route(
-                BaseSegment::from_static_pathname(DEV_MANIFEST_PATHNAME).collect(),
-                RouteType::Exact,
-                self_vc.into(),
-            ),
-            RouteTreeVc::new_route(
-                BaseSegment::from_static_pathname(BUILD_MANIFEST_PATHNAME).collect(),
-                RouteType::Exact,
-                self_vc.into(),
-            ),
-            RouteTreeVc::new_route(
-                BaseSegment::from_static_pathname(DEV_MIDDLEWARE_MANIFEST_PATHNAME).collect(),
-                RouteType::Exact,
-                self_vc.into(),
-            ),
-        ])
-        .merge()
+fn sort_json_keys(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(object) => {
+            let mut entries = object.into_iter().collect::<Vec<_>>();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            serde_json::Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key, sort_json_keys(value)))
+                    .collect(),
+            )
+        }
+        serde_json::Value::Array(items) => {
+            serde_json::Value::Array(items.into_iter().map(sort_json_keys).collect())
+        }
+        value => value,
     }
 }
 
-#[turbo_tasks::value_impl]
-impl GetContentSourceContent for DevManifestContentSource {
-    #[turbo_tasks::function]
-    async fn get(
-        self_vc: DevManifestContentSourceVc,
-        path: &str,
-        _data: turbo_tasks::Value<ContentSourceData>,
-    ) -> Result<ContentSourceContentVc> {
-        let manifest_file = match path {
-            DEV_MANIFEST_PATHNAME => {
-                let pages = &*self_vc.find_routes().await?;
+/// A route with dynamic segments in a [BuildManifest], which the client falls
+/// back to matching pathnames against when they aren't listed.
+#[derive(Debug, PartialEq, Serialize)]
+struct DynamicRouteEntry<'a> {
+    page: &'a str,
+    regex: String,
+}
 
-                File::from(serde_json::to_string(&serde_json::json!({
-                    "pages": pages,
-                }))?)
+/// Returns the regex matching the pathnames the route at `pathname` handles,
+/// or `None` if it has no dynamic segments.
+fn dynamic_route_regex(pathname: &str) -> Option<String> {
+    let segments = route_segments(pathname);
+    if segments
+        .iter()
+        .all(|segment| matches!(segment, RouteSegment::Static(_)))
+    {
+        return None;
+    }
+
+    let mut pattern = "^".to_string();
+    for segment in segments {
+        match segment {
+            RouteSegment::Static(segment) => {
+                pattern.push('/');
+                pattern.push_str(&regex::escape(&segment));
+            }
+            RouteSegment::Dynamic(_) => pattern.push_str("/([^/]+?)"),
+            RouteSegment::CatchAll(_) => pattern.push_str("/(.+?)"),
+            RouteSegment::OptionalCatchAll(_) => pattern.push_str("(?:/(.+?))?"),
+        }
+    }
+    pattern.push_str("(?:/)?$");
+    Some(pattern)
+}
+
+/// Returns the fallback entries of the dynamic routes in `sorted_pages`, in
+/// the order they are matched in.
+fn dynamic_route_entries(sorted_pages: &[ManifestRoute]) -> Vec<DynamicRouteEntry<'_>> {
+    sorted_pages
+        .iter()
+        .filter_map(|route| {
+            Some(DynamicRouteEntry {
+                page: &route.pathname,
+                regex: dynamic_route_regex(&route.pathname)?,
+            })
+        })
+        .collect()
+}
+
+/// How the client treats a route in a [BuildManifest] beyond matching it.
+#[derive(Debug, PartialEq, Serialize)]
+struct RouteHints {
+    /// Whether the client prefetches the chunks of the route eagerly.
+    preload: bool,
+}
+
+/// Returns the hints of the routes in `sorted_pages` listed in
+/// `preload_routes`. Routes without hints are left out.
+fn route_hints<'a>(
+    sorted_pages: &'a [ManifestRoute],
+    preload_routes: &[String],
+) -> IndexMap<&'a str, RouteHints> {
+    sorted_pages
+        .iter()
+        .filter(|route| preload_routes.contains(&route.pathname))
+        .map(|route| (route.pathname.as_str(), RouteHints { preload: true }))
+        .collect()
+}
+
+/// The routes of a [BuildManifest] as a prefix trie of their path segments,
+/// which lets the client match a pathname segment by segment.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RouteTrie {
+    /// The chunks of the route ending at this node.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    files: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    children: IndexMap<String, RouteTrie>,
+}
+
+impl RouteTrie {
+    pub fn insert(&mut self, pathname: &str, files: Vec<String>) {
+        let node = pathname
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .fold(self, |node, segment| {
+                node.children.entry(segment.to_string()).or_default()
+            });
+        node.files = Some(files);
+    }
+
+    /// Returns the flat routes stored in the trie, with their chunks.
+    pub fn routes(&self) -> IndexMap<String, Vec<String>> {
+        let mut routes = IndexMap::new();
+        self.collect_routes(String::new(), &mut routes);
+        routes
+    }
+
+    fn collect_routes(&self, prefix: String, routes: &mut IndexMap<String, Vec<String>>) {
+        if let Some(files) = &self.files {
+            let pathname = if prefix.is_empty() {
+                "/".to_string()
+            } else {
+                prefix.clone()
+            };
+            routes.insert(pathname, files.clone());
+        }
+        for (segment, child) in &self.children {
+            child.collect_routes(format!("{}/{}", prefix, segment), routes);
+        }
+    }
+}
+
+impl<'a> FromIterator<(&'a str, Vec<String>)> for RouteTrie {
+    fn from_iter<T: IntoIterator<Item = (&'a str, Vec<String>)>>(iter: T) -> Self {
+        let mut trie = RouteTrie::default();
+        for (pathname, files) in iter {
+            trie.insert(pathname, files);
+        }
+        trie
+    }
+}
+
+// These are URL paths, which stay below `_next/` with a custom `distDir`.
+const DEV_MANIFEST_PATHNAME: &str = "_next/static/development/_devPagesManifest.json";
+const DEV_MIDDLEWARE_MANIFEST_PATHNAME: &str =
+    "_next/static/development/_devMiddlewareManifest.json";
+const DEV_MANIFESTS_PATHNAME: &str = "_next/static/development/_manifests.json";
+const DEV_HEADERS_MANIFEST_PATHNAME: &str = "_next/static/development/_headersManifest.json";
+const DEV_REDIRECTS_MANIFEST_PATHNAME: &str = "_next/static/development/_redirectsManifest.json";
+const DEV_MANIFEST_VERSION_PATHNAME: &str = "_next/static/development/_manifestVersion.json";
+
+/// If there is actual middleware, the middleware manifest request will have
+/// been handled by the node router in next-core/js/src/entry/router.ts and
+/// next/src/server/lib/route-resolver.ts.
+/// If we've reached this point, then there is no middleware and we need to
+/// respond with an empty `MiddlewareMatcher[]`.
+const DEV_MIDDLEWARE_MANIFEST: &str = "[]";
+
+/// Returns the `_devPagesManifest.json` listing `pages`, and with
+/// `include_segments` also each page with its [RouteSegment]s and URL under
+/// `routes`. Only the URLs follow `trailing_slash`; pages are keyed by their
+/// name, like in the build manifest.
+fn dev_pages_manifest(pages: &[String], include_segments: bool, trailing_slash: bool) -> JsonValue {
+    if !include_segments {
+        return serde_json::json!({
+            "pages": pages,
+        });
+    }
+    let routes = pages
+        .iter()
+        .map(|page| {
+            serde_json::json!({
+                "page": page,
+                "url": normalize_trailing_slash(page, trailing_slash),
+                "segments": route_segments(page),
+            })
+        })
+        .collect::<Vec<_>>();
+    serde_json::json!({
+        "pages": pages,
+        "routes": routes,
+    })
+}
+
+const BUILD_MANIFEST_PLACEHOLDER: &str = "$$MANIFEST$$";
+
+/// Replaces the `placeholder` in the `_buildManifest.js` `template` with the
+/// `manifest` JSON.
+fn render_build_manifest(template: &str, placeholder: &str, manifest: &str) -> Result<String> {
+    if !template.contains(placeholder) {
+        bail!(
+            "the buildManifest template doesn't contain the placeholder {}",
+            placeholder
+        );
+    }
+    Ok(template.replace(placeholder, manifest))
+}
+
+const DEFAULT_BUILD_MANIFEST_SIZE_WARNING: usize = 1024 * 1024;
+
+/// Describes why a build manifest of `size` bytes for `route_count` routes is
+/// too large, if it exceeds `threshold`.
+fn large_build_manifest_description(
+    size: usize,
+    route_count: usize,
+    threshold: usize,
+) -> Option<String> {
+    if size <= threshold {
+        return None;
+    }
+    Some(format!(
+        "The build manifest is {} bytes for {} routes, which exceeds the threshold of {} bytes. \
+         Large manifests slow down or crash the client, consider reducing the number of routes, \
+         e.g. with dynamic segments. The threshold can be changed with \
+         \"experimental.buildManifestSizeWarning\".",
+        size, route_count, threshold
+    ))
+}
+
+/// Returns the headers manifest for the `headers()` rules of the next config,
+/// with their `has`/`missing` conditions.
+fn headers_manifest(headers: &[Header]) -> JsonValue {
+    serde_json::json!({
+        "headers": headers,
+    })
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RedirectsManifestEntry<'a> {
+    source: &'a str,
+    destination: &'a str,
+    status_code: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    has: Option<&'a Vec<RouteHas>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    missing: Option<&'a Vec<RouteHas>>,
+}
+
+/// Returns the redirects manifest for the `redirects()` rules of the next
+/// config. Like the routes manifest of Next.js, every entry carries its
+/// resolved `statusCode` instead of the `permanent` flag.
+fn redirects_manifest(redirects: &[Redirect]) -> JsonValue {
+    let redirects = redirects
+        .iter()
+        .map(|redirect| RedirectsManifestEntry {
+            source: &redirect.source,
+            destination: &redirect.destination,
+            status_code: redirect.status_code(),
+            has: redirect.has.as_ref(),
+            missing: redirect.missing.as_ref(),
+        })
+        .collect::<Vec<_>>();
+    serde_json::json!({
+        "redirects": redirects,
+    })
+}
+
+/// Bundles the payloads of all individual manifest endpoints, so the client
+/// can fetch them with a single request.
+fn combined_manifests(
+    pages: &[String],
+    include_segments: bool,
+    trailing_slash: bool,
+    build_manifest: &str,
+) -> Result<JsonValue> {
+    Ok(serde_json::json!({
+        "devPagesManifest": dev_pages_manifest(pages, include_segments, trailing_slash),
+        "buildManifest": build_manifest,
+        "devMiddlewareManifest": serde_json::from_str::<JsonValue>(DEV_MIDDLEWARE_MANIFEST)?,
+    }))
+}
+
+#[turbo_tasks::value_impl]
+impl ContentSource for DevManifestContentSource {
+    #[turbo_tasks::function]
+    async fn get_routes(self_vc: DevManifestContentSourceVc) -> Result<RouteTreeVc> {
+        let build_id = &self_vc.await?.build_id;
+        Ok(RouteTreesVc::cell(vec![
+            RouteTreeVc::new_route(
+                BaseSegment::from_static_pathname(DEV_MANIFEST_PATHNAME).collect(),
+                RouteType::Exact,
+                self_vc.into(),
+            ),
+            RouteTreeVc::new_route(
+                BaseSegment::from_static_pathname(&build_manifest_pathname(build_id)).collect(),
+                RouteType::Exact,
+                self_vc.into(),
+            ),
+            RouteTreeVc::new_route(
+                BaseSegment::from_static_pathname(DEV_MIDDLEWARE_MANIFEST_PATHNAME).collect(),
+                RouteType::Exact,
+                self_vc.into(),
+            ),
+            RouteTreeVc::new_route(
+                BaseSegment::from_static_pathname(DEV_MANIFESTS_PATHNAME).collect(),
+                RouteType::Exact,
+                self_vc.into(),
+            ),
+            RouteTreeVc::new_route(
+                BaseSegment::from_static_pathname(DEV_HEADERS_MANIFEST_PATHNAME).collect(),
+                RouteType::Exact,
+                self_vc.into(),
+            ),
+            RouteTreeVc::new_route(
+                BaseSegment::from_static_pathname(DEV_REDIRECTS_MANIFEST_PATHNAME).collect(),
+                RouteType::Exact,
+                self_vc.into(),
+            ),
+            RouteTreeVc::new_route(
+                BaseSegment::from_static_pathname(DEV_MANIFEST_VERSION_PATHNAME).collect(),
+                RouteType::Exact,
+                self_vc.into(),
+            ),
+        ])
+        .merge())
+    }
+}
+
+#[turbo_tasks::value_impl]
+impl GetContentSourceContent for DevManifestContentSource {
+    #[turbo_tasks::function]
+    async fn get(
+        self_vc: DevManifestContentSourceVc,
+        path: &str,
+        _data: Value<ContentSourceData>,
+    ) -> Result<ContentSourceContentVc> {
+        let this = self_vc.await?;
+        let include_segments = this
+            .next_config
+            .await?
+            .experimental
+            .dev_pages_manifest_segments
+            .unwrap_or(false);
+        let trailing_slash = *this.next_config.trailing_slash().await?;
+        let manifest_file = match path {
+            DEV_MANIFEST_PATHNAME => {
+                let pages = self_vc.find_route_pathnames().await?;
+
+                File::from(serde_json::to_string(&dev_pages_manifest(
+                    &pages,
+                    include_segments,
+                    trailing_slash,
+                ))?)
                 .with_content_type(APPLICATION_JSON)
             }
-            BUILD_MANIFEST_PATHNAME => {
+            path if path == build_manifest_pathname(&this.build_id) => {
                 let build_manifest = &*self_vc.create_build_manifest().await?;
 
                 File::from(build_manifest.as_str()).with_content_type(APPLICATION_JAVASCRIPT_UTF_8)
             }
             DEV_MIDDLEWARE_MANIFEST_PATHNAME => {
-                // If there is actual middleware, this request will have been handled by the
-                // node router in next-core/js/src/entry/router.ts and
-                // next/src/server/lib/route-resolver.ts.
-                // If we've reached this point, then there is no middleware and we need to
-                // respond with an empty `MiddlewareMatcher[]`.
-                File::from("[]").with_content_type(APPLICATION_JSON)
+                File::from(DEV_MIDDLEWARE_MANIFEST).with_content_type(APPLICATION_JSON)
+            }
+            DEV_MANIFESTS_PATHNAME => {
+                // Both manifests share the `find_routes` traversal, so they are built
+                // concurrently once it's done.
+                let (pages, build_manifest) =
+                    try_join(async { self_vc.find_route_pathnames().await }, async {
+                        self_vc.create_build_manifest().await
+                    })
+                    .await?;
+
+                File::from(serde_json::to_string(&combined_manifests(
+                    &pages,
+                    include_segments,
+                    trailing_slash,
+                    &build_manifest,
+                )?)?)
+                .with_content_type(APPLICATION_JSON)
+            }
+            DEV_HEADERS_MANIFEST_PATHNAME => {
+                let headers = self_vc.await?.next_config.headers().await?;
+
+                File::from(serde_json::to_string(&headers_manifest(&headers))?)
+                    .with_content_type(APPLICATION_JSON)
+            }
+            DEV_REDIRECTS_MANIFEST_PATHNAME => {
+                let redirects = self_vc.await?.next_config.redirects().await?;
+
+                File::from(serde_json::to_string(&redirects_manifest(&redirects))?)
+                    .with_content_type(APPLICATION_JSON)
+            }
+            DEV_MANIFEST_VERSION_PATHNAME => {
+                let version = self_vc.manifest_version().await?;
+
+                File::from(serde_json::to_string(&serde_json::json!({
+                    "version": *version,
+                    "session": this.session,
+                }))?)
+                .with_content_type(APPLICATION_JSON)
             }
             _ => bail!("unknown path: {}", path),
         };
@@ -231,23 +1655,249 @@ impl Introspectable for DevManifestContentSource {
     #[turbo_tasks::function]
     fn details(&self) -> StringVc {
         StringVc::cell(
-            "provides _devPagesManifest.json, _buildManifest.js and _devMiddlewareManifest.json."
+            "provides _devPagesManifest.json, _buildManifest.js, _devMiddlewareManifest.json, \
+             _headersManifest.json, _redirectsManifest.json, the combined _manifests.json and \
+             _manifestVersion.json, which changes when routes are added or removed and lists the \
+             dev server session."
                 .to_string(),
         )
     }
 }
 
+#[turbo_tasks::value(shared)]
+struct MissingRouteChunksIssue {
+    path: FileSystemPathVc,
+    pathnames: Vec<String>,
+    /// Whether the routes were omitted from the build manifest.
+    omitted: bool,
+}
+
+#[turbo_tasks::value_impl]
+impl Issue for MissingRouteChunksIssue {
+    #[turbo_tasks::function]
+    fn severity(&self) -> IssueSeverityVc {
+        IssueSeverity::Warning.into()
+    }
+
+    #[turbo_tasks::function]
+    fn category(&self) -> StringVc {
+        StringVc::cell("manifest".to_string())
+    }
+
+    #[turbo_tasks::function]
+    fn context(&self) -> FileSystemPathVc {
+        self.path
+    }
+
+    #[turbo_tasks::function]
+    fn title(&self) -> StringVc {
+        StringVc::cell("Routes in the build manifest have no chunk".to_string())
+    }
+
+    #[turbo_tasks::function]
+    fn description(&self) -> StringVc {
+        StringVc::cell(format!(
+            "No chunk is served for {}, so client-side navigation to {} fails. This usually means \
+             the page failed to compile.{}",
+            self.pathnames.join(", "),
+            if self.pathnames.len() == 1 {
+                "it"
+            } else {
+                "them"
+            },
+            if self.omitted {
+                " The routes were omitted from the build manifest."
+            } else {
+                ""
+            }
+        ))
+    }
+}
+
+#[turbo_tasks::value(shared)]
+struct InvalidChunkUrlTemplateIssue {
+    path: FileSystemPathVc,
+    template: String,
+    reason: String,
+}
+
+#[turbo_tasks::value_impl]
+impl Issue for InvalidChunkUrlTemplateIssue {
+    #[turbo_tasks::function]
+    fn severity(&self) -> IssueSeverityVc {
+        IssueSeverity::Error.into()
+    }
+
+    #[turbo_tasks::function]
+    fn category(&self) -> StringVc {
+        StringVc::cell("config".to_string())
+    }
+
+    #[turbo_tasks::function]
+    fn context(&self) -> FileSystemPathVc {
+        self.path
+    }
+
+    #[turbo_tasks::function]
+    fn title(&self) -> StringVc {
+        StringVc::cell(format!(
+            "Invalid experimental.chunkUrlTemplate \"{}\"",
+            self.template
+        ))
+    }
+
+    #[turbo_tasks::function]
+    fn description(&self) -> StringVc {
+        StringVc::cell(format!(
+            "{} The default chunk URLs are used instead.",
+            self.reason
+        ))
+    }
+}
+
+#[turbo_tasks::value(shared)]
+struct LargeBuildManifestIssue {
+    path: FileSystemPathVc,
+    description: String,
+}
+
+#[turbo_tasks::value_impl]
+impl Issue for LargeBuildManifestIssue {
+    #[turbo_tasks::function]
+    fn severity(&self) -> IssueSeverityVc {
+        IssueSeverity::Warning.into()
+    }
+
+    #[turbo_tasks::function]
+    fn category(&self) -> StringVc {
+        StringVc::cell("routing".to_string())
+    }
+
+    #[turbo_tasks::function]
+    fn context(&self) -> FileSystemPathVc {
+        self.path
+    }
+
+    #[turbo_tasks::function]
+    fn title(&self) -> StringVc {
+        StringVc::cell("The build manifest is very large".to_string())
+    }
+
+    #[turbo_tasks::function]
+    fn description(&self) -> StringVc {
+        StringVc::cell(self.description.clone())
+    }
+}
+
+#[turbo_tasks::value(shared)]
+struct ConflictingRouteIssue {
+    path: FileSystemPathVc,
+    pathname: String,
+    files: Vec<String>,
+}
+
+#[turbo_tasks::value_impl]
+impl Issue for ConflictingRouteIssue {
+    #[turbo_tasks::function]
+    fn severity(&self) -> IssueSeverityVc {
+        IssueSeverity::Error.into()
+    }
+
+    #[turbo_tasks::function]
+    fn category(&self) -> StringVc {
+        StringVc::cell("routing".to_string())
+    }
+
+    #[turbo_tasks::function]
+    fn context(&self) -> FileSystemPathVc {
+        self.path
+    }
+
+    #[turbo_tasks::function]
+    fn title(&self) -> StringVc {
+        StringVc::cell(format!(
+            "Conflicting app and page files define \"{}\"",
+            self.pathname
+        ))
+    }
+
+    #[turbo_tasks::function]
+    fn description(&self) -> StringVc {
+        StringVc::cell(format!(
+            "Both \"{}\" and \"{}\" define the route \"{}\", only the first one is served. Remove \
+             one of them.",
+            self.files[0], self.files[1], self.pathname
+        ))
+    }
+}
+
+#[turbo_tasks::value(shared)]
+struct AmbiguousRoutesIssue {
+    path: FileSystemPathVc,
+    conflicts: Vec<String>,
+}
+
+#[turbo_tasks::value_impl]
+impl Issue for AmbiguousRoutesIssue {
+    #[turbo_tasks::function]
+    fn severity(&self) -> IssueSeverityVc {
+        IssueSeverity::Error.into()
+    }
+
+    #[turbo_tasks::function]
+    fn category(&self) -> StringVc {
+        StringVc::cell("routing".to_string())
+    }
+
+    #[turbo_tasks::function]
+    fn context(&self) -> FileSystemPathVc {
+        self.path
+    }
+
+    #[turbo_tasks::function]
+    fn title(&self) -> StringVc {
+        StringVc::cell("Ambiguous dynamic routes".to_string())
+    }
+
+    #[turbo_tasks::function]
+    fn description(&self) -> StringVc {
+        StringVc::cell(format!(
+            "These routes can match the same path and are only told apart by precedence, because \
+             \"experimental.strictRoutePrecedence\" is enabled this is an error:\n{}",
+            self.conflicts.join("\n")
+        ))
+    }
+}
+
 /// PageSortKey is necessary because the next.js client code looks for matches
 /// in the order the pages are sent in the manifest,if they're sorted
 /// alphabetically this means \[slug] and \[\[catchall]] routes are prioritized
 /// over fixed paths, so we have to override the ordering with this.
-#[derive(Ord, PartialOrd, Eq, PartialEq)]
-enum PageSortKey {
+///
+/// Static segments sort before dynamic (`[slug]`, including `[...slug]`)
+/// segments, which sort before optional catch-all (`[[...slug]]`) segments.
+/// Static segments are ordered alphabetically among themselves.
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq)]
+pub enum PageSortKey {
     Static(String),
     Slug,
     CatchAll,
 }
 
+/// Returns the sort key of each segment of `pathname`. Comparing these sorts
+/// routes in the priority Next.js matches them with.
+///
+/// ```
+/// use next_core::manifest::route_sort_key;
+///
+/// let mut pathnames = vec!["/[id]", "/static"];
+/// pathnames.sort_by_key(|pathname| route_sort_key(pathname));
+/// assert_eq!(pathnames, ["/static", "/[id]"]);
+/// ```
+pub fn route_sort_key(pathname: &str) -> Vec<PageSortKey> {
+    pathname.split('/').map(PageSortKey::from).collect()
+}
+
 impl From<&str> for PageSortKey {
     fn from(value: &str) -> Self {
         if value.starts_with("[[") && value.ends_with("]]") {
@@ -259,3 +1909,1179 @@ impl From<&str> for PageSortKey {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+
+    use anyhow::Result;
+    use indexmap::IndexMap;
+    use tracing::{
+        span::{Attributes, Id},
+        Subscriber,
+    };
+    use tracing_subscriber::{
+        layer::{Context, SubscriberExt},
+        registry::LookupSpan,
+        Layer,
+    };
+    use turbo_tasks::Value;
+    use turbopack_binding::{
+        turbo::{
+            tasks::TurboTasks,
+            tasks_env::EnvMapVc,
+            tasks_fs::{FileContent, FileSystemPathVc},
+            tasks_memory::MemoryBackend,
+        },
+        turbopack::{
+            core::{asset::AssetContent, environment::ServerAddrVc, issue::IssueVc},
+            dev_server::source::{
+                ContentSourceContent, ContentSourceData, ContentSourceVc, GetContentSourceContentVc,
+            },
+        },
+    };
+
+    use super::{
+        ambiguous_routes, build_manifest_json, chunk_url_template_error, combined_manifests,
+        content_source_to_route, dev_pages_manifest, dynamic_route_entries, headers_manifest,
+        large_build_manifest_description, manifest_chunk_path, normalize_app_pathname,
+        normalize_trailing_slash, production_manifest_path, redirects_manifest,
+        render_build_manifest, route_chunk_path, route_hints, route_segments, route_sort_key,
+        route_source_file, route_source_stems, routes_version, templated_chunk_path, visit_bounded,
+        BuildManifest, DevManifestContentSource, DevManifestContentSourceBuilder,
+        DevManifestContentSourceVc, ManifestRoute, RenderMode, RouteKind, RouteOrigin,
+        RouteSegment, RouteSet, RouteTrie, DEV_BUILD_ID, DEV_MANIFESTS_PATHNAME,
+        DEV_MANIFEST_PATHNAME,
+    };
+    use crate::{
+        app_structure::{find_app_dir_if_enabled, OptionAppDirVc},
+        create_app_source, create_page_source,
+        embed_js::internal_code_root,
+        next_config::{
+            ExperimentalConfig, Header, ManifestChunkValidation, NextConfig, NextConfigVc,
+            Redirect, Rewrites,
+        },
+        pages_structure::find_pages_structure,
+        test_util::{execution_context, run_in_project},
+    };
+
+    fn sort_routes(routes: &mut Vec<ManifestRoute>) -> Vec<(ManifestRoute, ManifestRoute)> {
+        let (sorted, conflicts) = routes.drain(..).collect::<RouteSet>().into_sorted();
+        *routes = sorted;
+        conflicts
+    }
+
+    fn pages_route(pathname: &str) -> ManifestRoute {
+        ManifestRoute {
+            pathname: pathname.to_string(),
+            origin: RouteOrigin::Pages,
+            kind: RouteKind::Page,
+            source_pathname: None,
+        }
+    }
+
+    #[test]
+    fn test_route_chunk_path_by_origin() {
+        let page_route = pages_route("/blog");
+        let app_route = ManifestRoute {
+            pathname: "/dashboard".to_string(),
+            origin: RouteOrigin::App,
+            kind: RouteKind::Page,
+            source_pathname: None,
+        };
+
+        assert_eq!(
+            route_chunk_path(&page_route),
+            "_next/static/chunks/pages/blog.js"
+        );
+        assert_eq!(
+            route_chunk_path(&app_route),
+            "_next/static/chunks/app/dashboard.js"
+        );
+    }
+
+    #[test]
+    fn test_production_manifest_path() {
+        assert_eq!(
+            production_manifest_path("my-build-id", "_buildManifest.js"),
+            "static/my-build-id/_buildManifest.js"
+        );
+        assert_eq!(
+            production_manifest_path("my-build-id", "_ssgManifest.js"),
+            "static/my-build-id/_ssgManifest.js"
+        );
+    }
+
+    #[test]
+    fn test_manifest_chunk_path() {
+        assert_eq!(
+            manifest_chunk_path("/", RouteOrigin::Pages, ".js", "", ""),
+            "_next/static/chunks/pages/index.js"
+        );
+        assert_eq!(
+            manifest_chunk_path("/blog/[slug]/comments", RouteOrigin::App, ".js", "", ""),
+            "_next/static/chunks/app/blog/[slug]/comments.js"
+        );
+        assert_eq!(
+            manifest_chunk_path("/index/about", RouteOrigin::Pages, ".css", "", ""),
+            "_next/static/chunks/pages/index/index/about.css"
+        );
+    }
+
+    #[test]
+    fn test_manifest_chunk_path_prefixes() {
+        assert_eq!(
+            manifest_chunk_path("/blog", RouteOrigin::Pages, ".js", "", "/docs"),
+            "/docs/_next/static/chunks/pages/blog.js"
+        );
+        assert_eq!(
+            manifest_chunk_path(
+                "/blog",
+                RouteOrigin::Pages,
+                ".js",
+                "https://cdn.example.com/",
+                ""
+            ),
+            "https://cdn.example.com/_next/static/chunks/pages/blog.js"
+        );
+        assert_eq!(
+            manifest_chunk_path("/blog", RouteOrigin::Pages, ".js", "/assets", "/docs"),
+            "/assets/_next/static/chunks/pages/blog.js"
+        );
+    }
+
+    #[test]
+    fn test_normalize_trailing_slash() {
+        assert_eq!(normalize_trailing_slash("/about", true), "/about/");
+        assert_eq!(normalize_trailing_slash("/about/", true), "/about/");
+        assert_eq!(
+            normalize_trailing_slash("/blog/[slug]", true),
+            "/blog/[slug]/"
+        );
+        assert_eq!(normalize_trailing_slash("/", true), "/");
+    }
+
+    #[test]
+    fn test_normalize_without_trailing_slash() {
+        assert_eq!(normalize_trailing_slash("/about", false), "/about");
+        assert_eq!(normalize_trailing_slash("/about/", false), "/about");
+        assert_eq!(normalize_trailing_slash("/", false), "/");
+    }
+
+    #[test]
+    fn test_route_chunk_path_ignores_trailing_slash() {
+        assert_eq!(
+            route_chunk_path(&pages_route("/about/")),
+            "_next/static/chunks/pages/about.js"
+        );
+    }
+
+    /// Returns the titles of the issues `source` emits while creating its
+    /// build manifest.
+    async fn build_manifest_issues(source: DevManifestContentSourceVc) -> Result<Vec<String>> {
+        let issues = IssueVc::peek_issues_with_path(source.create_build_manifest())
+            .await?
+            .strongly_consistent()
+            .await?;
+        let mut titles = Vec::new();
+        for issue in issues.iter() {
+            titles.push(issue.title().await?.clone_value());
+        }
+        Ok(titles)
+    }
+
+    #[tokio::test]
+    async fn test_classified_routes() -> Result<()> {
+        let files = [
+            (
+                "pages/blog.js",
+                "export default function Blog() { return null }\nexport async function \
+                 getStaticProps() { return { props: {} } }",
+            ),
+            (
+                "pages/feed.js",
+                "export default function Feed() { return null }\nexport async function \
+                 getServerSideProps() { return { props: {} } }",
+            ),
+            ("pages/api/hello.js", "export default function handler() {}"),
+            (
+                "app/layout.js",
+                "export default function RootLayout({ children }) { return children }",
+            ),
+            (
+                "app/about/page.js",
+                "export default function About() { return null }",
+            ),
+            (
+                "app/dashboard/page.js",
+                "export const dynamic = 'force-dynamic'\nexport default function Dashboard() { \
+                 return null }",
+            ),
+        ];
+        let render_modes = run_in_project(&files, |project_path| async move {
+            let next_config = NextConfig {
+                experimental: ExperimentalConfig {
+                    app_dir: Some(true),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }
+            .cell();
+            let pages_structure = find_pages_structure(project_path, project_path, next_config);
+            let app_dir = find_app_dir_if_enabled(project_path, next_config);
+            let page_source = create_page_source(
+                pages_structure,
+                project_path,
+                execution_context(project_path),
+                project_path.join(".next/server/pages"),
+                project_path,
+                EnvMapVc::empty().into(),
+                "last 1 Chrome versions",
+                next_config,
+                ServerAddrVc::empty(),
+            );
+            let app_source = create_app_source(
+                app_dir,
+                project_path,
+                execution_context(project_path),
+                project_path.join(".next/server/app"),
+                project_path,
+                EnvMapVc::empty().into(),
+                "last 1 Chrome versions",
+                next_config,
+                ServerAddrVc::empty(),
+            );
+            let source = DevManifestContentSourceVc::builder(
+                project_path,
+                pages_structure,
+                app_dir,
+                next_config,
+            )
+            .page_roots(vec![page_source])
+            .app_roots(vec![app_source])
+            .session("session")
+            .build()?;
+            Ok(source
+                .classified_routes()
+                .await?
+                .iter()
+                .cloned()
+                .collect::<HashMap<_, _>>())
+        })
+        .await?;
+
+        assert_eq!(render_modes.get("/blog"), Some(&RenderMode::Static));
+        assert_eq!(render_modes.get("/feed"), Some(&RenderMode::Dynamic));
+        assert_eq!(render_modes.get("/api/hello"), Some(&RenderMode::Api));
+        assert_eq!(render_modes.get("/about"), Some(&RenderMode::Static));
+        assert_eq!(render_modes.get("/dashboard"), Some(&RenderMode::Dynamic));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_missing_page_chunks_reported() -> Result<()> {
+        let files = [
+            (
+                "pages/index.js",
+                "export default function Home() { return null }",
+            ),
+            (
+                "pages/blog.js",
+                "export default function Blog() { return null }",
+            ),
+        ];
+        let titles = run_in_project(&files, |project_path| async move {
+            let next_config = NextConfig {
+                experimental: ExperimentalConfig {
+                    manifest_chunk_validation: Some(ManifestChunkValidation::Warn),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }
+            .cell();
+            let page_source = create_page_source(
+                find_pages_structure(project_path, project_path, next_config),
+                project_path,
+                execution_context(project_path),
+                project_path.join(".next/server/pages"),
+                project_path,
+                EnvMapVc::empty().into(),
+                "last 1 Chrome versions",
+                next_config,
+                ServerAddrVc::empty(),
+            );
+            // The sources rendering the pages, without the page loaders serving
+            // their chunks.
+            let mut rendered_sources = Vec::new();
+            visit_bounded(
+                [page_source],
+                |source| async move {
+                    Ok((
+                        source.get_children().await?.clone_value(),
+                        content_source_to_route(source).await?.map(|_| source),
+                    ))
+                },
+                usize::MAX,
+                |source| rendered_sources.extend(source),
+            )
+            .await?;
+
+            let mut titles = Vec::new();
+            for page_roots in [vec![page_source], rendered_sources] {
+                let source = empty_source(project_path, next_config)
+                    .page_roots(page_roots)
+                    .session("session")
+                    .build()?;
+                titles.push(build_manifest_issues(source).await?);
+            }
+            Ok(titles)
+        })
+        .await?;
+
+        assert_eq!(titles[0], Vec::<String>::new());
+        assert_eq!(titles[1], ["Routes in the build manifest have no chunk"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_normalize_app_pathname() {
+        assert_eq!(normalize_app_pathname("/(marketing)/about"), "/about");
+        assert_eq!(normalize_app_pathname("/(marketing)"), "/");
+        assert_eq!(
+            normalize_app_pathname("/dashboard/@analytics/(charts)/views"),
+            "/dashboard/views"
+        );
+        assert_eq!(
+            normalize_app_pathname("/feed/(.)photo/[id]"),
+            "/feed/(.)photo/[id]"
+        );
+        assert_eq!(normalize_app_pathname("/blog/[slug]"), "/blog/[slug]");
+    }
+
+    #[test]
+    fn test_route_group_source_mapping() {
+        let route = ManifestRoute {
+            pathname: "/about".to_string(),
+            origin: RouteOrigin::App,
+            kind: RouteKind::Page,
+            source_pathname: Some("/(marketing)/about".to_string()),
+        };
+        assert_eq!(route_source_stems(&route), vec!["(marketing)/about/page"]);
+        assert_eq!(
+            route_chunk_path(&route),
+            "_next/static/chunks/app/(marketing)/about.js"
+        );
+    }
+
+    #[test]
+    fn test_templated_chunk_path() {
+        assert_eq!(
+            templated_chunk_path("static/[name].js", &pages_route("/blog")),
+            "static/pages/blog.js"
+        );
+
+        let chunk_path = templated_chunk_path("assets/js/[name].[hash].js", &pages_route("/blog"));
+        let hash = chunk_path
+            .strip_prefix("assets/js/pages/blog.")
+            .and_then(|rest| rest.strip_suffix(".js"))
+            .unwrap();
+        assert_eq!(hash.len(), 8);
+        assert!(hash.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(
+            templated_chunk_path("assets/js/[name].[hash].js", &pages_route("/about")),
+            chunk_path.replace("blog", "about")
+        );
+
+        assert_eq!(chunk_url_template_error("assets/js/[name].[hash].js"), None);
+        assert!(chunk_url_template_error("assets/js/[hash].js").is_some());
+    }
+
+    #[test]
+    fn test_route_segments() {
+        assert_eq!(
+            route_segments("/user/[id]/posts/[...rest]"),
+            vec![
+                RouteSegment::Static("user".to_string()),
+                RouteSegment::Dynamic("id".to_string()),
+                RouteSegment::Static("posts".to_string()),
+                RouteSegment::CatchAll("rest".to_string()),
+            ]
+        );
+        assert_eq!(
+            route_segments("/docs/[[...slug]]"),
+            vec![
+                RouteSegment::Static("docs".to_string()),
+                RouteSegment::OptionalCatchAll("slug".to_string()),
+            ]
+        );
+        assert!(route_segments("/").is_empty());
+    }
+
+    #[test]
+    fn test_ambiguous_routes() {
+        assert_eq!(
+            ambiguous_routes(&["/[a]/b", "/c/[b]", "/c/d", "/[a]/[b]"]),
+            vec![("/[a]/b", "/c/[b]")]
+        );
+        assert!(ambiguous_routes(&["/blog/[slug]", "/blog/new", "/[...rest]"]).is_empty());
+    }
+
+    #[test]
+    fn test_combined_manifests() {
+        let pages = vec!["/".to_string(), "/blog".to_string()];
+        let combined =
+            combined_manifests(&pages, false, false, "self.__BUILD_MANIFEST = {};").unwrap();
+
+        assert_eq!(
+            combined["devPagesManifest"],
+            serde_json::json!({ "pages": ["/", "/blog"] })
+        );
+        assert_eq!(combined["buildManifest"], "self.__BUILD_MANIFEST = {};");
+        assert_eq!(combined["devMiddlewareManifest"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn test_dev_pages_manifest_segments() {
+        let pages = vec!["/".to_string(), "/user/[id]".to_string()];
+        assert_eq!(
+            dev_pages_manifest(&pages, false, false),
+            serde_json::json!({ "pages": ["/", "/user/[id]"] })
+        );
+
+        let manifest = dev_pages_manifest(&pages, true, false);
+        assert_eq!(manifest["pages"], serde_json::json!(["/", "/user/[id]"]));
+        assert_eq!(
+            manifest["routes"][1],
+            serde_json::json!({
+                "page": "/user/[id]",
+                "url": "/user/[id]",
+                "segments": [
+                    { "type": "static", "name": "user" },
+                    { "type": "dynamic", "name": "id" },
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn test_dev_pages_manifest_trailing_slash() {
+        let pages = vec!["/".to_string(), "/about".to_string()];
+        let manifest = dev_pages_manifest(&pages, true, true);
+
+        // Pages stay keyed by their name, only the URLs get a trailing slash.
+        assert_eq!(manifest["pages"], serde_json::json!(["/", "/about"]));
+        assert_eq!(manifest["routes"][0]["page"], "/");
+        assert_eq!(manifest["routes"][0]["url"], "/");
+        assert_eq!(manifest["routes"][1]["page"], "/about");
+        assert_eq!(manifest["routes"][1]["url"], "/about/");
+
+        let manifest = dev_pages_manifest(&pages, true, false);
+        assert_eq!(manifest["routes"][1]["url"], "/about");
+    }
+
+    #[test]
+    fn test_sort_routes_is_deterministic() {
+        let pathnames = [
+            "/",
+            "/blog",
+            "/Blog",
+            "/blog/[slug]",
+            "/blog/[[...slug]]",
+            "/blog/new",
+            "/about",
+            "/About",
+        ];
+
+        let mut expected = pathnames
+            .iter()
+            .copied()
+            .map(pages_route)
+            .collect::<Vec<_>>();
+        sort_routes(&mut expected);
+
+        for shift in 1..pathnames.len() {
+            let mut shuffled = pathnames
+                .iter()
+                .copied()
+                .map(pages_route)
+                .collect::<Vec<_>>();
+            shuffled.rotate_left(shift);
+            if shift % 2 == 0 {
+                shuffled.reverse();
+            }
+            sort_routes(&mut shuffled);
+            assert_eq!(shuffled, expected);
+        }
+    }
+
+    #[test]
+    fn test_build_manifest_json_is_deterministic() {
+        let pathnames = [
+            "/",
+            "/blog/[slug]",
+            "/about",
+            "/blog/new",
+            "/docs/[[...slug]]",
+        ];
+        let rewrites = Rewrites::default();
+        let manifest_json = |pathnames: &[&str]| {
+            let (sorted, _) = pathnames
+                .iter()
+                .copied()
+                .map(pages_route)
+                .collect::<RouteSet>()
+                .into_sorted();
+            // Maps are filled in the order of the (shuffled) input, not the
+            // sorted routes.
+            let routes = pathnames
+                .iter()
+                .map(|&pathname| (pathname, vec![format!("static/chunks/pages{pathname}.js")]))
+                .collect::<IndexMap<_, _>>();
+            let manifest = BuildManifest {
+                rewrites: &rewrites,
+                sorted_pages: sorted.iter().map(|route| route.pathname.as_str()).collect(),
+                route_trie: Some(
+                    routes
+                        .iter()
+                        .map(|(pathname, files)| (*pathname, files.clone()))
+                        .collect(),
+                ),
+                dynamic_routes: Some(dynamic_route_entries(&sorted)),
+                route_hints: None,
+                routes,
+            };
+            build_manifest_json(&manifest).unwrap()
+        };
+
+        let expected = manifest_json(&pathnames);
+        for shift in 1..pathnames.len() {
+            let mut shuffled = pathnames;
+            shuffled.rotate_left(shift);
+            if shift % 2 == 0 {
+                shuffled.reverse();
+            }
+            assert_eq!(manifest_json(&shuffled), expected);
+        }
+    }
+
+    #[test]
+    fn test_sort_merged_routes() {
+        let mut routes = ["/blog/[slug]", "/"]
+            .iter()
+            .copied()
+            .map(pages_route)
+            .collect::<Vec<_>>();
+        routes.extend(
+            ["/blog/new", "/", "/dashboard"].map(|pathname| ManifestRoute {
+                pathname: pathname.to_string(),
+                origin: RouteOrigin::App,
+                kind: RouteKind::Page,
+                source_pathname: None,
+            }),
+        );
+
+        sort_routes(&mut routes);
+
+        assert_eq!(
+            routes
+                .iter()
+                .map(|route| route.pathname.as_str())
+                .collect::<Vec<_>>(),
+            ["/", "/blog/new", "/blog/[slug]", "/dashboard"]
+        );
+        assert_eq!(routes[0].origin, RouteOrigin::Pages);
+    }
+
+    #[test]
+    fn test_headers_manifest() {
+        let headers: Vec<Header> = serde_json::from_str(
+            r#"[{
+                "source": "/(.*)",
+                "headers": [{ "key": "X-Frame-Options", "value": "DENY" }],
+                "missing": [{ "type": "header", "key": "x-embed" }]
+            }]"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            headers_manifest(&headers),
+            serde_json::json!({
+                "headers": [{
+                    "source": "/(.*)",
+                    "headers": [{ "key": "X-Frame-Options", "value": "DENY" }],
+                    "missing": [{ "type": "header", "key": "x-embed" }]
+                }]
+            })
+        );
+    }
+
+    #[test]
+    fn test_redirects_manifest() {
+        let redirects: Vec<Redirect> = serde_json::from_str(
+            r#"[
+                { "source": "/old", "destination": "/new", "permanent": true },
+                { "source": "/sale", "destination": "/shop", "permanent": false }
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            redirects_manifest(&redirects),
+            serde_json::json!({
+                "redirects": [
+                    { "source": "/old", "destination": "/new", "statusCode": 308 },
+                    { "source": "/sale", "destination": "/shop", "statusCode": 307 }
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn test_routes_version() {
+        let routes = vec![pages_route("/"), pages_route("/about")];
+        let version = routes_version("session", &routes);
+
+        let unrelated_change = vec![
+            pages_route("/"),
+            ManifestRoute {
+                kind: RouteKind::Api,
+                ..pages_route("/about")
+            },
+        ];
+        assert_eq!(routes_version("session", &unrelated_change), version);
+
+        let added_route = vec![
+            pages_route("/"),
+            pages_route("/about"),
+            pages_route("/blog"),
+        ];
+        assert_ne!(routes_version("session", &added_route), version);
+
+        // A client still referencing the manifests of an earlier dev server
+        // session sees a different version, and requests them again.
+        assert_ne!(routes_version("earlier session", &routes), version);
+    }
+
+    #[tokio::test]
+    async fn test_manifest_version_per_session() -> Result<()> {
+        crate::register();
+        let tt = TurboTasks::new(MemoryBackend::default());
+        tt.run_once(async move {
+            let next_config = NextConfig::default().cell();
+            let project_path = internal_code_root();
+            let source = |session: &str| {
+                empty_source(project_path, next_config)
+                    .session(session)
+                    .build()
+            };
+
+            let version = source("session")?.manifest_version().await?;
+            assert_eq!(*source("session")?.manifest_version().await?, *version);
+            // A restarted dev server has another session, so it never serves
+            // the manifests cached for the earlier session.
+            assert_ne!(
+                *source("restarted session")?.manifest_version().await?,
+                *version
+            );
+            Ok(())
+        })
+        .await
+    }
+
+    /// Returns a builder for a manifest source without routes.
+    fn empty_source(
+        project_path: FileSystemPathVc,
+        next_config: NextConfigVc,
+    ) -> DevManifestContentSourceBuilder {
+        DevManifestContentSourceVc::builder(
+            project_path,
+            find_pages_structure(project_path, project_path, next_config),
+            OptionAppDirVc::cell(None),
+            next_config,
+        )
+    }
+
+    /// Returns the JSON `source` serves at `path`, looked up through its
+    /// routes like the dev server does.
+    async fn served_json(source: ContentSourceVc, path: &str) -> Result<serde_json::Value> {
+        for content_source in source.get_routes().get(path).await?.iter() {
+            let content = content_source
+                .get(path, Value::new(ContentSourceData::default()))
+                .await?;
+            let ContentSourceContent::Static(static_content) = *content else {
+                continue;
+            };
+            let asset_content = static_content.await?.content.content().await?;
+            let AssetContent::File(file_content) = *asset_content else {
+                continue;
+            };
+            if let FileContent::Content(file) = &*file_content.await? {
+                return Ok(serde_json::from_str(&file.content().to_str()?)?);
+            }
+        }
+        anyhow::bail!("{path} isn't served")
+    }
+
+    #[tokio::test]
+    async fn test_new_source_serves_manifests() -> Result<()> {
+        crate::register();
+        let tt = TurboTasks::new(MemoryBackend::default());
+        tt.run_once(async move {
+            let next_config = NextConfig::default().cell();
+            let project_path = internal_code_root();
+            let source = empty_source(project_path, next_config)
+                .session("session")
+                .build()?;
+
+            assert_eq!(
+                served_json(source.into(), DEV_MANIFEST_PATHNAME).await?,
+                serde_json::json!({ "pages": [] })
+            );
+            Ok(())
+        })
+        .await
+    }
+
+    /// Counts the `find routes` spans.
+    #[derive(Clone, Default)]
+    struct FindRoutesSpans(Arc<AtomicUsize>);
+
+    impl<S> Layer<S> for FindRoutesSpans
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        fn on_new_span(&self, attrs: &Attributes<'_>, _id: &Id, _ctx: Context<'_, S>) {
+            if attrs.metadata().name() == "find routes" {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_manifests_share_route_traversal() -> Result<()> {
+        let spans = FindRoutesSpans::default();
+        let _subscriber =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(spans.clone()));
+        let files = [
+            (
+                "pages/index.js",
+                "export default function Home() { return null }",
+            ),
+            ("pages/api/hello.js", "export default function handler() {}"),
+        ];
+        run_in_project(&files, |project_path| async move {
+            let source = empty_source(project_path, NextConfig::default().cell())
+                .session("session")
+                .build()?
+                .into();
+            served_json(source, DEV_MANIFESTS_PATHNAME).await?;
+            served_json(source, DEV_MANIFEST_PATHNAME).await?;
+            Ok(())
+        })
+        .await?;
+
+        // The pages and build manifests of `_manifests.json`, and the pages
+        // manifest served on its own, are built from a single traversal.
+        assert_eq!(spans.0.load(Ordering::SeqCst), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_build_manifest_served_for_build_id() -> Result<()> {
+        crate::register();
+        let tt = TurboTasks::new(MemoryBackend::default());
+        tt.run_once(async move {
+            let next_config = NextConfig::default().cell();
+            let project_path = internal_code_root();
+            let source = |build_id: &str| -> Result<GetContentSourceContentVc> {
+                Ok(empty_source(project_path, next_config)
+                    .build_id(build_id)
+                    .session("session")
+                    .build()?
+                    .into())
+            };
+            let get = |build_id: &str, path: &str| -> Result<_> {
+                Ok(source(build_id)?.get(path, Value::new(ContentSourceData::default())))
+            };
+
+            let content = get(DEV_BUILD_ID, "_next/static/development/_buildManifest.js")?.await?;
+            assert!(matches!(*content, ContentSourceContent::Static(_)));
+            let content = get("my-build-id", "_next/static/my-build-id/_buildManifest.js")?.await?;
+            assert!(matches!(*content, ContentSourceContent::Static(_)));
+            assert!(
+                get("my-build-id", "_next/static/development/_buildManifest.js")?
+                    .await
+                    .is_err()
+            );
+            Ok(())
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_builder_validates_ids() -> Result<()> {
+        crate::register();
+        let tt = TurboTasks::new(MemoryBackend::default());
+        tt.run_once(async move {
+            let next_config = NextConfig::default().cell();
+            let project_path = internal_code_root();
+
+            assert!(empty_source(project_path, next_config).build().is_err());
+            assert!(empty_source(project_path, next_config)
+                .session("session")
+                .build_id("my/build")
+                .build()
+                .is_err());
+            assert!(empty_source(project_path, next_config)
+                .session("session")
+                .build_id("my-build-id")
+                .build()
+                .is_ok());
+            Ok(())
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_merge_requires_matching_sources() -> Result<()> {
+        crate::register();
+        let tt = TurboTasks::new(MemoryBackend::default());
+        tt.run_once(async move {
+            let next_config = NextConfig::default().cell();
+            let project_path = internal_code_root();
+            let source = |build_id: &str, session: &str, next_config| {
+                empty_source(project_path, next_config)
+                    .build_id(build_id)
+                    .session(session)
+                    .build()
+            };
+            let merge = |other| -> Result<_> {
+                Ok(DevManifestContentSourceVc::merge(vec![
+                    source(DEV_BUILD_ID, "session", next_config)?,
+                    other,
+                ]))
+            };
+
+            assert!(merge(source(DEV_BUILD_ID, "session", next_config)?)?
+                .await
+                .is_ok());
+            assert!(merge(source("my-build-id", "session", next_config)?)?
+                .await
+                .is_err());
+            assert!(
+                merge(source(DEV_BUILD_ID, "restarted session", next_config)?)?
+                    .await
+                    .is_err()
+            );
+            let other_config = NextConfig {
+                dist_dir: Some("build".to_string()),
+                ..Default::default()
+            }
+            .cell();
+            assert!(merge(source(DEV_BUILD_ID, "session", other_config)?)?
+                .await
+                .is_err());
+            Ok(())
+        })
+        .await
+    }
+
+    #[test]
+    fn test_custom_build_manifest_template() {
+        assert_eq!(
+            render_build_manifest(
+                "globalThis.__MY_MANIFEST = /* manifest */;",
+                "/* manifest */",
+                r#"{"sortedPages":[]}"#
+            )
+            .unwrap(),
+            r#"globalThis.__MY_MANIFEST = {"sortedPages":[]};"#
+        );
+        assert!(render_build_manifest("self.__BUILD_MANIFEST = {}", "$$MANIFEST$$", "{}").is_err());
+    }
+
+    #[test]
+    fn test_large_build_manifest_warning() {
+        assert_eq!(large_build_manifest_description(1024, 10, 1024), None);
+
+        let description = large_build_manifest_description(4096, 250, 1024).unwrap();
+        assert!(description.contains("4096 bytes for 250 routes"));
+    }
+
+    #[tokio::test]
+    async fn test_large_build_manifest_issue() -> Result<()> {
+        crate::register();
+        let tt = TurboTasks::new(MemoryBackend::default());
+        tt.run_once(async move {
+            let project_path = internal_code_root();
+            let source = |next_config: NextConfig| {
+                empty_source(project_path, next_config.cell())
+                    .session("session")
+                    .build()
+            };
+
+            let titles = build_manifest_issues(source(NextConfig::default())?).await?;
+            assert_eq!(titles, Vec::<String>::new());
+
+            let next_config = NextConfig {
+                experimental: ExperimentalConfig {
+                    build_manifest_size_warning: Some(1),
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            let titles = build_manifest_issues(source(next_config)?).await?;
+            assert_eq!(titles, ["The build manifest is very large"]);
+            Ok(())
+        })
+        .await
+    }
+
+    #[test]
+    fn test_route_trie_round_trip() {
+        let routes = ["/", "/blog", "/blog/[slug]", "/blog/new", "/about"]
+            .map(|pathname| (pathname, vec![format!("{}.js", pathname)]));
+        let trie = routes.iter().cloned().collect::<RouteTrie>();
+
+        let json = serde_json::to_string(&trie).unwrap();
+        let trie: RouteTrie = serde_json::from_str(&json).unwrap();
+
+        let mut round_tripped = trie.routes().into_iter().collect::<Vec<_>>();
+        round_tripped.sort();
+        let mut expected = routes
+            .map(|(pathname, files)| (pathname.to_string(), files))
+            .to_vec();
+        expected.sort();
+        assert_eq!(round_tripped, expected);
+    }
+
+    #[test]
+    fn test_conflicting_root_routes() {
+        let mut routes = vec![
+            ManifestRoute {
+                pathname: "/".to_string(),
+                origin: RouteOrigin::App,
+                kind: RouteKind::Page,
+                source_pathname: None,
+            },
+            pages_route("/"),
+            pages_route("/about"),
+        ];
+
+        let conflicts = sort_routes(&mut routes);
+
+        assert_eq!(conflicts.len(), 1);
+        let (kept, removed) = &conflicts[0];
+        assert_eq!(route_source_stems(kept), vec!["index"]);
+        assert_eq!(route_source_stems(removed), vec!["page"]);
+        assert_eq!(routes.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_route_source_file() -> Result<()> {
+        let page = "export default function Page() {}\n";
+        let files = [
+            ("src/pages/index.tsx", page),
+            ("src/pages/blog/index.js", page),
+            ("app/page.tsx", page),
+            ("app/api/route.ts", page),
+        ];
+        run_in_project(&files, |project_path| async move {
+            let next_config = NextConfig::default().cell();
+            let source = |app_dir| DevManifestContentSource {
+                project_path,
+                page_roots: vec![],
+                app_roots: vec![],
+                pages_structure: find_pages_structure(project_path, project_path, next_config),
+                app_dir: OptionAppDirVc::cell(app_dir),
+                next_config,
+                build_id: DEV_BUILD_ID.to_string(),
+                session: "session".to_string(),
+            };
+            let app_route = |pathname: &str, kind| ManifestRoute {
+                origin: RouteOrigin::App,
+                kind,
+                ..pages_route(pathname)
+            };
+
+            let with_app_dir = source(Some(project_path.join("app")));
+            let mut files = Vec::new();
+            for route in [
+                pages_route("/"),
+                pages_route("/blog"),
+                pages_route("/about"),
+                app_route("/", RouteKind::Page),
+                app_route("/api", RouteKind::Api),
+            ] {
+                files.push(route_source_file(&with_app_dir, &route).await?);
+            }
+            assert_eq!(
+                files,
+                vec![
+                    "src/pages/index.tsx",
+                    "src/pages/blog/index.js",
+                    "src/pages/about",
+                    "app/page.tsx",
+                    "app/api/route.ts",
+                ]
+            );
+
+            let without_app_dir = source(None);
+            assert_eq!(
+                route_source_file(&without_app_dir, &app_route("/", RouteKind::Page)).await?,
+                "app/page"
+            );
+            Ok(())
+        })
+        .await
+    }
+
+    #[test]
+    fn test_route_set_matches_sorting_all_routes() {
+        let pathnames = (0..20_000)
+            .map(|i| match i % 4 {
+                0 => format!("/section-{}/page-{}", i % 97, i % 1_013),
+                1 => format!("/section-{}/[slug]", i % 89),
+                2 => format!("/section-{}/[[...rest]]", i % 83),
+                _ => format!("/Section-{}/nested/page-{}.tsx", i % 7, i % 501),
+            })
+            .collect::<Vec<_>>();
+        let routes = pathnames
+            .iter()
+            .enumerate()
+            .map(|(i, pathname)| ManifestRoute {
+                pathname: pathname.clone(),
+                origin: if i % 3 == 0 {
+                    RouteOrigin::App
+                } else {
+                    RouteOrigin::Pages
+                },
+                kind: RouteKind::Page,
+                source_pathname: None,
+            })
+            .collect::<Vec<_>>();
+
+        let mut expected = routes.clone();
+        expected.sort_by_cached_key(|route| {
+            (
+                route_sort_key(&route.pathname),
+                route.pathname.clone(),
+                route.origin,
+            )
+        });
+        let mut expected_conflicts = Vec::new();
+        expected.dedup_by(|removed, kept| {
+            if removed.pathname != kept.pathname {
+                return false;
+            }
+            if removed.origin != kept.origin {
+                expected_conflicts.push((kept.clone(), removed.clone()));
+            }
+            true
+        });
+        // Identical routes are only stored once.
+        expected_conflicts.dedup();
+
+        let (sorted, conflicts) = routes.into_iter().rev().collect::<RouteSet>().into_sorted();
+        assert_eq!(sorted, expected);
+        assert_eq!(conflicts, expected_conflicts);
+    }
+
+    #[test]
+    fn test_sort_routes_prioritizes_static_segments() {
+        let mut routes = vec![
+            pages_route("/blog/[[...slug]]"),
+            pages_route("/blog/[slug]"),
+            pages_route("/blog/new"),
+            pages_route("/blog/new"),
+        ];
+        sort_routes(&mut routes);
+
+        assert_eq!(
+            routes,
+            vec![
+                pages_route("/blog/new"),
+                pages_route("/blog/[slug]"),
+                pages_route("/blog/[[...slug]]"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dynamic_route_fallbacks() {
+        let routes = [
+            "/",
+            "/blog/new",
+            "/blog/[slug]",
+            "/docs/[...path]",
+            "/shop/[[...path]]",
+        ]
+        .map(pages_route);
+
+        let entries = dynamic_route_entries(&routes);
+        assert_eq!(
+            entries.iter().map(|entry| entry.page).collect::<Vec<_>>(),
+            ["/blog/[slug]", "/docs/[...path]", "/shop/[[...path]]"]
+        );
+
+        let matches = |page: &str, pathname: &str| {
+            let entry = entries.iter().find(|entry| entry.page == page).unwrap();
+            regex::Regex::new(&entry.regex).unwrap().is_match(pathname)
+        };
+        assert!(matches("/blog/[slug]", "/blog/hello-world"));
+        assert!(matches("/blog/[slug]", "/blog/hello-world/"));
+        assert!(!matches("/blog/[slug]", "/blog/hello/world"));
+        assert!(matches("/docs/[...path]", "/docs/a/b/c"));
+        assert!(!matches("/docs/[...path]", "/docs"));
+        assert!(matches("/shop/[[...path]]", "/shop"));
+        assert!(matches("/shop/[[...path]]", "/shop/shoes/red"));
+    }
+
+    #[test]
+    fn test_preload_route_hints() {
+        let routes = ["/", "/blog", "/blog/[slug]"].map(pages_route);
+
+        let hints = route_hints(&routes, &["/blog/[slug]".to_string()]);
+        assert_eq!(
+            serde_json::to_value(&hints).unwrap(),
+            serde_json::json!({ "/blog/[slug]": { "preload": true } })
+        );
+
+        // Routes which don't exist aren't hinted.
+        assert!(route_hints(&routes, &["/about".to_string()]).is_empty());
+    }
+
+    #[test]
+    fn test_visit_bounded() {
+        // A tree of 10,000 nodes where every node also links back to the root.
+        const NODES: u32 = 10_000;
+        let visit = |node: u32| async move {
+            let children = (1..=2)
+                .map(|i| node * 2 + i)
+                .filter(|&child| child < NODES)
+                .chain([0])
+                .collect::<Vec<_>>();
+            Ok::<_, anyhow::Error>((children, node))
+        };
+
+        let visited = |limit| {
+            let mut nodes = Vec::new();
+            futures::executor::block_on(visit_bounded([0], visit, limit, |node| nodes.push(node)))
+                .unwrap();
+            nodes.sort_unstable();
+            nodes
+        };
+
+        let unbounded = visited(usize::MAX);
+        assert_eq!(unbounded, (0..NODES).collect::<Vec<_>>());
+        for limit in [0, 1, 3] {
+            assert_eq!(visited(limit), unbounded);
+        }
+    }
+}
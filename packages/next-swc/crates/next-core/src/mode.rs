@@ -1,7 +1,7 @@
 use turbo_tasks::TaskInput;
 
 /// The mode in which Next.js is running.
-#[derive(Debug, Copy, Clone, TaskInput)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, TaskInput)]
 pub enum NextMode {
     /// `next dev`
     Development,
@@ -10,7 +10,15 @@ pub enum NextMode {
 }
 
 impl NextMode {
-    /// Returns the NODE_ENV value for the current mode.
+    /// Every mode paired with the NODE_ENV value it is compiled with.
+    pub const NODE_ENVS: [(NextMode, &'static str); 2] = [
+        (NextMode::Development, "development"),
+        (NextMode::Build, "production"),
+    ];
+
+    /// Returns the NODE_ENV value for the current mode. This is the value
+    /// used for the `process.env.NODE_ENV` define and as a resolve
+    /// condition.
     pub fn node_env(&self) -> &'static str {
         match self {
             NextMode::Development => "development",
@@ -18,6 +26,15 @@ impl NextMode {
         }
     }
 
+    /// Returns the mode which is compiled with the given NODE_ENV value, if
+    /// any.
+    pub fn from_node_env(node_env: &str) -> Option<NextMode> {
+        Self::NODE_ENVS
+            .iter()
+            .find(|(_, value)| *value == node_env)
+            .map(|(mode, _)| *mode)
+    }
+
     /// Returns true if the development React runtime should be used.
     pub fn is_react_development(&self) -> bool {
         match self {
@@ -26,3 +43,28 @@ impl NextMode {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::NextMode;
+
+    #[test]
+    fn test_node_env_round_trip() {
+        for node_env in ["development", "production"] {
+            let mode = NextMode::from_node_env(node_env).unwrap();
+            assert_eq!(mode.node_env(), node_env);
+        }
+    }
+
+    #[test]
+    fn test_node_envs_table() {
+        for (mode, node_env) in NextMode::NODE_ENVS {
+            assert_eq!(mode.node_env(), node_env);
+        }
+    }
+
+    #[test]
+    fn test_unknown_node_env() {
+        assert_eq!(NextMode::from_node_env("test"), None);
+    }
+}
@@ -42,7 +42,7 @@ use turbopack_binding::{
 use super::transforms::get_next_client_transforms_rules;
 use crate::{
     babel::maybe_add_babel_loader,
-    embed_js::next_js_fs,
+    embed_js::internal_code_root,
     env::env_for_js,
     mode::NextMode,
     next_build::{get_external_next_compiled_package_mapping, get_postcss_package_mapping},
@@ -55,7 +55,9 @@ use crate::{
     next_shared::{
         resolve::UnsupportedModulesResolvePluginVc,
         transforms::{
+            auto_import_react::get_auto_import_react_transform_plugin,
             emotion::get_emotion_transform_plugin, get_relay_transform_plugin,
+            strip_comments::get_strip_comments_transform_plugin,
             styled_components::get_styled_components_transform_plugin,
             styled_jsx::get_styled_jsx_transform_plugin,
         },
@@ -63,7 +65,7 @@ use crate::{
     sass::maybe_add_sass_loader,
     transform_options::{
         get_decorators_transform_options, get_jsx_transform_options,
-        get_typescript_transform_options,
+        get_typescript_transform_options, is_react_aliased_to_preact,
     },
     util::foreign_code_context_condition,
 };
@@ -174,12 +176,13 @@ pub async fn get_client_module_options_context(
     mode: NextMode,
     next_config: NextConfigVc,
 ) -> Result<ModuleOptionsContextVc> {
-    let custom_rules = get_next_client_transforms_rules(next_config, ty.into_value()).await?;
+    let custom_rules =
+        get_next_client_transforms_rules(project_path, next_config, ty.into_value()).await?;
     let resolve_options_context =
         get_client_resolve_options_context(project_path, ty, mode, next_config, execution_context);
 
-    let tsconfig = get_typescript_transform_options(project_path);
-    let decorators_options = get_decorators_transform_options(project_path);
+    let tsconfig = get_typescript_transform_options(project_path, next_config);
+    let decorators_options = get_decorators_transform_options(project_path, next_config);
     let enable_mdx_rs = if *next_config.mdx_rs().await? {
         Some(
             MdxTransformModuleOptions {
@@ -190,8 +193,12 @@ pub async fn get_client_module_options_context(
     } else {
         None
     };
-    let jsx_runtime_options =
-        get_jsx_transform_options(project_path, mode, Some(resolve_options_context));
+    let jsx_runtime_options = get_jsx_transform_options(
+        project_path,
+        mode,
+        Some(resolve_options_context),
+        next_config,
+    );
     let webpack_rules =
         *maybe_add_babel_loader(project_path, *next_config.webpack_rules().await?).await?;
     let webpack_rules = maybe_add_sass_loader(next_config.sass_config(), webpack_rules).await?;
@@ -207,9 +214,10 @@ pub async fn get_client_module_options_context(
 
     let source_transforms = vec![
         *get_relay_transform_plugin(next_config).await?,
-        *get_emotion_transform_plugin(next_config).await?,
-        *get_styled_components_transform_plugin(next_config).await?,
-        *get_styled_jsx_transform_plugin().await?,
+        *get_emotion_transform_plugin(next_config, project_path).await?,
+        *get_auto_import_react_transform_plugin(next_config).await?,
+        *get_styled_components_transform_plugin(next_config, project_path).await?,
+        *get_styled_jsx_transform_plugin(next_config).await?,
         Some(TransformPluginVc::cell(Box::new(
             ServerDirectiveTransformer::new(
                 // ServerDirective is not implemented yet and always reports an issue.
@@ -225,7 +233,9 @@ pub async fn get_client_module_options_context(
     let custom_ecma_transform_plugins = Some(CustomEcmascriptTransformPluginsVc::cell(
         CustomEcmascriptTransformPlugins {
             source_transforms,
-            output_transforms: vec![],
+            output_transforms: (*get_strip_comments_transform_plugin(next_config, mode).await?)
+                .into_iter()
+                .collect(),
         },
     ));
 
@@ -261,7 +271,7 @@ pub async fn get_client_module_options_context(
             // If the module is an internal asset (i.e overlay, fallback) coming from the embedded
             // FS, don't apply user defined transforms.
             (
-                ContextCondition::InPath(next_js_fs().root()),
+                ContextCondition::InPath(internal_code_root()),
                 ModuleOptionsContext {
                     enable_typescript_transform: Some(TypescriptTransformOptions::default().cell()),
                     enable_jsx: Some(JsxTransformOptions::default().cell()),
@@ -363,10 +373,13 @@ pub async fn get_client_runtime_entries(
                 next_config,
                 execution_context,
             );
-            let enable_react_refresh =
+            let enable_react_refresh = if is_react_aliased_to_preact(next_config).await? {
+                None
+            } else {
                 assert_can_resolve_react_refresh(project_root, resolve_options_context)
                     .await?
-                    .as_request();
+                    .as_request()
+            };
 
             // It's important that React Refresh come before the regular bootstrap file,
             // because the bootstrap contains JSX which requires Refresh's global
@@ -381,7 +394,7 @@ pub async fn get_client_runtime_entries(
                     RequestVc::parse(Value::new(Pattern::Constant(
                         "./build/client/bootstrap.ts".to_string(),
                     ))),
-                    next_js_fs().root().join("_"),
+                    internal_code_root().join("_"),
                 )
                 .cell(),
             );
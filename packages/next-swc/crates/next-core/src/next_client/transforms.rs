@@ -1,30 +1,41 @@
 use anyhow::Result;
 use next_transform_strip_page_exports::ExportFilter;
-use turbopack_binding::turbopack::turbopack::module_options::ModuleRule;
+use turbopack_binding::{
+    turbo::tasks_fs::FileSystemPathVc, turbopack::turbopack::module_options::ModuleRule,
+};
 
 use crate::{
     next_client::context::ClientContextType,
     next_config::NextConfigVc,
     next_shared::transforms::{
-        get_next_dynamic_transform_rule, get_next_font_transform_rule, get_next_image_rule,
-        get_next_modularize_imports_rule, get_next_pages_transforms_rule,
+        get_markdown_unsupported_rule, get_next_dynamic_transform_rule,
+        get_next_font_transform_rule, get_next_image_rule, get_next_modularize_imports_rule,
+        get_next_pages_transforms_rule, get_relay_transform_rule, get_swcrc_target_rule,
     },
 };
 
 /// Returns a list of module rules which apply client-side, Next.js-specific
 /// transforms.
 pub async fn get_next_client_transforms_rules(
+    project_path: FileSystemPathVc,
     next_config: NextConfigVc,
     context_ty: ClientContextType,
 ) -> Result<Vec<ModuleRule>> {
     let mut rules = vec![];
 
+    if let Some(relay_rule) = get_relay_transform_rule(project_path, next_config).await? {
+        rules.push(relay_rule);
+    }
+
     let modularize_imports_config = &next_config.await?.modularize_imports;
     if let Some(modularize_imports_config) = modularize_imports_config {
         rules.push(get_next_modularize_imports_rule(modularize_imports_config));
     }
 
     rules.push(get_next_font_transform_rule());
+    if let Some(swcrc_target_rule) = get_swcrc_target_rule(project_path, next_config).await? {
+        rules.push(swcrc_target_rule);
+    }
 
     let pages_dir = match context_ty {
         ClientContextType::Pages { pages_dir } => {
@@ -42,5 +53,9 @@ pub async fn get_next_client_transforms_rules(
 
     rules.push(get_next_image_rule());
 
+    if let Some(markdown_rule) = get_markdown_unsupported_rule(next_config, None).await? {
+        rules.push(markdown_rule);
+    }
+
     Ok(rules)
 }
@@ -1,9 +1,14 @@
 use anyhow::Result;
-use turbo_tasks::Value;
+use turbo_tasks::{primitives::StringVc, Value};
 use turbopack_binding::{
     turbo::tasks_fs::FileSystemPathVc,
     turbopack::{
-        core::{asset::AssetVc, chunk::ChunkingContextVc, compile_time_info::CompileTimeInfoVc},
+        core::{
+            asset::{Asset, AssetVc},
+            chunk::ChunkingContextVc,
+            compile_time_info::CompileTimeInfoVc,
+            issue::{Issue, IssueSeverity, IssueSeverityVc, IssueVc},
+        },
         node::execution_context::ExecutionContextVc,
         turbopack::{
             ecmascript::chunk::EcmascriptChunkPlaceableVc,
@@ -104,22 +109,150 @@ impl Transition for NextClientChunksTransition {
     }
 
     #[turbo_tasks::function]
-    async fn process_module(
-        &self,
-        asset: AssetVc,
-        _context: ModuleAssetContextVc,
-    ) -> Result<AssetVc> {
-        Ok(
-            if let Some(placeable) = EcmascriptChunkPlaceableVc::resolve_from(asset).await? {
-                WithChunksAsset {
-                    asset: placeable,
-                    chunking_context: self.client_chunking_context,
-                }
-                .cell()
-                .into()
-            } else {
-                asset
-            },
-        )
+    fn process_module(&self, asset: AssetVc, _context: ModuleAssetContextVc) -> AssetVc {
+        with_client_chunks(asset, self.client_chunking_context)
+    }
+}
+
+/// Wraps `asset` so it's loaded with its client chunks. Assets which aren't
+/// JavaScript modules are returned as is, with a [NonModuleDynamicImportIssue].
+#[turbo_tasks::function]
+async fn with_client_chunks(
+    asset: AssetVc,
+    chunking_context: ChunkingContextVc,
+) -> Result<AssetVc> {
+    Ok(
+        if let Some(placeable) = EcmascriptChunkPlaceableVc::resolve_from(asset).await? {
+            WithChunksAsset {
+                asset: placeable,
+                chunking_context,
+            }
+            .cell()
+            .into()
+        } else {
+            let path = asset.ident().path();
+            NonModuleDynamicImportIssue {
+                path,
+                file: path.await?.path.clone(),
+            }
+            .cell()
+            .as_issue()
+            .emit();
+            asset
+        },
+    )
+}
+
+/// Returns the guidance for a `next/dynamic` import of `file`, which isn't a
+/// JavaScript module.
+fn non_module_dynamic_import_description(file: &str) -> String {
+    let extension = file.rsplit_once('.').map(|(_, extension)| extension);
+    let guidance = match extension {
+        Some("css" | "scss" | "sass" | "less") => {
+            "Stylesheets are loaded with the components using them. Import it at the top of such a \
+             component instead, e.g. `import './styles.css'`."
+        }
+        Some("json") => {
+            "Import the JSON with a regular import, or with `import()` outside of `dynamic()` to \
+             load it lazily."
+        }
+        _ => "Only modules exporting a React component can be loaded with `dynamic()`.",
+    };
+    format!("{file} is imported with next/dynamic, but isn't a JavaScript module. {guidance}")
+}
+
+#[turbo_tasks::value(shared)]
+struct NonModuleDynamicImportIssue {
+    path: FileSystemPathVc,
+    file: String,
+}
+
+#[turbo_tasks::value_impl]
+impl Issue for NonModuleDynamicImportIssue {
+    #[turbo_tasks::function]
+    fn severity(&self) -> IssueSeverityVc {
+        IssueSeverity::Error.into()
+    }
+
+    #[turbo_tasks::function]
+    fn category(&self) -> StringVc {
+        StringVc::cell("resolve".to_string())
+    }
+
+    #[turbo_tasks::function]
+    fn context(&self) -> FileSystemPathVc {
+        self.path
+    }
+
+    #[turbo_tasks::function]
+    fn title(&self) -> StringVc {
+        StringVc::cell("next/dynamic can only load JavaScript modules".to_string())
+    }
+
+    #[turbo_tasks::function]
+    fn description(&self) -> StringVc {
+        StringVc::cell(non_module_dynamic_import_description(&self.file))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use turbopack_binding::{
+        turbo::{
+            tasks::TurboTasks,
+            tasks_fs::{FileSystem, VirtualFileSystemVc},
+            tasks_memory::MemoryBackend,
+        },
+        turbopack::core::{issue::IssueVc, source_asset::SourceAssetVc},
+    };
+
+    use super::{non_module_dynamic_import_description, with_client_chunks};
+    use crate::{
+        mode::NextMode,
+        next_client::context::{get_client_chunking_context, get_client_compile_time_info},
+    };
+
+    #[tokio::test]
+    async fn test_non_module_dynamic_import_issue() -> Result<()> {
+        crate::register();
+        let tt = TurboTasks::new(MemoryBackend::default());
+        tt.run_once(async move {
+            let root = VirtualFileSystemVc::new().as_file_system().root();
+            let chunking_context = get_client_chunking_context(
+                root,
+                root.join(".next"),
+                get_client_compile_time_info(NextMode::Development, "last 1 Chrome versions")
+                    .environment(),
+            );
+            let stylesheet = SourceAssetVc::new(root.join("components/styles.css")).into();
+
+            let asset = with_client_chunks(stylesheet, chunking_context);
+            // The stylesheet is returned as is.
+            assert_eq!(asset.resolve().await?, stylesheet.resolve().await?);
+
+            let issues = IssueVc::peek_issues_with_path(asset)
+                .await?
+                .strongly_consistent()
+                .await?;
+            let mut titles = Vec::new();
+            for issue in issues.iter() {
+                titles.push(issue.title().await?.clone_value());
+            }
+            assert_eq!(titles, ["next/dynamic can only load JavaScript modules"]);
+            Ok(())
+        })
+        .await
+    }
+
+    #[test]
+    fn test_non_module_dynamic_import_description() {
+        assert_eq!(
+            non_module_dynamic_import_description("components/styles.css"),
+            "components/styles.css is imported with next/dynamic, but isn't a JavaScript module. \
+             Stylesheets are loaded with the components using them. Import it at the top of such \
+             a component instead, e.g. `import './styles.css'`."
+        );
+        assert!(non_module_dynamic_import_description("data.json").ends_with("to load it lazily."));
     }
 }
@@ -58,7 +58,13 @@ pub struct NextConfig {
     pub page_extensions: Vec<String>,
     pub react_strict_mode: Option<bool>,
     pub rewrites: Rewrites,
+    pub headers: Vec<Header>,
+    pub redirects: Vec<Redirect>,
     pub transpile_packages: Option<Vec<String>>,
+    /// Bundles the dependencies of Pages Router server code like the App
+    /// Router does, instead of loading them from `node_modules` at runtime.
+    /// `experimental.serverComponentsExternalPackages` are still external.
+    pub bundle_pages_router_dependencies: Option<bool>,
     pub modularize_imports: Option<IndexMap<String, ModularizeImportPackageConfig>>,
     sass_options: Option<serde_json::Value>,
 
@@ -67,6 +73,22 @@ pub struct NextConfig {
 
     pub output: Option<OutputType>,
 
+    pub skip_middleware_url_normalize: Option<bool>,
+    pub skip_trailing_slash_redirect: Option<bool>,
+
+    /// Whether responses get an `X-Powered-By` header. Defaults to `true`.
+    pub powered_by_header: Option<bool>,
+    /// Whether ETags are generated for pages. Defaults to `true`.
+    pub generate_etags: Option<bool>,
+    /// Whether rendered content is gzip compressed. Defaults to `true`.
+    pub compress: Option<bool>,
+    /// The directory, relative to the project root, the build output is
+    /// written to. Defaults to `.next`. URL paths like `/_next/static/...`
+    /// are not affected.
+    pub dist_dir: Option<String>,
+
+    pub turbopack: Option<TurbopackConfig>,
+
     // unsupported
     cross_origin: Option<String>,
     amp: AmpConfig,
@@ -74,26 +96,20 @@ pub struct NextConfig {
     asset_prefix: String,
     base_path: String,
     clean_dist_dir: bool,
-    compress: bool,
     dev_indicators: DevIndicatorsConfig,
-    dist_dir: String,
     eslint: EslintConfig,
     exclude_default_moment_locales: bool,
     // this can be a function in js land
     export_path_map: Option<serde_json::Value>,
     // this is a function in js land
     generate_build_id: Option<serde_json::Value>,
-    generate_etags: bool,
-    headers: Vec<Header>,
     http_agent_options: HttpAgentConfig,
     i18n: Option<I18NConfig>,
     on_demand_entries: OnDemandEntriesConfig,
     optimize_fonts: bool,
     output_file_tracing: bool,
-    powered_by_header: bool,
     production_browser_source_maps: bool,
     public_runtime_config: IndexMap<String, serde_json::Value>,
-    redirects: Vec<Redirect>,
     server_runtime_config: IndexMap<String, serde_json::Value>,
     static_page_generation_timeout: f64,
     swc_minify: bool,
@@ -217,6 +233,26 @@ pub struct Header {
     pub missing: Option<Vec<RouteHas>>,
 }
 
+#[turbo_tasks::value(transparent)]
+pub struct Headers(Vec<Header>);
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, TraceRawVcs)]
+#[serde(untagged)]
+pub enum ClientDirectiveConfig {
+    Enabled(bool),
+    Transition { transition: String },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, TraceRawVcs)]
+#[serde(rename_all = "camelCase")]
+pub struct BuildManifestTemplate {
+    /// The path of the template, relative to the project root.
+    pub path: String,
+    /// The placeholder in the template which is replaced by the manifest JSON.
+    /// Defaults to `$$MANIFEST$$`, like in the built-in template.
+    pub placeholder: Option<String>,
+}
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize, TraceRawVcs)]
 #[serde(rename_all = "camelCase")]
 pub enum RedirectStatus {
@@ -242,6 +278,21 @@ pub struct Redirect {
     pub status: RedirectStatus,
 }
 
+impl Redirect {
+    /// Returns the HTTP status code of the redirect. Permanent redirects use
+    /// 308 and temporary ones 307, so the request method is preserved.
+    pub fn status_code(&self) -> u16 {
+        match self.status {
+            RedirectStatus::StatusCode(status_code) => status_code as u16,
+            RedirectStatus::Permanent(true) => 308,
+            RedirectStatus::Permanent(false) => 307,
+        }
+    }
+}
+
+#[turbo_tasks::value(transparent)]
+pub struct Redirects(Vec<Redirect>);
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize, TraceRawVcs)]
 #[serde(rename_all = "camelCase")]
 pub struct Rewrite {
@@ -281,6 +332,9 @@ pub struct ImageConfig {
     pub image_sizes: Vec<u16>,
     pub path: String,
     pub loader: ImageLoader,
+    /// The file exporting the loader function for `loader: "custom"`,
+    /// relative to the project root.
+    pub loader_file: Option<String>,
     pub domains: Vec<String>,
     pub disable_static_images: bool,
     #[serde(rename(deserialize = "minimumCacheTTL"))]
@@ -301,6 +355,7 @@ impl Default for ImageConfig {
             image_sizes: vec![16, 32, 48, 64, 96, 128, 256, 384],
             path: "/_next/image".to_string(),
             loader: ImageLoader::Default,
+            loader_file: None,
             domains: vec![],
             disable_static_images: false,
             minimum_cache_ttl: 60,
@@ -350,6 +405,58 @@ pub enum RemotePatternProtocal {
     Https,
 }
 
+/// Declarative replacements for options of the `webpack` function, which
+/// Turbopack can't run.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, TraceRawVcs)]
+#[serde(rename_all = "camelCase")]
+pub struct TurbopackConfig {
+    /// Aliases like the `resolve.alias` a `webpack` function adds. They take
+    /// precedence over `experimental.turbo.resolveAlias`.
+    pub resolve_alias: Option<IndexMap<String, JsonValue>>,
+}
+
+/// Returns the aliases of `experimental.turbo.resolveAlias` merged with those
+/// of `turbopack.resolveAlias`, or `None` if neither is configured.
+pub fn merged_resolve_alias(next_config: &NextConfig) -> Option<IndexMap<String, JsonValue>> {
+    let experimental = next_config
+        .experimental
+        .turbo
+        .as_ref()
+        .and_then(|turbo| turbo.resolve_alias.as_ref());
+    let turbopack = next_config
+        .turbopack
+        .as_ref()
+        .and_then(|turbopack| turbopack.resolve_alias.as_ref());
+    if experimental.is_none() && turbopack.is_none() {
+        return None;
+    }
+    Some(
+        experimental
+            .into_iter()
+            .chain(turbopack)
+            .flatten()
+            .map(|(request, alias)| (request.clone(), alias.clone()))
+            .collect(),
+    )
+}
+
+/// Returns the `distDir` of `next_config`, without a leading `./` and trailing
+/// slashes, or `.next` if none is configured.
+pub fn dist_dir(next_config: &NextConfig) -> &str {
+    next_config
+        .dist_dir
+        .as_deref()
+        .map(|dist_dir| dist_dir.trim_start_matches("./").trim_end_matches('/'))
+        .filter(|dist_dir| !dist_dir.is_empty())
+        .unwrap_or(".next")
+}
+
+/// Returns whether a `webpack` function is configured without any declarative
+/// aliases, so whatever it configures is ignored.
+pub fn has_untranslated_webpack_config(next_config: &NextConfig) -> bool {
+    next_config.webpack.is_some() && merged_resolve_alias(next_config).is_none()
+}
+
 #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, TraceRawVcs)]
 #[serde(rename_all = "camelCase")]
 pub struct ExperimentalTurboConfig {
@@ -357,8 +464,23 @@ pub struct ExperimentalTurboConfig {
     pub loaders: Option<JsonValue>,
     pub rules: Option<IndexMap<String, RuleConfigItem>>,
     pub resolve_alias: Option<IndexMap<String, JsonValue>>,
+    /// Transforms to apply to specific packages in node_modules, which are
+    /// otherwise compiled without any transforms.
+    pub foreign_code_transforms: Option<IndexMap<String, ForeignCodeTransforms>>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, TraceRawVcs)]
+#[serde(rename_all = "camelCase")]
+pub struct ForeignCodeTransforms {
+    #[serde(default)]
+    pub typescript: bool,
+    #[serde(default)]
+    pub jsx: bool,
 }
 
+#[turbo_tasks::value(transparent)]
+pub struct ForeignCodeTransformsMap(IndexMap<String, ForeignCodeTransforms>);
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize, TraceRawVcs)]
 #[serde(rename_all = "camelCase", untagged)]
 pub enum RuleConfigItem {
@@ -388,6 +510,118 @@ pub struct ExperimentalConfig {
     pub isr_memory_cache_size: Option<f64>,
     pub isr_flush_to_disk: Option<bool>,
     mdx_rs: Option<bool>,
+    /// Adds a `process.env.__NEXT_PREVIEW = true` define to server code, for
+    /// dedicated preview deployments.
+    pub preview_build: Option<bool>,
+    /// Whether native Node.js addons (`.node` files) can be resolved during
+    /// server-side rendering of the app directory. Defaults to `true`.
+    pub ssr_native_modules: Option<bool>,
+    /// Whether native Node.js addons (`.node` files) can be resolved in server
+    /// contexts at all, for deployment targets without native addon support.
+    /// Defaults to `true`, and `false` takes precedence over
+    /// `ssrNativeModules`.
+    pub native_modules: Option<bool>,
+    /// Global identifiers which reference a module export, e.g. a library
+    /// injected by a `<script>` tag.
+    pub free_vars: Option<IndexMap<String, FreeVarConfig>>,
+    /// Whether ESM packages can be externalized instead of being bundled.
+    /// Defaults to `true`.
+    pub esm_externals: Option<EsmExternals>,
+    /// Packages which are bundled in a server context even if they're marked
+    /// as external, e.g. by `serverComponentsExternalPackages`, keyed by the
+    /// context, like `{ "AppRSC": ["some-package"] }`.
+    pub force_bundle_packages: Option<IndexMap<String, Vec<String>>>,
+    /// A module, relative to the project root, evaluated before any server
+    /// code, e.g. to install polyfills.
+    pub server_prelude: Option<String>,
+    /// Modules, relative to the project root, evaluated before the middleware,
+    /// e.g. to polyfill APIs missing from the edge runtime. Node.js contexts
+    /// don't load them.
+    pub edge_polyfills: Option<Vec<String>>,
+    /// Whether link types are generated from the known routes.
+    pub typed_routes: Option<bool>,
+    /// Reports dynamic routes which can match the same path as an error,
+    /// instead of silently picking one by precedence.
+    pub strict_route_precedence: Option<bool>,
+    /// Adds the routes of the build manifest as a prefix trie under
+    /// `__routeTrie`, which the client can match against without scanning
+    /// every route.
+    pub route_trie_manifest: Option<bool>,
+    /// The maximum number of content sources visited at once while finding
+    /// the routes for the manifests, for huge apps where the unbounded
+    /// traversal floods the task scheduler. The routes found are the same.
+    pub route_traversal_concurrency: Option<usize>,
+    /// Adds the routes of `_devPagesManifest.json` with their segments under
+    /// `routes`, e.g. so the dev overlay can show the names of a route's
+    /// params. The flat `pages` list is kept as is.
+    pub dev_pages_manifest_segments: Option<bool>,
+    /// Whether route handlers resolve the `module` entry of packages, which
+    /// is the default. Disable it for CommonJS-first dependencies whose
+    /// `module` entry is meant for bundlers targeting browsers.
+    pub app_route_module_resolution: Option<bool>,
+    /// Adds the dynamic routes of the build manifest with the regex matching
+    /// them under `__dynamicRoutes`, so the client can soft navigate to
+    /// pathnames which aren't listed in the manifest.
+    pub dynamic_route_fallbacks: Option<bool>,
+    /// Pathnames of routes, like `/blog/[slug]`, which are marked with
+    /// `preload: true` under `__routeHints` in the build manifest, so the
+    /// client prefetches their chunks eagerly.
+    pub preload_routes: Option<Vec<String>>,
+    /// A custom template for `_buildManifest.js`, e.g. to assign the manifest
+    /// to a different global.
+    pub build_manifest_template: Option<BuildManifestTemplate>,
+    /// The size in bytes above which a warning is reported for
+    /// `_buildManifest.js`. Defaults to 1 MiB.
+    pub build_manifest_size_warning: Option<usize>,
+    /// A template for the chunk URLs listed in `_buildManifest.js`, like
+    /// `assets/js/[name].[hash].js`, for hosting setups which serve chunks
+    /// from elsewhere. `[name]` is replaced with the chunk name, like
+    /// `pages/blog`, and `[hash]` with a hash of it. The template must
+    /// contain `[name]`.
+    pub chunk_url_template: Option<String>,
+    /// Checks that the page loader chunk of every pages router route in
+    /// `_buildManifest.js` is served, and reports an issue for routes whose
+    /// chunk is missing, e.g. because the page failed to compile. With
+    /// `"omit"`, these routes are also left out of the manifest. Disabled
+    /// by default.
+    pub manifest_chunk_validation: Option<ManifestChunkValidation>,
+    /// Configures how `'use client'` modules are handled in server component
+    /// contexts. `false` leaves them untransformed, and `{ transition }` moves
+    /// them into a custom transition instead of the built-in
+    /// `server-to-client` one.
+    pub client_directive: Option<ClientDirectiveConfig>,
+    /// Whether server code is minified in production builds. Defaults to
+    /// `true`.
+    pub server_minification: Option<bool>,
+    /// Whether each run of a server transform is traced in a `transform`
+    /// span, to measure the execution time of the transforms.
+    pub transform_timings: Option<bool>,
+    /// Whether `React` is imported into files which opt into the classic JSX
+    /// runtime with a `@jsxRuntime classic` pragma, so they don't have to.
+    pub auto_import_react: Option<bool>,
+    /// Directories, relative to the project root, the relay transform of
+    /// `compiler.relay` is limited to. It applies to all files when unset.
+    pub relay_include: Option<Vec<String>>,
+    /// Whether the `jsc.target` and `jsc.transform` settings of a `.swcrc` in
+    /// the project root apply to app code. Settings in `tsconfig.json` take
+    /// precedence.
+    pub swcrc: Option<bool>,
+    /// Skips all SWC source transforms of app code in server contexts (like
+    /// styled-components, emotion, relay and the directive transforms), for
+    /// quick runs which only need the module graph, like type checking or
+    /// route discovery. The compiled code isn't correct with this enabled.
+    pub skip_source_transforms: Option<bool>,
+    /// Whether `react` and `react-dom` resolve to the copies bundled with
+    /// Next.js in App Router contexts, instead of the ones installed in the
+    /// project. Defaults to `true`.
+    pub bundled_react: Option<bool>,
+    /// Allows server code to import files outside of the project directory,
+    /// e.g. shared code in a monorepo. Such imports fail to resolve unless
+    /// this is enabled.
+    pub external_dir: Option<bool>,
+    /// Minifies the CSS emitted by production builds. Either `true` or the
+    /// options of critters, which only enable the minification.
+    pub optimize_css: Option<serde_json::Value>,
 
     // unsupported
     adjust_font_fallbacks: Option<bool>,
@@ -399,8 +633,6 @@ pub struct ExperimentalConfig {
     disable_optimized_loading: Option<bool>,
     disable_postcss_preset_env: Option<bool>,
     enable_undici: Option<bool>,
-    esm_externals: Option<serde_json::Value>,
-    external_dir: Option<bool>,
     fallback_node_polyfills: Option<bool>,
     fetch_cache: Option<bool>,
     font_loaders: Option<serde_json::Value>,
@@ -415,7 +647,6 @@ pub struct ExperimentalConfig {
     new_next_link_behavior: Option<bool>,
     next_script_workers: Option<bool>,
     optimistic_client_cache: Option<bool>,
-    optimize_css: Option<serde_json::Value>,
     output_file_tracing_ignores: Option<Vec<String>>,
     output_file_tracing_root: Option<String>,
     page_env: Option<bool>,
@@ -439,6 +670,39 @@ pub struct ExperimentalConfig {
     worker_threads: Option<bool>,
 }
 
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, TraceRawVcs)]
+#[serde(untagged)]
+pub enum EsmExternals {
+    Boolean(bool),
+    Mode(EsmExternalsMode),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, TraceRawVcs)]
+#[serde(rename_all = "lowercase")]
+pub enum EsmExternalsMode {
+    Loose,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, TraceRawVcs)]
+#[serde(rename_all = "lowercase")]
+pub enum ManifestChunkValidation {
+    /// Reports routes with missing chunks.
+    Warn,
+    /// Reports routes with missing chunks and omits them from the manifest.
+    Omit,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, TraceRawVcs)]
+#[serde(untagged)]
+pub enum FreeVarConfig {
+    /// References the default export of the module.
+    Request(String),
+    Export {
+        request: String,
+        export: Option<String>,
+    },
+}
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize, TraceRawVcs)]
 #[serde(rename_all = "kebab-case")]
 enum MiddlewarePrefetchType {
@@ -468,6 +732,14 @@ pub struct CompilerConfig {
     pub emotion: Option<EmotionTransformOptionsOrBoolean>,
     pub remove_console: Option<RemoveConsoleConfig>,
     pub styled_components: Option<StyledComponentsTransformOptionsOrBoolean>,
+    /// Set to `false` to skip the styled-jsx transform.
+    pub styled_jsx: Option<bool>,
+    /// Set to `false` to also strip license banners (`/*!`, `@license` and
+    /// `@preserve` comments) from production output.
+    pub preserve_banner: Option<bool>,
+    /// Compile-time constants, like `__APP_VERSION__` or
+    /// `process.env.FEATURE`, which are replaced by the given value.
+    pub define: Option<IndexMap<String, JsonValue>>,
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize, TraceRawVcs)]
@@ -498,6 +770,29 @@ impl NextConfigVc {
         ))
     }
 
+    /// Returns the `experimental.forceBundlePackages` of the server context
+    /// with the given name.
+    #[turbo_tasks::function]
+    pub async fn force_bundle_packages(self, context: &str) -> Result<StringsVc> {
+        Ok(StringsVc::cell(
+            self.await?
+                .experimental
+                .force_bundle_packages
+                .as_ref()
+                .and_then(|packages| packages.get(context))
+                .cloned()
+                .unwrap_or_default(),
+        ))
+    }
+
+    #[turbo_tasks::function]
+    pub async fn esm_externals(self) -> Result<BoolVc> {
+        Ok(BoolVc::cell(!matches!(
+            self.await?.experimental.esm_externals,
+            Some(EsmExternals::Boolean(false))
+        )))
+    }
+
     #[turbo_tasks::function]
     pub async fn app_dir(self) -> Result<BoolVc> {
         Ok(BoolVc::cell(
@@ -545,11 +840,75 @@ impl NextConfigVc {
         Ok(StringsVc::cell(self.await?.page_extensions.clone()))
     }
 
+    #[turbo_tasks::function]
+    pub async fn dist_dir(self) -> Result<StringVc> {
+        Ok(StringVc::cell(dist_dir(&*self.await?).to_string()))
+    }
+
+    /// Returns the `distDir` below `root`, which all build output, including
+    /// the manifests, is written to.
+    #[turbo_tasks::function]
+    pub async fn dist_root(self, root: FileSystemPathVc) -> Result<FileSystemPathVc> {
+        Ok(root.join(&self.dist_dir().await?))
+    }
+
+    #[turbo_tasks::function]
+    pub async fn typed_routes(self) -> Result<BoolVc> {
+        Ok(BoolVc::cell(
+            self.await?.experimental.typed_routes.unwrap_or(false),
+        ))
+    }
+
+    #[turbo_tasks::function]
+    pub async fn dynamic_route_fallbacks(self) -> Result<BoolVc> {
+        Ok(BoolVc::cell(
+            self.await?
+                .experimental
+                .dynamic_route_fallbacks
+                .unwrap_or(false),
+        ))
+    }
+
+    #[turbo_tasks::function]
+    pub async fn route_trie_manifest(self) -> Result<BoolVc> {
+        Ok(BoolVc::cell(
+            self.await?
+                .experimental
+                .route_trie_manifest
+                .unwrap_or(false),
+        ))
+    }
+
+    #[turbo_tasks::function]
+    pub async fn strict_route_precedence(self) -> Result<BoolVc> {
+        Ok(BoolVc::cell(
+            self.await?
+                .experimental
+                .strict_route_precedence
+                .unwrap_or(false),
+        ))
+    }
+
+    #[turbo_tasks::function]
+    pub async fn trailing_slash(self) -> Result<BoolVc> {
+        Ok(BoolVc::cell(self.await?.trailing_slash))
+    }
+
     #[turbo_tasks::function]
     pub async fn rewrites(self) -> Result<RewritesVc> {
         Ok(self.await?.rewrites.clone().cell())
     }
 
+    #[turbo_tasks::function]
+    pub async fn headers(self) -> Result<HeadersVc> {
+        Ok(HeadersVc::cell(self.await?.headers.clone()))
+    }
+
+    #[turbo_tasks::function]
+    pub async fn redirects(self) -> Result<RedirectsVc> {
+        Ok(RedirectsVc::cell(self.await?.redirects.clone()))
+    }
+
     #[turbo_tasks::function]
     pub async fn transpile_packages(self) -> Result<StringsVc> {
         Ok(StringsVc::cell(
@@ -600,13 +959,25 @@ impl NextConfigVc {
         ))))
     }
 
+    #[turbo_tasks::function]
+    pub async fn foreign_code_transforms(self) -> Result<ForeignCodeTransformsMapVc> {
+        Ok(ForeignCodeTransformsMapVc::cell(
+            self.await?
+                .experimental
+                .turbo
+                .as_ref()
+                .and_then(|t| t.foreign_code_transforms.clone())
+                .unwrap_or_default(),
+        ))
+    }
+
     #[turbo_tasks::function]
     pub async fn resolve_alias_options(self) -> Result<ResolveAliasMapVc> {
         let this = self.await?;
-        let Some(resolve_alias) = this.experimental.turbo.as_ref().and_then(|t| t.resolve_alias.as_ref()) else {
+        let Some(resolve_alias) = merged_resolve_alias(&this) else {
             return Ok(ResolveAliasMapVc::cell(ResolveAliasMap::default()));
         };
-        let alias_map: ResolveAliasMap = resolve_alias.try_into()?;
+        let alias_map: ResolveAliasMap = (&resolve_alias).try_into()?;
         Ok(alias_map.cell())
     }
 
@@ -617,6 +988,27 @@ impl NextConfigVc {
         ))
     }
 
+    #[turbo_tasks::function]
+    pub async fn bundled_react(self) -> Result<BoolVc> {
+        Ok(BoolVc::cell(
+            self.await?.experimental.bundled_react.unwrap_or(true),
+        ))
+    }
+
+    #[turbo_tasks::function]
+    pub async fn swcrc(self) -> Result<BoolVc> {
+        Ok(BoolVc::cell(
+            self.await?.experimental.swcrc.unwrap_or(false),
+        ))
+    }
+
+    #[turbo_tasks::function]
+    pub async fn ssr_native_modules(self) -> Result<BoolVc> {
+        Ok(BoolVc::cell(
+            self.await?.experimental.ssr_native_modules.unwrap_or(true),
+        ))
+    }
+
     #[turbo_tasks::function]
     pub async fn sass_config(self) -> Result<JsonValueVc> {
         Ok(JsonValueVc::cell(
@@ -765,3 +1157,112 @@ impl Issue for OutdatedConfigIssue {
         StringVc::cell(self.description.to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use indexmap::IndexMap;
+    use serde_json::json;
+    use turbopack_binding::turbo::{
+        tasks::TurboTasks,
+        tasks_fs::{FileSystem, VirtualFileSystemVc},
+        tasks_memory::MemoryBackend,
+    };
+
+    use super::{
+        dist_dir, has_untranslated_webpack_config, merged_resolve_alias, ExperimentalConfig,
+        ExperimentalTurboConfig, NextConfig, TurbopackConfig,
+    };
+    use crate::manifest::production_manifest_path;
+
+    fn aliases(aliases: serde_json::Value) -> IndexMap<String, serde_json::Value> {
+        serde_json::from_value(aliases).unwrap()
+    }
+
+    fn turbopack_config(resolve_alias: serde_json::Value) -> Option<TurbopackConfig> {
+        Some(TurbopackConfig {
+            resolve_alias: Some(aliases(resolve_alias)),
+        })
+    }
+
+    #[test]
+    fn test_merged_resolve_alias() {
+        let next_config = NextConfig {
+            experimental: ExperimentalConfig {
+                turbo: Some(ExperimentalTurboConfig {
+                    resolve_alias: Some(aliases(json!({ "a": "./a", "b": "./b" }))),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            turbopack: turbopack_config(json!({ "b": "./turbopack-b", "c": "./c" })),
+            ..Default::default()
+        };
+        assert_eq!(
+            merged_resolve_alias(&next_config),
+            Some(aliases(
+                json!({ "a": "./a", "b": "./turbopack-b", "c": "./c" })
+            ))
+        );
+        assert_eq!(merged_resolve_alias(&NextConfig::default()), None);
+    }
+
+    #[test]
+    fn test_untranslated_webpack_config() {
+        let next_config = NextConfig {
+            webpack: Some(json!({})),
+            ..Default::default()
+        };
+        assert!(has_untranslated_webpack_config(&next_config));
+        assert!(!has_untranslated_webpack_config(&NextConfig {
+            turbopack: turbopack_config(json!({ "a": "./a" })),
+            ..next_config
+        }));
+        assert!(!has_untranslated_webpack_config(&NextConfig::default()));
+    }
+
+    #[test]
+    fn test_dist_dir() {
+        assert_eq!(dist_dir(&NextConfig::default()), ".next");
+
+        let next_config = NextConfig {
+            dist_dir: Some("./build/".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(dist_dir(&next_config), "build");
+    }
+
+    #[tokio::test]
+    async fn test_manifests_written_to_dist_dir() -> Result<()> {
+        crate::register();
+        let tt = TurboTasks::new(MemoryBackend::default());
+        tt.run_once(async move {
+            let root = VirtualFileSystemVc::new().as_file_system().root();
+            // Like next-build, which writes the manifests below the `dist_root`.
+            let manifest_fs_path = |next_config: NextConfig| {
+                next_config
+                    .cell()
+                    .dist_root(root)
+                    .join(&production_manifest_path(
+                        "my-build-id",
+                        "_buildManifest.js",
+                    ))
+            };
+
+            assert_eq!(
+                manifest_fs_path(NextConfig::default()).await?.path,
+                ".next/static/my-build-id/_buildManifest.js"
+            );
+            let next_config = NextConfig {
+                dist_dir: Some("./build/".to_string()),
+                ..Default::default()
+            };
+            assert_eq!(
+                manifest_fs_path(next_config).await?.path,
+                "build/static/my-build-id/_buildManifest.js"
+            );
+            Ok(())
+        })
+        .await
+    }
+}
@@ -3,7 +3,11 @@ use indexmap::indexmap;
 use turbopack_binding::{
     turbo::tasks_fs::FileSystemPathVc,
     turbopack::{
-        core::{asset::AssetVc, chunk::ChunkingContextVc, compile_time_info::CompileTimeInfoVc},
+        core::{
+            asset::{AssetVc, AssetsVc},
+            chunk::{ChunkingContextVc, EvaluatableAssetVc, EvaluatableAssetsVc},
+            compile_time_info::CompileTimeInfoVc,
+        },
         ecmascript::chunk_group_files_asset::ChunkGroupFilesAsset,
         turbopack::{
             module_options::ModuleOptionsContextVc,
@@ -26,6 +30,9 @@ pub struct NextEdgeRouteTransition {
     pub base_path: FileSystemPathVc,
     pub bootstrap_asset: AssetVc,
     pub entry_name: String,
+    /// Modules evaluated before the entry, like the
+    /// `experimental.edgePolyfills`.
+    pub polyfills: AssetsVc,
 }
 
 #[turbo_tasks::value_impl]
@@ -70,11 +77,21 @@ impl Transition for NextEdgeRouteTransition {
             }),
         );
 
+        let polyfills = self.polyfills.await?;
+        let runtime_entries = (!polyfills.is_empty()).then(|| {
+            EvaluatableAssetsVc::cell(
+                polyfills
+                    .iter()
+                    .map(|&polyfill| EvaluatableAssetVc::from_asset(polyfill, context.into()))
+                    .collect(),
+            )
+        });
+
         let asset = ChunkGroupFilesAsset {
             asset: new_asset.into(),
             client_root: self.output_path,
             chunking_context: self.edge_chunking_context,
-            runtime_entries: None,
+            runtime_entries,
         };
 
         Ok(asset.cell().into())
@@ -1,12 +1,16 @@
 use std::collections::{BTreeMap, HashMap};
 
 use anyhow::{Context, Result};
-use turbo_tasks::Value;
+use turbo_tasks::{
+    primitives::{BoolVc, StringVc},
+    Value,
+};
 use turbopack_binding::{
-    turbo::tasks_fs::{glob::GlobVc, FileSystem, FileSystemPathVc},
+    turbo::tasks_fs::{glob::GlobVc, FileJsonContent, FileSystem, FileSystemPathVc},
     turbopack::{
         core::{
             asset::Asset,
+            issue::{Issue, IssueSeverity, IssueSeverityVc, IssueVc},
             resolve::{
                 options::{
                     ConditionValue, ImportMap, ImportMapVc, ImportMapping, ImportMappingVc,
@@ -23,14 +27,15 @@ use turbopack_binding::{
 };
 
 use crate::{
-    embed_js::{next_js_fs, VIRTUAL_PACKAGE_NAME},
+    embed_js::{internal_code_root, VIRTUAL_PACKAGE_NAME},
     next_client::context::ClientContextType,
-    next_config::NextConfigVc,
+    next_config::{has_untranslated_webpack_config, ImageConfig, ImageLoader, NextConfigVc},
     next_font::{
         google::{NextFontGoogleCssModuleReplacerVc, NextFontGoogleReplacerVc},
         local::{NextFontLocalCssModuleReplacerVc, NextFontLocalReplacerVc},
     },
-    next_server::context::ServerContextType,
+    next_server::context::{ServerContextKind, ServerContextType},
+    util::major_version,
 };
 
 // Make sure to not add any external requests here.
@@ -144,7 +149,7 @@ pub fn get_next_build_import_map() -> ImportMapVc {
     insert_package_alias(
         &mut import_map,
         &format!("{VIRTUAL_PACKAGE_NAME}/"),
-        next_js_fs().root(),
+        internal_code_root(),
     );
 
     let external = ImportMapping::External(None).cell();
@@ -202,17 +207,46 @@ pub async fn get_next_server_import_map(
     )
     .await?;
 
+    let ty = ty.into_value();
+
     insert_alias_option(
         &mut import_map,
         project_path,
         next_config.resolve_alias_options(),
-        [],
+        server_alias_conditions(ty.kind()).iter().copied(),
     )
     .await?;
 
-    let ty = ty.into_value();
+    if has_untranslated_webpack_config(&*next_config.await?) {
+        UntranslatedWebpackConfigIssue { path: project_path }
+            .cell()
+            .as_issue()
+            .emit();
+    }
 
-    insert_next_server_special_aliases(&mut import_map, ty).await?;
+    insert_next_server_special_aliases(&mut import_map, ty, next_config).await?;
+    if matches!(
+        ty,
+        ServerContextType::AppSSR { .. }
+            | ServerContextType::AppRSC { .. }
+            | ServerContextType::AppRoute { .. }
+    ) && *next_config.bundled_react().await?
+    {
+        let installed = get_package_version(project_path, "react").await?;
+        let bundled = get_package_version(project_path, "next/dist/compiled/react").await?;
+        if let (Some(installed), Some(bundled)) = (&*installed, &*bundled) {
+            if is_conflicting_react_version(installed, bundled) {
+                IgnoredReactVersionIssue {
+                    path: project_path,
+                    installed: installed.clone(),
+                    bundled: bundled.clone(),
+                }
+                .cell()
+                .as_issue()
+                .emit();
+            }
+        }
+    }
     let external = ImportMapping::External(None).cell();
 
     match ty {
@@ -266,17 +300,17 @@ pub async fn get_next_edge_import_map(
     )
     .await?;
 
+    let ty = ty.into_value();
+
     insert_alias_option(
         &mut import_map,
         project_path,
         next_config.resolve_alias_options(),
-        [],
+        server_alias_conditions(ty.kind()).iter().copied(),
     )
     .await?;
 
-    let ty = ty.into_value();
-
-    insert_next_server_special_aliases(&mut import_map, ty).await?;
+    insert_next_server_special_aliases(&mut import_map, ty, next_config).await?;
 
     match ty {
         ServerContextType::Pages { .. } | ServerContextType::PagesData { .. } => {}
@@ -346,9 +380,43 @@ static NEXT_ALIASES: [(&str, &str); 23] = [
     ("setImmediate", "next/dist/compiled/setimmediate"),
 ];
 
+/// The aliases which make `react` and `react-dom` resolve to the copies
+/// bundled with Next.js in App Router contexts. Aliases ending with `/` match
+/// all subpaths.
+const BUNDLED_REACT_ALIASES: &[(&str, &str)] = &[
+    ("react", "next/dist/compiled/react"),
+    ("react/", "next/dist/compiled/react/*"),
+    (
+        "react-dom",
+        "next/dist/compiled/react-dom/server-rendering-stub.js",
+    ),
+    ("react-dom/", "next/dist/compiled/react-dom/*"),
+];
+
+/// Returns the aliases for React in App Router contexts, which are only
+/// applied with `experimental.bundledReact` enabled.
+fn bundled_react_aliases(bundled_react: bool) -> &'static [(&'static str, &'static str)] {
+    if bundled_react {
+        BUNDLED_REACT_ALIASES
+    } else {
+        &[]
+    }
+}
+
+/// Returns whether the `installed` React differs from the `bundled` one in
+/// its major version, which makes the installed version being ignored
+/// noteworthy.
+fn is_conflicting_react_version(installed: &str, bundled: &str) -> bool {
+    match (major_version(installed), major_version(bundled)) {
+        (Some(installed), Some(bundled)) => installed != bundled,
+        _ => false,
+    }
+}
+
 pub async fn insert_next_server_special_aliases(
     import_map: &mut ImportMap,
     ty: ServerContextType,
+    next_config: NextConfigVc,
 ) -> Result<()> {
     match ty {
         ServerContextType::Pages { pages_dir } => {
@@ -393,25 +461,14 @@ pub async fn insert_next_server_special_aliases(
                 // @opentelemetry/api
                 request_to_import_mapping(app_dir, "next/dist/compiled/@opentelemetry/api"),
             );
-            import_map.insert_exact_alias(
-                "react",
-                request_to_import_mapping(app_dir, "next/dist/compiled/react"),
-            );
-            import_map.insert_wildcard_alias(
-                "react/",
-                request_to_import_mapping(app_dir, "next/dist/compiled/react/*"),
-            );
-            import_map.insert_exact_alias(
-                "react-dom",
-                request_to_import_mapping(
-                    app_dir,
-                    "next/dist/compiled/react-dom/server-rendering-stub.js",
-                ),
-            );
-            import_map.insert_wildcard_alias(
-                "react-dom/",
-                request_to_import_mapping(app_dir, "next/dist/compiled/react-dom/*"),
-            );
+            for (alias, request) in bundled_react_aliases(*next_config.bundled_react().await?) {
+                let mapping = request_to_import_mapping(app_dir, request);
+                if alias.ends_with('/') {
+                    import_map.insert_wildcard_alias(*alias, mapping);
+                } else {
+                    import_map.insert_exact_alias(*alias, mapping);
+                }
+            }
             import_map.insert_wildcard_alias(
                 "react-server-dom-webpack/",
                 request_to_import_mapping(app_dir, "next/dist/compiled/react-server-dom-webpack/*"),
@@ -434,7 +491,7 @@ pub async fn insert_next_shared_aliases(
     execution_context: ExecutionContextVc,
     next_config: NextConfigVc,
 ) -> Result<()> {
-    let package_root = next_js_fs().root();
+    let package_root = internal_code_root();
 
     if *next_config.mdx_rs().await? {
         insert_alias_to_alternatives(
@@ -498,6 +555,19 @@ pub async fn insert_next_shared_aliases(
         ImportMapping::Dynamic(NextFontLocalCssModuleReplacerVc::new(project_path).into()).into(),
     );
 
+    let images = &next_config.await?.images;
+    if let Some(loader_file) = image_loader_request(images) {
+        import_map.insert_exact_alias(
+            IMAGE_LOADER_REQUEST,
+            request_to_import_mapping(project_path, &loader_file),
+        );
+    } else if is_image_loader_file_missing(images) {
+        MissingImageLoaderFileIssue { path: project_path }
+            .cell()
+            .as_issue()
+            .emit();
+    }
+
     import_map.insert_singleton_alias("@swc/helpers", get_next_package(project_path));
     import_map.insert_singleton_alias("styled-jsx", get_next_package(project_path));
     import_map.insert_singleton_alias("next", project_path);
@@ -514,6 +584,140 @@ pub async fn insert_next_shared_aliases(
     Ok(())
 }
 
+/// The module `next/image` imports its loader function from.
+const IMAGE_LOADER_REQUEST: &str = "next/dist/shared/lib/image-loader";
+
+/// Returns the request for the `images.loaderFile`, which replaces the default
+/// loader of `next/image`, relative to the project root.
+fn image_loader_request(images: &ImageConfig) -> Option<String> {
+    let loader_file = images.loader_file.as_deref()?;
+    Some(format!("./{}", loader_file.trim_start_matches("./")))
+}
+
+/// Returns whether a custom image loader is configured without the file it is
+/// loaded from.
+fn is_image_loader_file_missing(images: &ImageConfig) -> bool {
+    images.loader == ImageLoader::Custom && images.loader_file.is_none() && !images.unoptimized
+}
+
+#[turbo_tasks::value(shared)]
+struct MissingImageLoaderFileIssue {
+    path: FileSystemPathVc,
+}
+
+#[turbo_tasks::value_impl]
+impl Issue for MissingImageLoaderFileIssue {
+    #[turbo_tasks::function]
+    fn severity(&self) -> IssueSeverityVc {
+        IssueSeverity::Error.into()
+    }
+
+    #[turbo_tasks::function]
+    fn category(&self) -> StringVc {
+        StringVc::cell("config".to_string())
+    }
+
+    #[turbo_tasks::function]
+    fn context(&self) -> FileSystemPathVc {
+        self.path
+    }
+
+    #[turbo_tasks::function]
+    fn title(&self) -> StringVc {
+        StringVc::cell(
+            "\"images.loader\" is \"custom\", but \"images.loaderFile\" is not set".to_string(),
+        )
+    }
+
+    #[turbo_tasks::function]
+    fn description(&self) -> StringVc {
+        StringVc::cell(
+            "Set \"images.loaderFile\" to a file which exports the loader function as its default \
+             export, or set \"images.unoptimized\" to true."
+                .to_string(),
+        )
+    }
+}
+
+#[turbo_tasks::value(shared)]
+struct IgnoredReactVersionIssue {
+    path: FileSystemPathVc,
+    installed: String,
+    bundled: String,
+}
+
+#[turbo_tasks::value_impl]
+impl Issue for IgnoredReactVersionIssue {
+    #[turbo_tasks::function]
+    fn severity(&self) -> IssueSeverityVc {
+        IssueSeverity::Warning.into()
+    }
+
+    #[turbo_tasks::function]
+    fn category(&self) -> StringVc {
+        StringVc::cell("resolve".to_string())
+    }
+
+    #[turbo_tasks::function]
+    fn context(&self) -> FileSystemPathVc {
+        self.path
+    }
+
+    #[turbo_tasks::function]
+    fn title(&self) -> StringVc {
+        StringVc::cell(format!(
+            "The installed react@{} is not used by the App Router",
+            self.installed
+        ))
+    }
+
+    #[turbo_tasks::function]
+    fn description(&self) -> StringVc {
+        StringVc::cell(format!(
+            "App Router code uses react@{} bundled with Next.js. Set \
+             \"experimental.bundledReact\" to false to use the installed version instead.",
+            self.bundled
+        ))
+    }
+}
+
+#[turbo_tasks::value(shared)]
+struct UntranslatedWebpackConfigIssue {
+    path: FileSystemPathVc,
+}
+
+#[turbo_tasks::value_impl]
+impl Issue for UntranslatedWebpackConfigIssue {
+    #[turbo_tasks::function]
+    fn severity(&self) -> IssueSeverityVc {
+        IssueSeverity::Warning.into()
+    }
+
+    #[turbo_tasks::function]
+    fn category(&self) -> StringVc {
+        StringVc::cell("config".to_string())
+    }
+
+    #[turbo_tasks::function]
+    fn context(&self) -> FileSystemPathVc {
+        self.path
+    }
+
+    #[turbo_tasks::function]
+    fn title(&self) -> StringVc {
+        StringVc::cell("The \"webpack\" config function is ignored by Turbopack".to_string())
+    }
+
+    #[turbo_tasks::function]
+    fn description(&self) -> StringVc {
+        StringVc::cell(
+            "Turbopack can't run the \"webpack\" function. Move the \"resolve.alias\" entries it \
+             adds to \"turbopack.resolveAlias\" in next.config.js."
+                .to_string(),
+        )
+    }
+}
+
 #[turbo_tasks::function]
 async fn package_lookup_resolve_options(
     project_path: FileSystemPathVc,
@@ -530,6 +734,52 @@ async fn package_lookup_resolve_options(
     ))
 }
 
+/// Returns true if `package_name` can be resolved from `project_path`.
+#[turbo_tasks::function]
+pub async fn is_package_resolvable(
+    project_path: FileSystemPathVc,
+    package_name: &str,
+) -> Result<BoolVc> {
+    let result = resolve(
+        project_path,
+        RequestVc::parse(Value::new(Pattern::Constant(format!(
+            "{package_name}/package.json"
+        )))),
+        package_lookup_resolve_options(project_path),
+    );
+    let assets = result.primary_assets().await?;
+    Ok(BoolVc::cell(!assets.is_empty()))
+}
+
+/// The version of a package, if it can be resolved.
+#[turbo_tasks::value(transparent)]
+pub struct OptionPackageVersion(Option<String>);
+
+/// Returns the version of `package_name` as resolved from `project_path`, or
+/// `None` if it can't be resolved.
+#[turbo_tasks::function]
+pub async fn get_package_version(
+    project_path: FileSystemPathVc,
+    package_name: &str,
+) -> Result<OptionPackageVersionVc> {
+    let result = resolve(
+        project_path,
+        RequestVc::parse(Value::new(Pattern::Constant(format!(
+            "{package_name}/package.json"
+        )))),
+        package_lookup_resolve_options(project_path),
+    );
+    let assets = result.primary_assets().await?;
+    let Some(asset) = assets.first() else {
+        return Ok(OptionPackageVersionVc::cell(None));
+    };
+    let version = match &*asset.ident().path().read_json().await? {
+        FileJsonContent::Content(package) => package["version"].as_str().map(ToString::to_string),
+        _ => None,
+    };
+    Ok(OptionPackageVersionVc::cell(version))
+}
+
 #[turbo_tasks::function]
 pub async fn get_next_package(project_path: FileSystemPathVc) -> Result<FileSystemPathVc> {
     let result = resolve(
@@ -544,13 +794,13 @@ pub async fn get_next_package(project_path: FileSystemPathVc) -> Result<FileSyst
     Ok(asset.ident().path().parent())
 }
 
-pub async fn insert_alias_option<const N: usize>(
+pub async fn insert_alias_option(
     import_map: &mut ImportMap,
     project_path: FileSystemPathVc,
     alias_options: ResolveAliasMapVc,
-    conditions: [&'static str; N],
+    conditions: impl IntoIterator<Item = &'static str>,
 ) -> Result<()> {
-    let conditions = BTreeMap::from(conditions.map(|c| (c.to_string(), ConditionValue::Set)));
+    let conditions = alias_conditions(conditions);
     for (alias, value) in &alias_options.await? {
         if let Some(mapping) = export_value_to_import_mapping(value, &conditions, project_path) {
             import_map.insert_alias(alias, mapping);
@@ -559,11 +809,31 @@ pub async fn insert_alias_option<const N: usize>(
     Ok(())
 }
 
-fn export_value_to_import_mapping(
+/// Returns the conditions `experimental.turbo.resolveAlias` values are matched
+/// against in a server context. Aliases can be limited to React Server
+/// Components with the `react-server` condition.
+fn server_alias_conditions(kind: ServerContextKind) -> &'static [&'static str] {
+    match kind {
+        ServerContextKind::AppRSC => &["react-server"],
+        _ => &[],
+    }
+}
+
+fn alias_conditions(
+    conditions: impl IntoIterator<Item = &'static str>,
+) -> BTreeMap<String, ConditionValue> {
+    conditions
+        .into_iter()
+        .map(|c| (c.to_string(), ConditionValue::Set))
+        .collect()
+}
+
+/// Returns the targets of an alias value which apply with the given
+/// conditions.
+fn alias_targets(
     value: &SubpathValue,
     conditions: &BTreeMap<String, ConditionValue>,
-    project_path: FileSystemPathVc,
-) -> Option<ImportMappingVc> {
+) -> Vec<String> {
     let mut result = Vec::new();
     value.add_results(
         conditions,
@@ -571,6 +841,15 @@ fn export_value_to_import_mapping(
         &mut HashMap::new(),
         &mut result,
     );
+    result.iter().map(|m| m.to_string()).collect()
+}
+
+fn export_value_to_import_mapping(
+    value: &SubpathValue,
+    conditions: &BTreeMap<String, ConditionValue>,
+    project_path: FileSystemPathVc,
+) -> Option<ImportMappingVc> {
+    let result = alias_targets(value, conditions);
     if result.is_empty() {
         None
     } else {
@@ -627,3 +906,124 @@ fn request_to_import_mapping(context_path: FileSystemPathVc, request: &str) -> I
 fn external_request_to_import_mapping(request: &str) -> ImportMappingVc {
     ImportMapping::External(Some(request.to_string())).into()
 }
+
+#[cfg(test)]
+mod tests {
+    use indexmap::IndexMap;
+    use serde_json::json;
+    use turbopack_binding::turbopack::core::resolve::ResolveAliasMap;
+
+    use super::{
+        alias_conditions, alias_targets, bundled_react_aliases, image_loader_request,
+        is_conflicting_react_version, is_image_loader_file_missing, server_alias_conditions,
+    };
+    use crate::{
+        next_config::{
+            merged_resolve_alias, ImageConfig, ImageLoader, NextConfig, TurbopackConfig,
+        },
+        next_server::context::ServerContextKind,
+    };
+
+    #[test]
+    fn test_rsc_only_alias() {
+        let resolve_alias: IndexMap<String, serde_json::Value> = serde_json::from_value(json!({
+            "server-db": { "react-server": "./src/server/db" },
+        }))
+        .unwrap();
+        let alias_map = ResolveAliasMap::try_from(&resolve_alias).unwrap();
+        let (_, value) = (&alias_map).into_iter().next().unwrap();
+
+        let targets = |kind| {
+            alias_targets(
+                value,
+                &alias_conditions(server_alias_conditions(kind).iter().copied()),
+            )
+        };
+
+        assert_eq!(targets(ServerContextKind::AppRSC), vec!["./src/server/db"]);
+        assert!(targets(ServerContextKind::AppSSR).is_empty());
+        assert!(targets(ServerContextKind::Pages).is_empty());
+    }
+
+    #[test]
+    fn test_turbopack_resolve_alias() {
+        let next_config = NextConfig {
+            turbopack: Some(TurbopackConfig {
+                resolve_alias: Some(
+                    serde_json::from_value(json!({ "lodash": "lodash-es" })).unwrap(),
+                ),
+            }),
+            ..Default::default()
+        };
+        let resolve_alias = merged_resolve_alias(&next_config).unwrap();
+        let alias_map = ResolveAliasMap::try_from(&resolve_alias).unwrap();
+        let (_, value) = (&alias_map).into_iter().next().unwrap();
+
+        assert_eq!(
+            alias_targets(
+                value,
+                &alias_conditions(
+                    server_alias_conditions(ServerContextKind::Pages)
+                        .iter()
+                        .copied()
+                ),
+            ),
+            vec!["lodash-es"]
+        );
+    }
+
+    #[test]
+    fn test_bundled_react() {
+        let react_request = |bundled_react| {
+            bundled_react_aliases(bundled_react)
+                .iter()
+                .find(|(alias, _)| *alias == "react")
+                .map(|(_, request)| *request)
+        };
+        assert_eq!(react_request(true), Some("next/dist/compiled/react"));
+        assert_eq!(react_request(false), None);
+
+        assert!(is_conflicting_react_version(
+            "17.0.2",
+            "18.3.0-canary-1dba980e1f-20241220"
+        ));
+        assert!(!is_conflicting_react_version(
+            "18.2.0",
+            "18.3.0-canary-1dba980e1f-20241220"
+        ));
+    }
+
+    #[test]
+    fn test_image_loader_file() {
+        let images = ImageConfig {
+            loader: ImageLoader::Custom,
+            loader_file: Some("./lib/image-loader.js".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            image_loader_request(&images).as_deref(),
+            Some("./lib/image-loader.js")
+        );
+        assert!(!is_image_loader_file_missing(&images));
+
+        let images = ImageConfig {
+            loader_file: Some("image-loader.ts".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            image_loader_request(&images).as_deref(),
+            Some("./image-loader.ts")
+        );
+
+        let images = ImageConfig {
+            loader: ImageLoader::Custom,
+            ..Default::default()
+        };
+        assert_eq!(image_loader_request(&images), None);
+        assert!(is_image_loader_file_missing(&images));
+        assert!(!is_image_loader_file_missing(&ImageConfig {
+            unoptimized: true,
+            ..images
+        }));
+    }
+}
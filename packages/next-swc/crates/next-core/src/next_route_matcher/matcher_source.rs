@@ -0,0 +1,148 @@
+use turbo_tasks::primitives::StringVc;
+use turbopack_binding::{
+    turbo::tasks_fs::FileSystemPathVc,
+    turbopack::core::issue::{Issue, IssueSeverity, IssueSeverityVc, IssueVc},
+};
+
+/// Matches a single path segment of a dynamic parameter.
+const SEGMENT: &str = "[^/#?]+?";
+
+/// Converts a matcher `source` (as used by middleware matchers, rewrites and
+/// redirects) into the regex the runtime matcher uses. This follows
+/// path-to-regexp for the common cases: static segments, named parameters
+/// (`:slug`) and their `?`, `*` and `+` modifiers.
+///
+/// Returns `None` when the source uses syntax which isn't supported, like
+/// custom parameter patterns or parameters which don't span a whole segment.
+pub fn matcher_source_to_regex(source: &str) -> Option<String> {
+    let mut pattern = "^".to_string();
+    for segment in source.split('/').filter(|segment| !segment.is_empty()) {
+        if let Some(param) = segment.strip_prefix(':') {
+            let (name, modifier) = match param.as_bytes().last() {
+                Some(b'?' | b'*' | b'+') => param.split_at(param.len() - 1),
+                _ => (param, ""),
+            };
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return None;
+            }
+            pattern.push_str(&match modifier {
+                "?" => format!("(?:/({SEGMENT}))?"),
+                "*" => format!("(?:/((?:{SEGMENT})(?:/(?:{SEGMENT}))*))?"),
+                "+" => format!("(?:/((?:{SEGMENT})(?:/(?:{SEGMENT}))*))"),
+                _ => format!("(?:/({SEGMENT}))"),
+            });
+        } else if segment.contains(|c| matches!(c, ':' | '(' | ')' | '{' | '}' | '*' | '+' | '?')) {
+            return None;
+        } else {
+            pattern.push('/');
+            pattern.push_str(&regex::escape(segment));
+        }
+    }
+    if pattern.len() == 1 {
+        pattern.push('/');
+    }
+    pattern.push_str("[/#?]?$");
+    Some(pattern)
+}
+
+/// Like [matcher_source_to_regex], but emits an issue for sources with
+/// unsupported syntax and falls back to matching the source literally.
+#[turbo_tasks::function]
+pub fn matcher_source_regex(context: FileSystemPathVc, source: &str) -> StringVc {
+    if let Some(regex) = matcher_source_to_regex(source) {
+        return StringVc::cell(regex);
+    }
+
+    UnsupportedMatcherSourceIssue {
+        context,
+        source: source.to_string(),
+    }
+    .cell()
+    .as_issue()
+    .emit();
+
+    StringVc::cell(format!("^{}$", regex::escape(source)))
+}
+
+#[turbo_tasks::value(shared)]
+struct UnsupportedMatcherSourceIssue {
+    context: FileSystemPathVc,
+    source: String,
+}
+
+#[turbo_tasks::value_impl]
+impl Issue for UnsupportedMatcherSourceIssue {
+    #[turbo_tasks::function]
+    fn severity(&self) -> IssueSeverityVc {
+        IssueSeverity::Warning.into()
+    }
+
+    #[turbo_tasks::function]
+    fn category(&self) -> StringVc {
+        StringVc::cell("config".to_string())
+    }
+
+    #[turbo_tasks::function]
+    fn context(&self) -> FileSystemPathVc {
+        self.context
+    }
+
+    #[turbo_tasks::function]
+    fn title(&self) -> StringVc {
+        StringVc::cell(format!("Unsupported matcher source \"{}\"", self.source))
+    }
+
+    #[turbo_tasks::function]
+    fn description(&self) -> StringVc {
+        StringVc::cell(
+            "Only static segments and named parameters (`:param`, optionally followed by `?`, `*` \
+             or `+`) are supported. The source will only match itself literally."
+                .to_string(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::matcher_source_to_regex;
+
+    fn matches(source: &str, path: &str) -> bool {
+        let regex = matcher_source_to_regex(source).unwrap();
+        regex::Regex::new(&regex).unwrap().is_match(path)
+    }
+
+    #[test]
+    fn test_named_param() {
+        assert_eq!(
+            matcher_source_to_regex("/blog/:slug").as_deref(),
+            Some("^/blog(?:/([^/#?]+?))[/#?]?$")
+        );
+        assert!(matches("/blog/:slug", "/blog/hello"));
+        assert!(!matches("/blog/:slug", "/blog"));
+        assert!(!matches("/blog/:slug", "/blog/hello/world"));
+    }
+
+    #[test]
+    fn test_catch_all_param() {
+        assert!(matches("/blog/:slug*", "/blog"));
+        assert!(matches("/blog/:slug*", "/blog/hello"));
+        assert!(matches("/blog/:slug*", "/blog/hello/world"));
+        assert!(!matches("/blog/:slug*", "/about"));
+        assert!(!matches("/blog/:slug+", "/blog"));
+        assert!(matches("/blog/:slug+", "/blog/hello/world"));
+    }
+
+    #[test]
+    fn test_optional_param() {
+        assert!(matches("/blog/:slug?", "/blog"));
+        assert!(matches("/blog/:slug?", "/blog/hello"));
+        assert!(!matches("/blog/:slug?", "/blog/hello/world"));
+    }
+
+    #[test]
+    fn test_unsupported_syntax() {
+        assert_eq!(matcher_source_to_regex("/blog/:slug(\\d+)"), None);
+        assert_eq!(matcher_source_to_regex("/blog/post-:id"), None);
+        assert_eq!(matcher_source_to_regex("/blog/(.*)"), None);
+    }
+}
@@ -11,6 +11,7 @@ use self::{
 };
 
 mod all;
+pub(crate) mod matcher_source;
 mod path_regex;
 mod prefix_suffix;
 
@@ -1,25 +1,40 @@
-use anyhow::Result;
+use std::collections::HashMap;
+
+use anyhow::{bail, Result};
+use futures::try_join;
+use serde_json::Value as JsonValue;
+use tracing::{Instrument, Span};
 use turbo_tasks::{primitives::StringVc, Value};
 use turbo_tasks_fs::FileSystem;
 use turbopack_binding::{
-    turbo::{tasks_env::ProcessEnvVc, tasks_fs::FileSystemPathVc},
+    turbo::{
+        tasks_env::{EnvMapVc, ProcessEnvVc},
+        tasks_fs::FileSystemPathVc,
+    },
     turbopack::{
         core::{
+            asset::{AssetVc, AssetsVc},
             compile_time_defines,
             compile_time_info::{
-                CompileTimeDefines, CompileTimeDefinesVc, CompileTimeInfo, CompileTimeInfoVc,
-                FreeVarReferencesVc,
+                CompileTimeDefineValue, CompileTimeDefines, CompileTimeDefinesVc, CompileTimeInfo,
+                CompileTimeInfoVc, FreeVarReference, FreeVarReferences, FreeVarReferencesVc,
             },
+            context::AssetContextVc,
             environment::{EnvironmentVc, ExecutionEnvironment, NodeJsEnvironmentVc, ServerAddrVc},
             free_var_references,
+            issue::{Issue, IssueSeverity, IssueSeverityVc, IssueVc},
+            resolve::plugin::ResolvePluginVc,
+            source_asset::SourceAssetVc,
         },
         ecmascript::TransformPluginVc,
         ecmascript_plugin::transform::directives::{
             client::ClientDirectiveTransformer, server::ServerDirectiveTransformer,
         },
+        env::ProcessEnvAssetVc,
         node::execution_context::ExecutionContextVc,
         turbopack::{
             condition::ContextCondition,
+            evaluate_context::node_build_environment,
             module_options::{
                 CustomEcmascriptTransformPlugins, CustomEcmascriptTransformPluginsVc,
                 JsxTransformOptions, MdxTransformModuleOptions, ModuleOptionsContext,
@@ -27,27 +42,37 @@ use turbopack_binding::{
                 WebpackLoadersOptions,
             },
             resolve_options_context::{ResolveOptionsContext, ResolveOptionsContextVc},
+            transition::TransitionsByNameVc,
+            ModuleAssetContextVc,
         },
     },
 };
 
 use super::{
-    resolve::ExternalCjsModulesResolvePluginVc, transforms::get_next_server_transforms_rules,
+    resolve::{
+        externalizes_esm, validate_external_packages, DisabledNativeModulesResolvePluginVc,
+        ExternalCjsModulesResolvePluginVc, ExternalDirResolvePluginVc,
+    },
+    transforms::get_next_server_transforms_rules,
 };
 use crate::{
     babel::maybe_add_babel_loader,
-    embed_js::next_js_fs,
+    embed_js::internal_code_root,
     mode::NextMode,
     next_build::{get_external_next_compiled_package_mapping, get_postcss_package_mapping},
-    next_config::NextConfigVc,
-    next_import_map::{get_next_server_import_map, mdx_import_source_file},
+    next_config::{ClientDirectiveConfig, FreeVarConfig, NextConfig, NextConfigVc},
+    next_edge::context::{get_edge_compile_time_info, get_edge_resolve_options_context},
+    next_import_map::{get_next_server_import_map, get_package_version, mdx_import_source_file},
     next_server::resolve::ExternalPredicate,
     next_shared::{
         resolve::UnsupportedModulesResolvePluginVc,
         transforms::{
+            auto_import_react::get_auto_import_react_transform_plugin,
             emotion::get_emotion_transform_plugin, get_relay_transform_plugin,
+            minify::get_server_minify_transform_plugin,
+            strip_comments::get_strip_comments_transform_plugin,
             styled_components::get_styled_components_transform_plugin,
-            styled_jsx::get_styled_jsx_transform_plugin,
+            styled_jsx::get_styled_jsx_transform_plugin, timing::timed_transform_plugin,
         },
     },
     sass::maybe_add_sass_loader,
@@ -55,7 +80,10 @@ use crate::{
         get_decorators_transform_options, get_jsx_transform_options,
         get_typescript_transform_options,
     },
-    util::foreign_code_context_condition,
+    util::{
+        foreign_code_context_condition, foreign_code_module_options_rules,
+        issue_title_with_context_type, major_version,
+    },
 };
 
 #[turbo_tasks::value(serialization = "auto_for_input")]
@@ -69,6 +97,267 @@ pub enum ServerContextType {
     Middleware,
 }
 
+impl ServerContextType {
+    /// Returns the kind of this server context, without the directory it
+    /// applies to.
+    pub fn kind(&self) -> ServerContextKind {
+        match self {
+            ServerContextType::Pages { .. } => ServerContextKind::Pages,
+            ServerContextType::PagesData { .. } => ServerContextKind::PagesData,
+            ServerContextType::AppSSR { .. } => ServerContextKind::AppSSR,
+            ServerContextType::AppRSC { .. } => ServerContextKind::AppRSC,
+            ServerContextType::AppRoute { .. } => ServerContextKind::AppRoute,
+            ServerContextType::Middleware => ServerContextKind::Middleware,
+        }
+    }
+
+    /// Returns the directory the routes of this context are defined in, if
+    /// any.
+    fn routes_dir(&self) -> Option<FileSystemPathVc> {
+        match *self {
+            ServerContextType::Pages { pages_dir } | ServerContextType::PagesData { pages_dir } => {
+                Some(pages_dir)
+            }
+            ServerContextType::AppSSR { app_dir }
+            | ServerContextType::AppRSC { app_dir }
+            | ServerContextType::AppRoute { app_dir } => Some(app_dir),
+            ServerContextType::Middleware => None,
+        }
+    }
+}
+
+/// The discriminant of a [ServerContextType].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ServerContextKind {
+    Pages,
+    PagesData,
+    AppSSR,
+    AppRSC,
+    AppRoute,
+    Middleware,
+}
+
+impl ServerContextKind {
+    /// Every server context kind, in declaration order.
+    pub const ALL: [ServerContextKind; 6] = [
+        ServerContextKind::Pages,
+        ServerContextKind::PagesData,
+        ServerContextKind::AppSSR,
+        ServerContextKind::AppRSC,
+        ServerContextKind::AppRoute,
+        ServerContextKind::Middleware,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ServerContextKind::Pages => "Pages",
+            ServerContextKind::PagesData => "PagesData",
+            ServerContextKind::AppSSR => "AppSSR",
+            ServerContextKind::AppRSC => "AppRSC",
+            ServerContextKind::AppRoute => "AppRoute",
+            ServerContextKind::Middleware => "Middleware",
+        }
+    }
+
+    /// Returns the custom resolve conditions used by server contexts of this
+    /// kind.
+    pub fn resolve_conditions(&self, mode: NextMode) -> Vec<String> {
+        let mut conditions = vec![mode.node_env().to_string()];
+        if *self == ServerContextKind::AppRSC {
+            conditions.push("react-server".to_string());
+        }
+        // Middleware targets an edge-like runtime and doesn't get node exports.
+        if *self != ServerContextKind::Middleware {
+            // TODO
+            conditions.push("node".to_string());
+        }
+        conditions
+    }
+}
+
+/// Returns the transition `'use client'` modules are moved into from server
+/// component contexts, or `None` if the client directive transform is
+/// disabled.
+fn client_directive_transition(next_config: &NextConfig) -> Option<String> {
+    match &next_config.experimental.client_directive {
+        None | Some(ClientDirectiveConfig::Enabled(true)) => Some("server-to-client".to_string()),
+        Some(ClientDirectiveConfig::Enabled(false)) => None,
+        Some(ClientDirectiveConfig::Transition { transition }) => Some(transition.clone()),
+    }
+}
+
+/// Returns why server contexts of `kind`, with routes in a directory named
+/// `routes_dir_name`, can't be compiled in `mode`, if they can't.
+fn invalid_server_context_reason(
+    kind: ServerContextKind,
+    routes_dir_name: Option<&str>,
+    mode: NextMode,
+) -> Option<&'static str> {
+    match (kind, routes_dir_name, mode) {
+        (ServerContextKind::Middleware, _, NextMode::Build) => Some(
+            "Middleware is only compiled for development, where it runs in a Node.js sandbox. \
+             Production builds need the edge runtime for middleware, which isn't supported yet.",
+        ),
+        (ServerContextKind::Pages | ServerContextKind::PagesData, Some("app"), _) => Some(
+            "Pages Router contexts compile the `pages` directory, but were created for the `app` \
+             directory, whose routes are compiled by the App Router contexts.",
+        ),
+        (
+            ServerContextKind::AppSSR | ServerContextKind::AppRSC | ServerContextKind::AppRoute,
+            Some("pages"),
+            _,
+        ) => Some(
+            "App Router contexts compile the `app` directory, but were created for the `pages` \
+             directory, whose routes are compiled by the Pages Router contexts.",
+        ),
+        _ => None,
+    }
+}
+
+/// Checks that server contexts of type `ty` can be compiled in `mode`, and
+/// emits an issue and fails if they can't.
+async fn validate(
+    project_path: FileSystemPathVc,
+    ty: ServerContextType,
+    mode: NextMode,
+) -> Result<()> {
+    let routes_dir = match ty.routes_dir() {
+        Some(routes_dir) => Some(routes_dir.await?),
+        None => None,
+    };
+    let routes_dir_name = routes_dir.as_ref().map(|routes_dir| routes_dir.file_name());
+    if let Some(reason) = invalid_server_context_reason(ty.kind(), routes_dir_name, mode) {
+        InvalidServerContextIssue {
+            path: project_path,
+            kind: ty.kind().as_str().to_string(),
+            mode: format!("{:?}", mode),
+            reason: reason.to_string(),
+        }
+        .cell()
+        .as_issue()
+        .emit();
+        bail!(
+            "invalid {} server context in {:?} mode: {}",
+            ty.kind().as_str(),
+            mode,
+            reason
+        );
+    }
+    Ok(())
+}
+
+#[turbo_tasks::value(shared)]
+struct InvalidServerContextIssue {
+    path: FileSystemPathVc,
+    kind: String,
+    mode: String,
+    reason: String,
+}
+
+#[turbo_tasks::value_impl]
+impl Issue for InvalidServerContextIssue {
+    #[turbo_tasks::function]
+    fn severity(&self) -> IssueSeverityVc {
+        IssueSeverity::Error.into()
+    }
+
+    #[turbo_tasks::function]
+    fn category(&self) -> StringVc {
+        StringVc::cell("config".to_string())
+    }
+
+    #[turbo_tasks::function]
+    fn context(&self) -> FileSystemPathVc {
+        self.path
+    }
+
+    #[turbo_tasks::function]
+    fn title(&self) -> StringVc {
+        StringVc::cell(format!(
+            "Invalid {} server context in {} mode",
+            self.kind, self.mode
+        ))
+    }
+
+    #[turbo_tasks::function]
+    fn description(&self) -> StringVc {
+        StringVc::cell(self.reason.clone())
+    }
+}
+
+/// Reports that `experimental.skipSourceTransforms` is in effect, as the
+/// compiled code may behave differently.
+#[turbo_tasks::value(shared)]
+struct SkippedSourceTransformsIssue {
+    path: FileSystemPathVc,
+    kind: String,
+}
+
+#[turbo_tasks::value_impl]
+impl Issue for SkippedSourceTransformsIssue {
+    #[turbo_tasks::function]
+    fn severity(&self) -> IssueSeverityVc {
+        IssueSeverity::Warning.into()
+    }
+
+    #[turbo_tasks::function]
+    fn category(&self) -> StringVc {
+        StringVc::cell("config".to_string())
+    }
+
+    #[turbo_tasks::function]
+    fn context(&self) -> FileSystemPathVc {
+        self.path
+    }
+
+    #[turbo_tasks::function]
+    fn title(&self) -> StringVc {
+        StringVc::cell(issue_title_with_context_type(
+            "Source transforms are skipped",
+            Some(&self.kind),
+        ))
+    }
+
+    #[turbo_tasks::function]
+    fn description(&self) -> StringVc {
+        StringVc::cell(
+            "\"experimental.skipSourceTransforms\" is enabled, so styled-components, emotion, \
+             relay and the directive transforms don't run. Only use it for runs which don't \
+             execute the compiled code."
+                .to_string(),
+        )
+    }
+}
+
+/// Creates a span for building the `context` of a server context, carrying its
+/// kind so traces (and cache misses) can be attributed to it.
+fn server_context_span(context: &'static str, kind: ServerContextKind) -> Span {
+    tracing::info_span!("server context", context, ty = kind.as_str())
+}
+
+/// A span for one of the lookups a server context is built from, nested in
+/// the [server_context_span].
+fn server_lookup_span(lookup: &'static str) -> Span {
+    tracing::info_span!("server context lookup", lookup)
+}
+
+/// Additional resolve plugins for server contexts, e.g. from integrators
+/// embedding next-core.
+#[turbo_tasks::value(transparent)]
+pub struct ResolvePlugins(Vec<ResolvePluginVc>);
+
+#[turbo_tasks::value_impl]
+impl ResolvePluginsVc {
+    #[turbo_tasks::function]
+    pub fn empty() -> Self {
+        ResolvePluginsVc::cell(Vec::new())
+    }
+}
+
+/// Returns the resolve options context for the server context `ty`. The
+/// `extra_plugins` run after the built-in plugins of that context, in order, so
+/// they only see requests which aren't already handled by Next.js (like
+/// externals).
 #[turbo_tasks::function]
 pub async fn get_server_resolve_options_context(
     project_path: FileSystemPathVc,
@@ -76,35 +365,100 @@ pub async fn get_server_resolve_options_context(
     mode: NextMode,
     next_config: NextConfigVc,
     execution_context: ExecutionContextVc,
+    extra_plugins: ResolvePluginsVc,
+) -> Result<ResolveOptionsContextVc> {
+    validate(project_path, ty.into_value(), mode).await?;
+    server_resolve_options_context(
+        project_path,
+        ty,
+        mode,
+        next_config,
+        execution_context,
+        extra_plugins,
+    )
+    .instrument(server_context_span("resolve options", ty.kind()))
+    .await
+}
+
+async fn server_resolve_options_context(
+    project_path: FileSystemPathVc,
+    ty: Value<ServerContextType>,
+    mode: NextMode,
+    next_config: NextConfigVc,
+    execution_context: ExecutionContextVc,
+    extra_plugins: ResolvePluginsVc,
 ) -> Result<ResolveOptionsContextVc> {
+    validate_external_packages(project_path, next_config).await?;
     let next_server_import_map =
         get_next_server_import_map(project_path, ty, next_config, execution_context);
     let foreign_code_context_condition = foreign_code_context_condition(next_config).await?;
     let root_dir = project_path.root().resolve().await?;
-    let unsupported_modules_resolve_plugin = UnsupportedModulesResolvePluginVc::new(project_path);
+    let unsupported_modules_resolve_plugin =
+        UnsupportedModulesResolvePluginVc::new_with_context_type(project_path, ty.kind().as_str());
+    let esm_externals = *externalizes_esm(next_config).await?;
+    let context_type = ty.kind().as_str();
+    let force_bundle_packages = next_config.force_bundle_packages(context_type);
+    let custom_conditions = ty.kind().resolve_conditions(mode);
+    let module = resolves_module_field(ty.kind(), &*next_config.await?);
+    let disabled_native_modules_option =
+        native_modules_disabled_by(ty.kind(), &*next_config.await?);
+    let enable_node_native_modules = disabled_native_modules_option.is_none();
+    let with_native_modules_plugin = |mut plugins: Vec<ResolvePluginVc>| {
+        if let Some(option) = disabled_native_modules_option {
+            plugins.push(DisabledNativeModulesResolvePluginVc::new(project_path, option).into());
+        }
+        plugins
+    };
     let server_component_externals_plugin = ExternalCjsModulesResolvePluginVc::new(
         project_path,
         ExternalPredicate::Only(next_config.server_component_externals()).cell(),
+        force_bundle_packages,
+        esm_externals,
+        context_type,
     );
+    let external_dir_plugin: Option<ResolvePluginVc> = if next_config
+        .await?
+        .experimental
+        .external_dir
+        .unwrap_or(false)
+    {
+        None
+    } else {
+        Some(ExternalDirResolvePluginVc::new(project_path).into())
+    };
+    let extra_plugins = extra_plugins.await?;
+    let with_extra_plugins = |mut plugins: Vec<ResolvePluginVc>| {
+        plugins.extend(external_dir_plugin);
+        plugins.extend(extra_plugins.iter().copied());
+        plugins
+    };
 
     Ok(match ty.into_value() {
         ServerContextType::Pages { .. } | ServerContextType::PagesData { .. } => {
+            let predicate = if bundles_pages_router_dependencies(&*next_config.await?) {
+                ExternalPredicate::Only(next_config.server_component_externals())
+            } else {
+                ExternalPredicate::AllExcept(next_config.transpile_packages())
+            };
             let external_cjs_modules_plugin = ExternalCjsModulesResolvePluginVc::new(
                 project_path,
-                ExternalPredicate::AllExcept(next_config.transpile_packages()).cell(),
+                predicate.cell(),
+                force_bundle_packages,
+                esm_externals,
+                context_type,
             );
 
             let resolve_options_context = ResolveOptionsContext {
                 enable_node_modules: Some(root_dir),
                 enable_node_externals: true,
-                enable_node_native_modules: true,
+                enable_node_native_modules,
                 module: true,
-                custom_conditions: vec![mode.node_env().to_string(), "node".to_string()],
+                custom_conditions: custom_conditions.clone(),
                 import_map: Some(next_server_import_map),
-                plugins: vec![
+                plugins: with_extra_plugins(with_native_modules_plugin(vec![
                     external_cjs_modules_plugin.into(),
                     unsupported_modules_resolve_plugin.into(),
-                ],
+                ])),
                 ..Default::default()
             };
             ResolveOptionsContext {
@@ -118,21 +472,18 @@ pub async fn get_server_resolve_options_context(
             }
         }
         ServerContextType::AppSSR { .. } => {
+            let plugins = with_native_modules_plugin(vec![
+                server_component_externals_plugin.into(),
+                unsupported_modules_resolve_plugin.into(),
+            ]);
             let resolve_options_context = ResolveOptionsContext {
                 enable_node_modules: Some(root_dir),
                 enable_node_externals: true,
-                enable_node_native_modules: true,
+                enable_node_native_modules,
                 module: true,
-                custom_conditions: vec![
-                    mode.node_env().to_string(),
-                    // TODO!
-                    "node".to_string(),
-                ],
+                custom_conditions: custom_conditions.clone(),
                 import_map: Some(next_server_import_map),
-                plugins: vec![
-                    server_component_externals_plugin.into(),
-                    unsupported_modules_resolve_plugin.into(),
-                ],
+                plugins: with_extra_plugins(plugins),
                 ..Default::default()
             };
             ResolveOptionsContext {
@@ -149,19 +500,14 @@ pub async fn get_server_resolve_options_context(
             let resolve_options_context = ResolveOptionsContext {
                 enable_node_modules: Some(root_dir),
                 enable_node_externals: true,
-                enable_node_native_modules: true,
+                enable_node_native_modules,
                 module: true,
-                custom_conditions: vec![
-                    mode.node_env().to_string(),
-                    "react-server".to_string(),
-                    // TODO
-                    "node".to_string(),
-                ],
+                custom_conditions: custom_conditions.clone(),
                 import_map: Some(next_server_import_map),
-                plugins: vec![
+                plugins: with_extra_plugins(with_native_modules_plugin(vec![
                     server_component_externals_plugin.into(),
                     unsupported_modules_resolve_plugin.into(),
-                ],
+                ])),
                 ..Default::default()
             };
             ResolveOptionsContext {
@@ -177,13 +523,13 @@ pub async fn get_server_resolve_options_context(
         ServerContextType::AppRoute { .. } => {
             let resolve_options_context = ResolveOptionsContext {
                 enable_node_modules: Some(root_dir),
-                module: true,
-                custom_conditions: vec![mode.node_env().to_string(), "node".to_string()],
+                module,
+                custom_conditions: custom_conditions.clone(),
                 import_map: Some(next_server_import_map),
-                plugins: vec![
+                plugins: with_extra_plugins(vec![
                     server_component_externals_plugin.into(),
                     unsupported_modules_resolve_plugin.into(),
-                ],
+                ]),
                 ..Default::default()
             };
             ResolveOptionsContext {
@@ -199,10 +545,14 @@ pub async fn get_server_resolve_options_context(
         ServerContextType::Middleware => {
             let resolve_options_context = ResolveOptionsContext {
                 enable_node_modules: Some(root_dir),
+                // Middleware is executed in a Node.js sandbox during development, which still
+                // provides the Node.js built-in modules.
                 enable_node_externals: true,
+                // Middleware targets an edge-like runtime, so packages' browser shims apply.
+                browser: true,
                 module: true,
-                custom_conditions: vec![mode.node_env().to_string()],
-                plugins: vec![unsupported_modules_resolve_plugin.into()],
+                custom_conditions: custom_conditions.clone(),
+                plugins: with_extra_plugins(vec![unsupported_modules_resolve_plugin.into()]),
                 ..Default::default()
             };
             ResolveOptionsContext {
@@ -219,38 +569,205 @@ pub async fn get_server_resolve_options_context(
     .cell())
 }
 
-fn defines(mode: NextMode) -> CompileTimeDefines {
-    compile_time_defines!(
+/// Returns whether server contexts of `kind` resolve the `module` entry of
+/// packages. Route handlers can opt out with
+/// `experimental.appRouteModuleResolution`.
+fn resolves_module_field(kind: ServerContextKind, next_config: &NextConfig) -> bool {
+    match kind {
+        ServerContextKind::AppRoute => next_config
+            .experimental
+            .app_route_module_resolution
+            .unwrap_or(true),
+        _ => true,
+    }
+}
+
+/// Returns the config option which disables native Node.js addons in server
+/// contexts of `kind`, if any. `.node` files then fail to resolve with an
+/// issue.
+fn native_modules_disabled_by(
+    kind: ServerContextKind,
+    next_config: &NextConfig,
+) -> Option<&'static str> {
+    match kind {
+        // These contexts never resolve native modules.
+        ServerContextKind::AppRoute | ServerContextKind::Middleware => None,
+        _ if !next_config.experimental.native_modules.unwrap_or(true) => {
+            Some("experimental.nativeModules")
+        }
+        ServerContextKind::AppSSR
+            if !next_config.experimental.ssr_native_modules.unwrap_or(true) =>
+        {
+            Some("experimental.ssrNativeModules")
+        }
+        _ => None,
+    }
+}
+
+/// Returns whether Pages Router contexts bundle their dependencies, which
+/// leaves only `experimental.serverComponentsExternalPackages` external.
+fn bundles_pages_router_dependencies(next_config: &NextConfig) -> bool {
+    next_config
+        .bundle_pages_router_dependencies
+        .unwrap_or(false)
+}
+
+/// Returns the `compiler.define` constants as member expression paths with the
+/// values they are replaced by. Only boolean and string values can be inlined,
+/// others are skipped.
+fn configured_defines(
+    next_config: &NextConfig,
+) -> impl Iterator<Item = (Vec<String>, CompileTimeDefineValue)> + '_ {
+    next_config
+        .compiler
+        .iter()
+        .flat_map(|compiler| compiler.define.iter().flatten())
+        .filter_map(|(name, value)| {
+            let value = match value {
+                JsonValue::Bool(value) => CompileTimeDefineValue::Bool(*value),
+                JsonValue::String(value) => CompileTimeDefineValue::String(value.clone()),
+                _ => return None,
+            };
+            Some((name.split('.').map(ToString::to_string).collect(), value))
+        })
+}
+
+fn is_process_env(path: &[String]) -> bool {
+    matches!(path, [process, env, _] if process == "process" && env == "env")
+}
+
+fn defines(mode: NextMode, next_config: &NextConfig) -> CompileTimeDefines {
+    let mut defines = compile_time_defines!(
         process.turbopack = true,
         process.env.NODE_ENV = mode.node_env(),
         process.env.__NEXT_CLIENT_ROUTER_FILTER_ENABLED = false,
-        process.env.NEXT_RUNTIME = "nodejs"
-    )
+        process.env.NEXT_RUNTIME = "nodejs",
+        process.env.__NEXT_NO_MIDDLEWARE_URL_NORMALIZE =
+            next_config.skip_middleware_url_normalize.unwrap_or(false),
+        process.env.__NEXT_MANUAL_TRAILING_SLASH =
+            next_config.skip_trailing_slash_redirect.unwrap_or(false),
+        // Like Next.js, strict mode is opt-in for pages and opt-out for the app dir.
+        process.env.__NEXT_STRICT_MODE = next_config.react_strict_mode.unwrap_or(false),
+        process.env.__NEXT_STRICT_MODE_APP = next_config.react_strict_mode.unwrap_or(true),
+        // Response options the server runtime branches on, so the unused
+        // branches can be tree-shaken.
+        process.env.__NEXT_POWERED_BY_HEADER = next_config.powered_by_header.unwrap_or(true),
+        process.env.__NEXT_GENERATE_ETAGS = next_config.generate_etags.unwrap_or(true),
+        process.env.__NEXT_COMPRESS = next_config.compress.unwrap_or(true)
+    );
     // TODO(WEB-937) there are more defines needed, see
     // packages/next/src/build/webpack-config.ts
+
+    if next_config.experimental.preview_build.unwrap_or(false) {
+        defines.0.insert(
+            vec![
+                "process".to_string(),
+                "env".to_string(),
+                "__NEXT_PREVIEW".to_string(),
+            ],
+            CompileTimeDefineValue::Bool(true),
+        );
+    }
+
+    // Built-in defines take precedence over configured ones.
+    for (path, value) in configured_defines(next_config) {
+        if is_process_env(&path) {
+            defines.0.entry(path).or_insert(value);
+        }
+    }
+
+    defines
+}
+
+/// Returns the define exposing the major version of the installed React as
+/// `process.env.__NEXT_REACT_VERSION`, e.g. `"18"` for `18.2.0`. There is none
+/// when React can't be resolved or has an unexpected version.
+fn react_version_define(
+    react_version: Option<&str>,
+) -> Option<(Vec<String>, CompileTimeDefineValue)> {
+    let major = major_version(react_version?)?;
+    Some((
+        vec![
+            "process".to_string(),
+            "env".to_string(),
+            "__NEXT_REACT_VERSION".to_string(),
+        ],
+        CompileTimeDefineValue::String(major.to_string()),
+    ))
 }
 
 #[turbo_tasks::function]
-fn next_server_defines(mode: NextMode) -> CompileTimeDefinesVc {
-    defines(mode).cell()
+async fn next_server_defines(
+    project_path: FileSystemPathVc,
+    mode: NextMode,
+    next_config: NextConfigVc,
+) -> Result<CompileTimeDefinesVc> {
+    let mut defines = defines(mode, &*next_config.await?);
+    let react_version = get_package_version(project_path, "react").await?;
+    defines
+        .0
+        .extend(react_version_define(react_version.as_deref()));
+    Ok(defines.cell())
+}
+
+fn free_vars(mode: NextMode, next_config: &NextConfig) -> FreeVarReferences {
+    let mut free_vars = free_var_references!(..defines(mode, next_config).into_iter());
+
+    // Built-in free vars take precedence over configured ones.
+    for (name, config) in next_config.experimental.free_vars.iter().flatten() {
+        let (request, export) = match config {
+            FreeVarConfig::Request(request) => (request, Some("default".to_string())),
+            FreeVarConfig::Export { request, export } => (request, export.clone()),
+        };
+        free_vars
+            .0
+            .entry(name.split('.').map(ToString::to_string).collect())
+            .or_insert_with(|| FreeVarReference::EcmaScriptModule {
+                request: request.clone(),
+                context: None,
+                export,
+            });
+    }
+
+    for (path, value) in configured_defines(next_config) {
+        if !is_process_env(&path) {
+            free_vars
+                .0
+                .entry(path)
+                .or_insert_with(|| FreeVarReference::Value(value));
+        }
+    }
+
+    free_vars
 }
 
 #[turbo_tasks::function]
-async fn next_server_free_vars(mode: NextMode) -> Result<FreeVarReferencesVc> {
-    Ok(free_var_references!(..defines(mode).into_iter()).cell())
+async fn next_server_free_vars(
+    project_path: FileSystemPathVc,
+    mode: NextMode,
+    next_config: NextConfigVc,
+) -> Result<FreeVarReferencesVc> {
+    let mut free_vars = free_vars(mode, &*next_config.await?);
+    let react_version = get_package_version(project_path, "react").await?;
+    if let Some((path, value)) = react_version_define(react_version.as_deref()) {
+        free_vars.0.insert(path, FreeVarReference::Value(value));
+    }
+    Ok(free_vars.cell())
 }
 
 #[turbo_tasks::function]
 pub fn get_server_compile_time_info(
+    project_path: FileSystemPathVc,
     mode: NextMode,
     process_env: ProcessEnvVc,
     server_addr: ServerAddrVc,
+    next_config: NextConfigVc,
 ) -> CompileTimeInfoVc {
     CompileTimeInfo::builder(EnvironmentVc::new(Value::new(
         ExecutionEnvironment::NodeJsLambda(NodeJsEnvironmentVc::current(process_env, server_addr)),
     )))
-    .defines(next_server_defines(mode))
-    .free_var_references(next_server_free_vars(mode))
+    .defines(next_server_defines(project_path, mode, next_config))
+    .free_var_references(next_server_free_vars(project_path, mode, next_config))
     .cell()
 }
 
@@ -262,15 +779,89 @@ pub async fn get_server_module_options_context(
     mode: NextMode,
     next_config: NextConfigVc,
 ) -> Result<ModuleOptionsContextVc> {
-    let custom_rules = get_next_server_transforms_rules(next_config, ty.into_value()).await?;
+    Ok(
+        get_server_module_options(project_path, execution_context, ty, mode, next_config)
+            .await?
+            .context,
+    )
+}
+
+/// The [ModuleOptionsContext] of a server context, along with the source
+/// transforms the [ServerModuleOptionsContextBuilder] applied.
+#[turbo_tasks::value(shared)]
+pub struct ServerModuleOptions {
+    /// The options of app code.
+    pub context: ModuleOptionsContextVc,
+    /// The options of Next.js internals.
+    pub internal_context: ModuleOptionsContextVc,
+    /// The names of the source transforms applied to app code, in order.
+    pub transforms: Vec<String>,
+    /// The names of the source transforms applied to foreign code and Next.js
+    /// internals, in order.
+    pub foreign_code_transforms: Vec<String>,
+}
+
+#[turbo_tasks::function]
+pub async fn get_server_module_options(
+    project_path: FileSystemPathVc,
+    execution_context: ExecutionContextVc,
+    ty: Value<ServerContextType>,
+    mode: NextMode,
+    next_config: NextConfigVc,
+) -> Result<ServerModuleOptionsVc> {
+    validate(project_path, ty.into_value(), mode).await?;
+    server_module_options(project_path, execution_context, ty, mode, next_config)
+        .instrument(server_context_span("module options", ty.kind()))
+        .await
+}
+
+async fn server_module_options(
+    project_path: FileSystemPathVc,
+    execution_context: ExecutionContextVc,
+    ty: Value<ServerContextType>,
+    mode: NextMode,
+    next_config: NextConfigVc,
+) -> Result<ServerModuleOptionsVc> {
+    let skip_source_transforms = skips_source_transforms(&*next_config.await?);
+    if skip_source_transforms {
+        SkippedSourceTransformsIssue {
+            path: project_path,
+            kind: ty.kind().as_str().to_string(),
+        }
+        .cell()
+        .as_issue()
+        .emit();
+    }
+
+    let custom_rules =
+        get_next_server_transforms_rules(project_path, next_config, ty.into_value()).await?;
     let foreign_code_context_condition = foreign_code_context_condition(next_config).await?;
+
+    // These lookups read independent files (tsconfig, the postcss package,
+    // babel config), so they are issued concurrently. Each one gets its own
+    // span, which shows them overlapping in a trace.
+    let (tsconfig, decorators_options, postcss_package, webpack_rules) = try_join!(
+        get_typescript_transform_options(project_path, next_config)
+            .resolve()
+            .instrument(server_lookup_span("typescript")),
+        get_decorators_transform_options(project_path, next_config)
+            .resolve()
+            .instrument(server_lookup_span("decorators")),
+        get_postcss_package_mapping(project_path)
+            .resolve()
+            .instrument(server_lookup_span("postcss")),
+        async {
+            let webpack_rules = *next_config.webpack_rules().await?;
+            maybe_add_babel_loader(project_path, webpack_rules).await
+        }
+        .instrument(server_lookup_span("babel")),
+    )?;
     let enable_postcss_transform = Some(PostCssTransformOptions {
-        postcss_package: Some(get_postcss_package_mapping(project_path)),
+        postcss_package: Some(postcss_package),
         ..Default::default()
     });
 
-    let webpack_rules =
-        *maybe_add_babel_loader(project_path, *next_config.webpack_rules().await?).await?;
+    let webpack_rules = *webpack_rules;
     let webpack_rules = maybe_add_sass_loader(next_config.sass_config(), webpack_rules).await?;
     let enable_webpack_loaders = webpack_rules.map(|rules| {
         WebpackLoadersOptions {
@@ -283,23 +874,39 @@ pub async fn get_server_module_options_context(
     });
 
     // EcmascriptTransformPlugins for custom transforms
-    let styled_components_transform_plugin =
-        *get_styled_components_transform_plugin(next_config).await?;
-    let styled_jsx_transform_plugin = *get_styled_jsx_transform_plugin().await?;
-    let client_directive_transform_plugin = Some(TransformPluginVc::cell(Box::new(
-        ClientDirectiveTransformer::new(&StringVc::cell("server-to-client".to_string())),
-    )));
-    let server_directive_transform_plugin = Some(TransformPluginVc::cell(Box::new(
-        ServerDirectiveTransformer::new(
-            // ServerDirective is not implemented yet and always reports an issue.
-            // We don't have to pass a valid transition name yet, but the API is prepared.
-            &StringVc::cell("TODO".to_string()),
-        ),
-    )));
+    let time_transforms = next_config
+        .await?
+        .experimental
+        .transform_timings
+        .unwrap_or(false);
+    let timed = |name, plugin| timed_transform_plugin(name, plugin, time_transforms);
+    let source_transform_plugins = if skip_source_transforms {
+        SourceTransformPlugins::default()
+    } else {
+        SourceTransformPlugins {
+            styled_components: *get_styled_components_transform_plugin(next_config, project_path)
+                .await?,
+            styled_jsx: *get_styled_jsx_transform_plugin(next_config).await?,
+            client_directive: client_directive_transition(&*next_config.await?).map(|transition| {
+                TransformPluginVc::cell(Box::new(ClientDirectiveTransformer::new(&StringVc::cell(
+                    transition,
+                ))))
+            }),
+            server_directive: Some(TransformPluginVc::cell(Box::new(
+                ServerDirectiveTransformer::new(
+                    // ServerDirective is not implemented yet and always reports an issue.
+                    // We don't have to pass a valid transition name yet, but the API is
+                    // prepared.
+                    &StringVc::cell("TODO".to_string()),
+                ),
+            ))),
+            relay: *get_relay_transform_plugin(next_config).await?,
+            emotion: *get_emotion_transform_plugin(next_config, project_path).await?,
+            auto_import_react: *get_auto_import_react_transform_plugin(next_config).await?,
+        }
+    };
 
     // ModuleOptionsContext related options
-    let tsconfig = get_typescript_transform_options(project_path);
-    let decorators_options = get_decorators_transform_options(project_path);
     let enable_mdx_rs = if *next_config.mdx_rs().await? {
         Some(
             MdxTransformModuleOptions {
@@ -310,55 +917,27 @@ pub async fn get_server_module_options_context(
     } else {
         None
     };
-    let jsx_runtime_options = get_jsx_transform_options(project_path, mode, None);
+    let jsx_runtime_options = get_jsx_transform_options(project_path, mode, None, next_config);
 
-    let source_transforms: Vec<TransformPluginVc> = vec![
-        *get_relay_transform_plugin(next_config).await?,
-        *get_emotion_transform_plugin(next_config).await?,
+    let output_transforms: Vec<TransformPluginVc> = vec![
+        timed(
+            "strip-comments",
+            *get_strip_comments_transform_plugin(next_config, mode).await?,
+        ),
+        timed(
+            "minify",
+            *get_server_minify_transform_plugin(next_config, mode).await?,
+        ),
     ]
     .into_iter()
     .flatten()
     .collect();
 
-    let output_transforms = vec![];
-
-    let custom_ecma_transform_plugins = Some(CustomEcmascriptTransformPluginsVc::cell(
-        CustomEcmascriptTransformPlugins {
-            source_transforms: source_transforms.clone(),
-            output_transforms: output_transforms.clone(),
-        },
-    ));
-
-    let module_options_context = match ty.into_value() {
-        ServerContextType::Pages { .. } | ServerContextType::PagesData { .. } => {
-            let mut base_source_transforms: Vec<TransformPluginVc> = vec![
-                styled_components_transform_plugin,
-                styled_jsx_transform_plugin,
-            ]
-            .into_iter()
-            .flatten()
-            .collect();
-
-            base_source_transforms.extend(source_transforms);
-
-            let custom_ecma_transform_plugins = Some(CustomEcmascriptTransformPluginsVc::cell(
-                CustomEcmascriptTransformPlugins {
-                    source_transforms: base_source_transforms,
-                    output_transforms,
-                },
-            ));
-
-            let module_options_context = ModuleOptionsContext {
-                execution_context: Some(execution_context),
-                ..Default::default()
-            };
-
-            let internal_module_options_context = ModuleOptionsContext {
-                enable_typescript_transform: Some(TypescriptTransformOptions::default().cell()),
-                enable_jsx: Some(JsxTransformOptions::default().cell()),
-                ..module_options_context.clone()
-            };
-
+    let module_options = ServerModuleOptionsContextBuilder::new(ty.kind(), execution_context)
+        .source_transforms(source_transform_plugins)
+        .time_transforms(time_transforms)
+        .postcss(enable_postcss_transform.clone())
+        .build(
             ModuleOptionsContext {
                 enable_jsx: Some(jsx_runtime_options),
                 enable_postcss_transform,
@@ -366,220 +945,559 @@ pub async fn get_server_module_options_context(
                 enable_typescript_transform: Some(tsconfig),
                 enable_mdx_rs,
                 decorators: Some(decorators_options),
-                rules: vec![
-                    (
-                        foreign_code_context_condition,
-                        module_options_context.clone().cell(),
-                    ),
-                    (
-                        ContextCondition::InPath(next_js_fs().root()),
-                        internal_module_options_context.cell(),
-                    ),
-                ],
                 custom_rules,
-                custom_ecma_transform_plugins,
-                ..module_options_context
-            }
-        }
-        ServerContextType::AppSSR { .. } => {
-            let mut base_source_transforms: Vec<TransformPluginVc> = vec![
-                styled_components_transform_plugin,
-                styled_jsx_transform_plugin,
-                server_directive_transform_plugin,
-            ]
-            .into_iter()
-            .flatten()
-            .collect();
+                ..Default::default()
+            },
+            output_transforms,
+            next_config,
+            foreign_code_context_condition,
+        )
+        .await?
+        .cell();
 
-            let base_ecma_transform_plugins = Some(CustomEcmascriptTransformPluginsVc::cell(
-                CustomEcmascriptTransformPlugins {
-                    source_transforms: base_source_transforms.clone(),
-                    output_transforms: vec![],
-                },
-            ));
+    Ok(module_options)
+}
 
-            base_source_transforms.extend(source_transforms);
+/// Removes repeated entries from `transforms`, keeping the first occurrence of
+/// each, so a transform listed both as a base and a shared transform only runs
+/// once.
+fn dedup_transforms(transforms: Vec<SourceTransform>) -> Vec<SourceTransform> {
+    let count = transforms.len();
+    let mut deduped = Vec::with_capacity(count);
+    for transform in transforms {
+        if !deduped.contains(&transform) {
+            deduped.push(transform);
+        }
+    }
+    if deduped.len() < count {
+        tracing::debug!(
+            "collapsed {} duplicate source transforms",
+            count - deduped.len()
+        );
+    }
+    deduped
+}
 
-            let custom_ecma_transform_plugins = Some(CustomEcmascriptTransformPluginsVc::cell(
-                CustomEcmascriptTransformPlugins {
-                    source_transforms: base_source_transforms,
-                    output_transforms,
-                },
-            ));
+/// A source transform of app code in server contexts.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum SourceTransform {
+    StyledComponents,
+    StyledJsx,
+    ClientDirective,
+    ServerDirective,
+    Relay,
+    Emotion,
+    AutoImportReact,
+}
 
-            let module_options_context = ModuleOptionsContext {
-                custom_ecma_transform_plugins: base_ecma_transform_plugins,
-                execution_context: Some(execution_context),
-                ..Default::default()
-            };
-            let internal_module_options_context = ModuleOptionsContext {
-                enable_typescript_transform: Some(TypescriptTransformOptions::default().cell()),
-                ..module_options_context.clone()
-            };
+impl SourceTransform {
+    /// The source transforms every server context applies to app code, after
+    /// its base transforms.
+    const SHARED: [SourceTransform; 3] = [
+        SourceTransform::Relay,
+        SourceTransform::Emotion,
+        SourceTransform::AutoImportReact,
+    ];
 
-            ModuleOptionsContext {
-                enable_jsx: Some(jsx_runtime_options),
-                enable_postcss_transform,
-                enable_webpack_loaders,
-                enable_typescript_transform: Some(tsconfig),
-                enable_mdx_rs,
-                decorators: Some(decorators_options),
-                rules: vec![
-                    (
-                        foreign_code_context_condition,
-                        module_options_context.clone().cell(),
-                    ),
-                    (
-                        ContextCondition::InPath(next_js_fs().root()),
-                        internal_module_options_context.cell(),
-                    ),
-                ],
-                custom_rules,
-                custom_ecma_transform_plugins,
-                ..module_options_context
-            }
+    fn name(&self) -> &'static str {
+        match self {
+            SourceTransform::StyledComponents => "styled-components",
+            SourceTransform::StyledJsx => "styled-jsx",
+            SourceTransform::ClientDirective => "client-directive",
+            SourceTransform::ServerDirective => "server-directive",
+            SourceTransform::Relay => "relay",
+            SourceTransform::Emotion => "emotion",
+            SourceTransform::AutoImportReact => "auto-import-react",
         }
-        ServerContextType::AppRSC { .. } => {
-            let mut base_source_transforms: Vec<TransformPluginVc> = vec![
-                styled_components_transform_plugin,
-                client_directive_transform_plugin,
-                server_directive_transform_plugin,
-            ]
-            .into_iter()
-            .flatten()
-            .collect();
+    }
+}
 
-            let base_ecma_transform_plugins = Some(CustomEcmascriptTransformPluginsVc::cell(
-                CustomEcmascriptTransformPlugins {
-                    source_transforms: base_source_transforms.clone(),
-                    output_transforms: vec![],
-                },
-            ));
+/// The plugin of each [SourceTransform], or `None` if it's disabled.
+#[derive(Default)]
+struct SourceTransformPlugins {
+    styled_components: Option<TransformPluginVc>,
+    styled_jsx: Option<TransformPluginVc>,
+    client_directive: Option<TransformPluginVc>,
+    server_directive: Option<TransformPluginVc>,
+    relay: Option<TransformPluginVc>,
+    emotion: Option<TransformPluginVc>,
+    auto_import_react: Option<TransformPluginVc>,
+}
 
-            base_source_transforms.extend(source_transforms);
+impl SourceTransformPlugins {
+    fn get(&self, transform: SourceTransform) -> Option<TransformPluginVc> {
+        match transform {
+            SourceTransform::StyledComponents => self.styled_components,
+            SourceTransform::StyledJsx => self.styled_jsx,
+            SourceTransform::ClientDirective => self.client_directive,
+            SourceTransform::ServerDirective => self.server_directive,
+            SourceTransform::Relay => self.relay,
+            SourceTransform::Emotion => self.emotion,
+            SourceTransform::AutoImportReact => self.auto_import_react,
+        }
+    }
+}
 
-            let custom_ecma_transform_plugins = Some(CustomEcmascriptTransformPluginsVc::cell(
-                CustomEcmascriptTransformPlugins {
-                    source_transforms: base_source_transforms,
-                    output_transforms,
-                },
-            ));
+/// How the [ModuleOptionsContext] of a server context of some kind differs
+/// from the other kinds. Everything else is shared, see
+/// [ServerModuleOptionsContextBuilder].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct ServerModuleOptionsDeltas {
+    /// The source transforms which run before the
+    /// [shared ones](SourceTransform::SHARED).
+    base_transforms: &'static [SourceTransform],
+    /// Whether the base transforms also apply to foreign code and Next.js
+    /// internals.
+    base_transforms_in_foreign_code: bool,
+    /// Whether Next.js internals are compiled with the JSX transform.
+    internal_jsx: bool,
+}
 
-            let module_options_context = ModuleOptionsContext {
-                custom_ecma_transform_plugins: base_ecma_transform_plugins,
-                execution_context: Some(execution_context),
-                ..Default::default()
-            };
-            let internal_module_options_context = ModuleOptionsContext {
-                enable_typescript_transform: Some(TypescriptTransformOptions::default().cell()),
-                ..module_options_context.clone()
-            };
-            ModuleOptionsContext {
-                enable_jsx: Some(jsx_runtime_options),
-                enable_postcss_transform,
-                enable_webpack_loaders,
-                enable_typescript_transform: Some(tsconfig),
-                enable_mdx_rs,
-                decorators: Some(decorators_options),
-                rules: vec![
-                    (
-                        foreign_code_context_condition,
-                        module_options_context.clone().cell(),
-                    ),
-                    (
-                        ContextCondition::InPath(next_js_fs().root()),
-                        internal_module_options_context.cell(),
-                    ),
+impl ServerModuleOptionsDeltas {
+    fn for_kind(kind: ServerContextKind) -> Self {
+        match kind {
+            ServerContextKind::Pages | ServerContextKind::PagesData => ServerModuleOptionsDeltas {
+                base_transforms: &[
+                    SourceTransform::StyledComponents,
+                    SourceTransform::StyledJsx,
                 ],
-                custom_rules,
-                custom_ecma_transform_plugins,
-                ..module_options_context
-            }
-        }
-        ServerContextType::AppRoute { .. } => {
-            let module_options_context = ModuleOptionsContext {
-                execution_context: Some(execution_context),
-                ..Default::default()
-            };
-            let internal_module_options_context = ModuleOptionsContext {
-                enable_typescript_transform: Some(TypescriptTransformOptions::default().cell()),
-                ..module_options_context.clone()
-            };
-            ModuleOptionsContext {
-                enable_postcss_transform,
-                enable_webpack_loaders,
-                enable_typescript_transform: Some(tsconfig),
-                enable_mdx_rs,
-                decorators: Some(decorators_options),
-                rules: vec![
-                    (
-                        foreign_code_context_condition,
-                        module_options_context.clone().cell(),
-                    ),
-                    (
-                        ContextCondition::InPath(next_js_fs().root()),
-                        internal_module_options_context.cell(),
-                    ),
+                base_transforms_in_foreign_code: false,
+                internal_jsx: true,
+            },
+            ServerContextKind::AppSSR => ServerModuleOptionsDeltas {
+                base_transforms: &[
+                    SourceTransform::StyledComponents,
+                    SourceTransform::StyledJsx,
+                    SourceTransform::ServerDirective,
                 ],
-                custom_rules,
-                custom_ecma_transform_plugins,
-                ..module_options_context
-            }
-        }
-        ServerContextType::Middleware => {
-            let mut base_source_transforms: Vec<TransformPluginVc> = vec![
-                styled_components_transform_plugin,
-                styled_jsx_transform_plugin,
-            ]
+                base_transforms_in_foreign_code: true,
+                internal_jsx: false,
+            },
+            // styled-components only works in client components, which are transpiled in
+            // the AppSSR context, so its transform is skipped here.
+            ServerContextKind::AppRSC => ServerModuleOptionsDeltas {
+                base_transforms: &[
+                    SourceTransform::ClientDirective,
+                    SourceTransform::ServerDirective,
+                ],
+                base_transforms_in_foreign_code: true,
+                internal_jsx: false,
+            },
+            // Route handlers may return an `ImageResponse` from `next/og`, which is written
+            // with JSX, so they still get the shared JSX options.
+            ServerContextKind::AppRoute => ServerModuleOptionsDeltas {
+                base_transforms: &[],
+                base_transforms_in_foreign_code: false,
+                internal_jsx: false,
+            },
+            ServerContextKind::Middleware => ServerModuleOptionsDeltas {
+                base_transforms: &[
+                    SourceTransform::StyledComponents,
+                    SourceTransform::StyledJsx,
+                ],
+                base_transforms_in_foreign_code: false,
+                internal_jsx: false,
+            },
+        }
+    }
+}
+
+/// Assembles the [ModuleOptionsContext] of a server context: app code gets
+/// the shared options, while foreign code and Next.js internals get the
+/// context-specific subset described by [ServerModuleOptionsDeltas].
+struct ServerModuleOptionsContextBuilder {
+    deltas: ServerModuleOptionsDeltas,
+    execution_context: ExecutionContextVc,
+    source_transforms: SourceTransformPlugins,
+    time_transforms: bool,
+    postcss: Option<PostCssTransformOptions>,
+}
+
+impl ServerModuleOptionsContextBuilder {
+    fn new(kind: ServerContextKind, execution_context: ExecutionContextVc) -> Self {
+        ServerModuleOptionsContextBuilder {
+            deltas: ServerModuleOptionsDeltas::for_kind(kind),
+            execution_context,
+            source_transforms: SourceTransformPlugins::default(),
+            time_transforms: false,
+            postcss: None,
+        }
+    }
+
+    /// Sets the PostCSS options of all code, including foreign code, so the
+    /// CSS of dependencies (and the files it `@import`s) is processed like
+    /// app CSS, as on the client.
+    fn postcss(mut self, options: Option<PostCssTransformOptions>) -> Self {
+        self.postcss = options;
+        self
+    }
+
+    /// Sets the plugins of the source transforms. The context applies the
+    /// enabled ones of its base transforms, followed by the shared ones.
+    fn source_transforms(mut self, plugins: SourceTransformPlugins) -> Self {
+        self.source_transforms = plugins;
+        self
+    }
+
+    /// Sets whether the execution time of the source transforms is recorded.
+    fn time_transforms(mut self, enabled: bool) -> Self {
+        self.time_transforms = enabled;
+        self
+    }
+
+    /// Returns the enabled ones of `transforms`, in order.
+    fn enabled_transforms(
+        &self,
+        transforms: impl IntoIterator<Item = SourceTransform>,
+    ) -> Vec<SourceTransform> {
+        transforms
             .into_iter()
-            .flatten()
-            .collect();
+            .filter(|transform| self.source_transforms.get(*transform).is_some())
+            .collect()
+    }
 
-            base_source_transforms.extend(source_transforms);
+    /// Returns the plugins of `transforms`, which must be enabled.
+    fn transform_plugins(&self, transforms: &[SourceTransform]) -> Vec<TransformPluginVc> {
+        transforms
+            .iter()
+            .filter_map(|transform| {
+                timed_transform_plugin(
+                    transform.name(),
+                    self.source_transforms.get(*transform),
+                    self.time_transforms,
+                )
+            })
+            .collect()
+    }
 
-            let custom_ecma_transform_plugins = Some(CustomEcmascriptTransformPluginsVc::cell(
-                CustomEcmascriptTransformPlugins {
-                    source_transforms: base_source_transforms,
-                    output_transforms,
-                },
-            ));
+    /// The source transforms applied to foreign code and Next.js internals.
+    fn foreign_code_transforms(&self) -> Vec<SourceTransform> {
+        if self.deltas.base_transforms_in_foreign_code {
+            self.enabled_transforms(self.deltas.base_transforms.iter().copied())
+        } else {
+            vec![]
+        }
+    }
 
-            let module_options_context = ModuleOptionsContext {
-                execution_context: Some(execution_context),
-                ..Default::default()
-            };
-            let internal_module_options_context = ModuleOptionsContext {
-                enable_typescript_transform: Some(TypescriptTransformOptions::default().cell()),
-                ..module_options_context.clone()
-            };
-            ModuleOptionsContext {
-                enable_jsx: Some(jsx_runtime_options),
-                enable_postcss_transform,
-                enable_webpack_loaders,
-                enable_typescript_transform: Some(tsconfig),
-                enable_mdx_rs,
-                decorators: Some(decorators_options),
-                rules: vec![
-                    (
-                        foreign_code_context_condition,
-                        module_options_context.clone().cell(),
-                    ),
-                    (
-                        ContextCondition::InPath(next_js_fs().root()),
-                        internal_module_options_context.cell(),
-                    ),
-                ],
-                custom_rules,
-                custom_ecma_transform_plugins,
-                ..module_options_context
+    /// The source transforms applied to app code: the base transforms first,
+    /// then the shared ones which aren't base transforms already.
+    fn app_transforms(&self) -> Vec<SourceTransform> {
+        dedup_transforms(
+            self.enabled_transforms(
+                self.deltas
+                    .base_transforms
+                    .iter()
+                    .copied()
+                    .chain(SourceTransform::SHARED),
+            ),
+        )
+    }
+
+    /// The options foreign code is compiled with.
+    fn foreign_module_options_context(&self) -> ModuleOptionsContext {
+        ModuleOptionsContext {
+            custom_ecma_transform_plugins: self.deltas.base_transforms_in_foreign_code.then(|| {
+                CustomEcmascriptTransformPluginsVc::cell(CustomEcmascriptTransformPlugins {
+                    source_transforms: self.transform_plugins(&self.foreign_code_transforms()),
+                    output_transforms: vec![],
+                })
+            }),
+            enable_postcss_transform: self.postcss.clone(),
+            execution_context: Some(self.execution_context),
+            ..Default::default()
+        }
+    }
+
+    /// The options Next.js internals are compiled with.
+    fn internal_module_options_context(&self) -> ModuleOptionsContext {
+        ModuleOptionsContext {
+            enable_typescript_transform: Some(TypescriptTransformOptions::default().cell()),
+            enable_jsx: self
+                .deltas
+                .internal_jsx
+                .then(|| JsxTransformOptions::default().cell()),
+            ..self.foreign_module_options_context()
+        }
+    }
+
+    /// Returns the options for app code, which are `app` plus the rules for
+    /// foreign code and Next.js internals and the source transforms.
+    async fn build(
+        self,
+        app: ModuleOptionsContext,
+        output_transforms: Vec<TransformPluginVc>,
+        next_config: NextConfigVc,
+        foreign_code_context_condition: ContextCondition,
+    ) -> Result<ServerModuleOptions> {
+        let foreign_module_options_context = self.foreign_module_options_context();
+        let internal_module_options_context = self.internal_module_options_context().cell();
+        let rules = foreign_code_module_options_rules(
+            next_config,
+            foreign_code_context_condition,
+            &foreign_module_options_context,
+        )
+        .await?
+        .into_iter()
+        .chain([(
+            ContextCondition::InPath(internal_code_root()),
+            internal_module_options_context,
+        )])
+        .collect();
+
+        let app_transforms = self.app_transforms();
+        let names = |transforms: &[SourceTransform]| {
+            transforms
+                .iter()
+                .map(|transform| transform.name().to_string())
+                .collect()
+        };
+
+        Ok(ServerModuleOptions {
+            context: ModuleOptionsContext {
+                rules,
+                custom_ecma_transform_plugins: Some(CustomEcmascriptTransformPluginsVc::cell(
+                    CustomEcmascriptTransformPlugins {
+                        source_transforms: self.transform_plugins(&app_transforms),
+                        output_transforms,
+                    },
+                )),
+                execution_context: Some(self.execution_context),
+                ..app
             }
+            .cell(),
+            internal_context: internal_module_options_context,
+            transforms: names(&app_transforms),
+            foreign_code_transforms: names(&self.foreign_code_transforms()),
+        })
+    }
+}
+
+/// A summary of the features enabled by [get_server_module_options_context],
+/// e.g. to show users how their project will be compiled.
+#[turbo_tasks::value(shared)]
+#[derive(Debug)]
+pub struct ModuleOptionsSummary {
+    pub postcss: bool,
+    pub webpack_loaders: bool,
+    pub mdx: bool,
+    /// The names of the source transforms applied to app code, in order.
+    pub transforms: Vec<String>,
+}
+
+/// Returns whether `experimental.skipSourceTransforms` disables all source
+/// transforms of app code.
+fn skips_source_transforms(next_config: &NextConfig) -> bool {
+    next_config
+        .experimental
+        .skip_source_transforms
+        .unwrap_or(false)
+}
+
+/// Returns the [ServerContextType] of `kind` for the project at
+/// `project_path`, whose routes are in its `pages` or `app` directory.
+fn server_context_type(
+    kind: ServerContextKind,
+    project_path: FileSystemPathVc,
+) -> ServerContextType {
+    let pages_dir = project_path.join("pages");
+    let app_dir = project_path.join("app");
+    match kind {
+        ServerContextKind::Pages => ServerContextType::Pages { pages_dir },
+        ServerContextKind::PagesData => ServerContextType::PagesData { pages_dir },
+        ServerContextKind::AppSSR => ServerContextType::AppSSR { app_dir },
+        ServerContextKind::AppRSC => ServerContextType::AppRSC { app_dir },
+        ServerContextKind::AppRoute => ServerContextType::AppRoute { app_dir },
+        ServerContextKind::Middleware => ServerContextType::Middleware,
+    }
+}
+
+/// Returns a stable, human readable summary of what a server context of type
+/// `ty` is compiled with in `mode`, taken from its resolve options, module
+/// options and compile-time info: the resolve conditions, the source
+/// transforms applied to app code and foreign code, whether Next.js internals
+/// get JSX and the compile-time defines. Like in the router, Middleware is
+/// resolved and compiled for the edge runtime.
+#[turbo_tasks::function]
+pub async fn server_context_snapshot(
+    project_path: FileSystemPathVc,
+    execution_context: ExecutionContextVc,
+    ty: Value<ServerContextType>,
+    mode: NextMode,
+    next_config: NextConfigVc,
+) -> Result<StringVc> {
+    let server_addr = ServerAddrVc::empty();
+    let (resolve_options_context, compile_time_info) = if ty.kind() == ServerContextKind::Middleware
+    {
+        (
+            get_edge_resolve_options_context(project_path, ty, next_config, execution_context),
+            get_edge_compile_time_info(project_path, server_addr),
+        )
+    } else {
+        (
+            get_server_resolve_options_context(
+                project_path,
+                ty,
+                mode,
+                next_config,
+                execution_context,
+                ResolvePluginsVc::empty(),
+            ),
+            get_server_compile_time_info(
+                project_path,
+                mode,
+                EnvMapVc::empty().into(),
+                server_addr,
+                next_config,
+            ),
+        )
+    };
+    let module_options =
+        get_server_module_options(project_path, execution_context, ty, mode, next_config).await?;
+
+    let mut defines = compile_time_info
+        .await?
+        .defines
+        .await?
+        .0
+        .iter()
+        .map(|(path, value)| format!("  {} = {:?}", path.join("."), value))
+        .collect::<Vec<_>>();
+    defines.sort();
+    let list = |names: &[String]| {
+        if names.is_empty() {
+            "(none)".to_string()
+        } else {
+            names.join(", ")
         }
+    };
+
+    Ok(StringVc::cell(format!(
+        "# {} ({:?})\nconditions: {}\ntransforms: {}\nforeign code transforms: {}\ninternal jsx: \
+         {}\ndefines:\n{}\n",
+        ty.kind().as_str(),
+        mode,
+        resolve_options_context.await?.custom_conditions.join(", "),
+        list(&module_options.transforms),
+        list(&module_options.foreign_code_transforms),
+        module_options.internal_context.await?.enable_jsx.is_some(),
+        defines.join("\n")
+    )))
+}
+
+/// Returns the [server_context_snapshot]s of every server context kind in
+/// every mode it can be compiled in, for the project at `project_path`.
+///
+/// The golden file `src/next_server/snapshots/server_contexts.snap` holds this
+/// snapshot for an empty project with the default config. After an intended
+/// change, regenerate it with
+/// `UPDATE=1 cargo test -p next-core server_context_snapshots`.
+#[turbo_tasks::function]
+pub async fn server_context_snapshots(
+    project_path: FileSystemPathVc,
+    execution_context: ExecutionContextVc,
+    next_config: NextConfigVc,
+) -> Result<StringVc> {
+    let mut snapshots = Vec::new();
+    for kind in ServerContextKind::ALL {
+        for (mode, _) in NextMode::NODE_ENVS {
+            // The routes directory matches the kind, see [server_context_type].
+            if invalid_server_context_reason(kind, None, mode).is_some() {
+                continue;
+            }
+            let ty = Value::new(server_context_type(kind, project_path));
+            let snapshot =
+                server_context_snapshot(project_path, execution_context, ty, mode, next_config)
+                    .await?;
+            snapshots.push(snapshot.clone_value());
+        }
+    }
+    Ok(StringVc::cell(snapshots.join("\n")))
+}
+
+/// Describes the module options [get_server_module_options_context] creates
+/// for the given context, without compiling anything.
+#[turbo_tasks::function]
+pub async fn describe_server_module_options(
+    project_path: FileSystemPathVc,
+    execution_context: ExecutionContextVc,
+    ty: Value<ServerContextType>,
+    mode: NextMode,
+    next_config: NextConfigVc,
+) -> Result<ModuleOptionsSummaryVc> {
+    let module_options =
+        get_server_module_options(project_path, execution_context, ty, mode, next_config).await?;
+    let module_options_context = module_options.context.await?;
+
+    Ok(ModuleOptionsSummary {
+        postcss: module_options_context.enable_postcss_transform.is_some(),
+        webpack_loaders: module_options_context.enable_webpack_loaders.is_some(),
+        mdx: module_options_context.enable_mdx_rs.is_some(),
+        transforms: module_options.transforms.clone(),
     }
-    .cell();
+    .cell())
+}
 
-    Ok(module_options_context)
+/// Returns the path of the `experimental.serverPrelude` module relative to the
+/// project root.
+fn server_prelude_path(next_config: &NextConfig) -> Option<&str> {
+    next_config
+        .experimental
+        .server_prelude
+        .as_deref()
+        .map(|path| path.trim_start_matches("./"))
+}
+
+/// Returns the assets evaluated before any server entry: the injected
+/// `process.env`, followed by the `experimental.serverPrelude` module if one is
+/// configured.
+#[turbo_tasks::function]
+pub async fn get_server_runtime_entries(
+    project_root: FileSystemPathVc,
+    injected_env: ProcessEnvVc,
+    next_config: NextConfigVc,
+) -> Result<AssetsVc> {
+    let mut runtime_entries: Vec<AssetVc> =
+        vec![ProcessEnvAssetVc::new(project_root, injected_env).into()];
+    if let Some(prelude) = server_prelude_path(&*next_config.await?) {
+        runtime_entries.push(SourceAssetVc::new(project_root.join(prelude)).into());
+    }
+    Ok(AssetsVc::cell(runtime_entries))
+}
+
+/// Returns the paths of the `experimental.edgePolyfills` modules, relative to
+/// the project root, which are loaded in a context of the given kind. Only the
+/// Middleware, which runs in the edge runtime, gets them.
+fn edge_polyfill_paths(kind: ServerContextKind, next_config: &NextConfig) -> Vec<&str> {
+    if kind != ServerContextKind::Middleware {
+        return Vec::new();
+    }
+    next_config
+        .experimental
+        .edge_polyfills
+        .iter()
+        .flatten()
+        .map(|path| path.trim_start_matches("./"))
+        .collect()
 }
 
+/// Returns the `experimental.edgePolyfills` modules which are evaluated before
+/// any entry of a server context of the given type.
+#[turbo_tasks::function]
+pub async fn get_edge_polyfills(
+    project_root: FileSystemPathVc,
+    ty: Value<ServerContextType>,
+    next_config: NextConfigVc,
+) -> Result<AssetsVc> {
+    Ok(AssetsVc::cell(
+        edge_polyfill_paths(ty.kind(), &*next_config.await?)
+            .into_iter()
+            .map(|path| SourceAssetVc::new(project_root.join(path)).into())
+            .collect(),
+    ))
+}
+
+/// Module options for code which Next.js compiles and evaluates itself, like a
+/// `next.config.ts`. Types are stripped, but no framework transforms apply.
 #[turbo_tasks::function]
 pub fn get_build_module_options_context() -> ModuleOptionsContextVc {
     ModuleOptionsContext {
@@ -588,3 +1506,1338 @@ pub fn get_build_module_options_context() -> ModuleOptionsContextVc {
     }
     .cell()
 }
+
+/// Resolve options to go with [get_build_module_options_context]. Relative
+/// `.ts` imports resolve, and packages (like the `next` import providing the
+/// `NextConfig` type) are looked up in `node_modules` and kept external.
+#[turbo_tasks::function]
+pub async fn get_build_resolve_options_context(
+    project_path: FileSystemPathVc,
+) -> Result<ResolveOptionsContextVc> {
+    let root_dir = project_path.root().resolve().await?;
+    Ok(ResolveOptionsContext {
+        enable_typescript: true,
+        enable_node_modules: Some(root_dir),
+        enable_node_externals: true,
+        enable_node_native_modules: true,
+        module: true,
+        custom_conditions: vec!["node".to_string()],
+        ..Default::default()
+    }
+    .cell())
+}
+
+/// The asset context for code which Next.js compiles and evaluates itself at
+/// build time, like a `next.config.ts`.
+#[turbo_tasks::function]
+pub fn get_build_asset_context(project_path: FileSystemPathVc) -> AssetContextVc {
+    ModuleAssetContextVc::new(
+        TransitionsByNameVc::cell(HashMap::new()),
+        CompileTimeInfo::builder(node_build_environment()).cell(),
+        get_build_module_options_context(),
+        get_build_resolve_options_context(project_path),
+    )
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{HashMap, HashSet},
+        fmt::Debug,
+        sync::{Arc, Mutex},
+    };
+
+    use anyhow::Result;
+    use indexmap::IndexMap;
+    use swc_core::ecma::{
+        ast::{JSXElement, TsTypeAnn},
+        visit::{Visit, VisitWith},
+    };
+    use tracing::{
+        field::{Field, Visit as FieldVisit},
+        span::{Attributes, Id},
+        Subscriber,
+    };
+    use tracing_subscriber::{
+        layer::{Context, SubscriberExt},
+        registry::LookupSpan,
+        Layer,
+    };
+    use turbo_tasks::{primitives::StringVc, Value};
+    use turbopack_binding::{
+        turbo::{
+            tasks::TurboTasks,
+            tasks_env::EnvMapVc,
+            tasks_fs::{FileSystem, FileSystemPathVc, VirtualFileSystemVc},
+            tasks_memory::MemoryBackend,
+        },
+        turbopack::{
+            core::{
+                asset::Asset,
+                compile_time_info::{CompileTimeDefineValue, CompileTimeInfo, FreeVarReference},
+                context::AssetContext,
+                issue::IssueVc,
+                reference::AssetReference,
+                reference_type::{EntryReferenceSubType, ReferenceType},
+                resolve::{parse::RequestVc, pattern::Pattern, resolve},
+                source_asset::SourceAssetVc,
+            },
+            ecmascript::{parse::ParseResult, EcmascriptModuleAssetVc, TransformPluginVc},
+            ecmascript_plugin::transform::directives::server::ServerDirectiveTransformer,
+            turbopack::{
+                evaluate_context::node_build_environment, module_options::ModuleOptionsContext,
+                resolve_options, transition::TransitionsByNameVc, ModuleAssetContextVc,
+            },
+        },
+    };
+
+    use super::{
+        bundles_pages_router_dependencies, client_directive_transition, defines,
+        describe_server_module_options, edge_polyfill_paths, free_vars, get_build_asset_context,
+        get_server_module_options, get_server_resolve_options_context, get_server_runtime_entries,
+        native_modules_disabled_by, react_version_define, resolves_module_field,
+        server_context_snapshots, server_context_type, server_prelude_path, validate,
+        ResolvePluginVc, ResolvePluginsVc, ServerContextKind, ServerContextType,
+        ServerModuleOptionsContextBuilder, SourceTransform, SourceTransformPlugins,
+        UnsupportedModulesResolvePluginVc,
+    };
+    use crate::{
+        mode::NextMode,
+        next_config::{ClientDirectiveConfig, FreeVarConfig, NextConfig},
+        next_server::resolve::matches_external_predicate,
+        test_util::{execution_context, project_root, run_in_project, write_project},
+        util::foreign_code_context_condition,
+    };
+
+    fn has_preview_define(next_config: &NextConfig) -> bool {
+        defines(NextMode::Build, next_config).0.contains_key(&vec![
+            "process".to_string(),
+            "env".to_string(),
+            "__NEXT_PREVIEW".to_string(),
+        ])
+    }
+
+    #[test]
+    fn test_response_option_defines() {
+        let powered_by_header_define = |next_config: &NextConfig| {
+            defines(NextMode::Build, next_config)
+                .0
+                .get(&vec![
+                    "process".to_string(),
+                    "env".to_string(),
+                    "__NEXT_POWERED_BY_HEADER".to_string(),
+                ])
+                .cloned()
+        };
+
+        let mut next_config = NextConfig::default();
+        assert_eq!(
+            powered_by_header_define(&next_config),
+            Some(CompileTimeDefineValue::Bool(true))
+        );
+
+        next_config.powered_by_header = Some(false);
+        assert_eq!(
+            powered_by_header_define(&next_config),
+            Some(CompileTimeDefineValue::Bool(false))
+        );
+    }
+
+    #[test]
+    fn test_strict_mode_defines() {
+        let strict_mode_defines = |next_config: &NextConfig| {
+            let defines = defines(NextMode::Development, next_config).0;
+            ["__NEXT_STRICT_MODE", "__NEXT_STRICT_MODE_APP"].map(|name| {
+                defines
+                    .get(&vec![
+                        "process".to_string(),
+                        "env".to_string(),
+                        name.to_string(),
+                    ])
+                    .cloned()
+            })
+        };
+
+        let mut next_config = NextConfig::default();
+        assert_eq!(
+            strict_mode_defines(&next_config),
+            [
+                Some(CompileTimeDefineValue::Bool(false)),
+                Some(CompileTimeDefineValue::Bool(true))
+            ]
+        );
+
+        next_config.react_strict_mode = Some(false);
+        assert_eq!(
+            strict_mode_defines(&next_config),
+            [
+                Some(CompileTimeDefineValue::Bool(false)),
+                Some(CompileTimeDefineValue::Bool(false))
+            ]
+        );
+
+        next_config.react_strict_mode = Some(true);
+        assert_eq!(
+            strict_mode_defines(&next_config),
+            [
+                Some(CompileTimeDefineValue::Bool(true)),
+                Some(CompileTimeDefineValue::Bool(true))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_preview_define() {
+        let mut next_config = NextConfig::default();
+        assert!(!has_preview_define(&next_config));
+
+        next_config.experimental.preview_build = Some(true);
+        assert!(has_preview_define(&next_config));
+    }
+
+    #[test]
+    fn test_configured_free_vars() {
+        let mut next_config = NextConfig::default();
+        next_config.experimental.free_vars = Some(IndexMap::from([
+            (
+                "myLib".to_string(),
+                FreeVarConfig::Request("my-lib".to_string()),
+            ),
+            (
+                "process.turbopack".to_string(),
+                FreeVarConfig::Request("my-turbopack".to_string()),
+            ),
+        ]));
+
+        let free_vars = free_vars(NextMode::Development, &next_config);
+        assert!(free_vars.0.contains_key(&vec!["myLib".to_string()]));
+
+        // The `process.turbopack` define wins over the configured free var.
+        assert!(matches!(
+            free_vars
+                .0
+                .get(&vec!["process".to_string(), "turbopack".to_string()]),
+            Some(FreeVarReference::Value(_))
+        ));
+    }
+
+    #[test]
+    fn test_skip_trailing_slash_redirect_define() {
+        let manual_trailing_slash = vec![
+            "process".to_string(),
+            "env".to_string(),
+            "__NEXT_MANUAL_TRAILING_SLASH".to_string(),
+        ];
+        let mut next_config = NextConfig::default();
+        assert!(matches!(
+            defines(NextMode::Build, &next_config)
+                .0
+                .get(&manual_trailing_slash),
+            Some(CompileTimeDefineValue::Bool(false))
+        ));
+
+        next_config.skip_trailing_slash_redirect = Some(true);
+        assert!(matches!(
+            defines(NextMode::Build, &next_config)
+                .0
+                .get(&manual_trailing_slash),
+            Some(CompileTimeDefineValue::Bool(true))
+        ));
+    }
+
+    #[test]
+    fn test_compiler_define() {
+        let next_config = NextConfig {
+            compiler: Some(
+                serde_json::from_str(
+                    r#"{
+                        "define": {
+                            "__APP_VERSION__": "1.2.3",
+                            "process.env.FEATURE": true
+                        }
+                    }"#,
+                )
+                .unwrap(),
+            ),
+            ..Default::default()
+        };
+
+        let app_version = vec!["__APP_VERSION__".to_string()];
+        assert!(!defines(NextMode::Build, &next_config)
+            .0
+            .contains_key(&app_version));
+        assert!(matches!(
+            free_vars(NextMode::Build, &next_config).0.get(&app_version),
+            Some(FreeVarReference::Value(CompileTimeDefineValue::String(version)))
+                if version == "1.2.3"
+        ));
+
+        assert!(matches!(
+            defines(NextMode::Build, &next_config).0.get(&vec![
+                "process".to_string(),
+                "env".to_string(),
+                "FEATURE".to_string()
+            ]),
+            Some(CompileTimeDefineValue::Bool(true))
+        ));
+    }
+
+    #[test]
+    fn test_server_prelude_path() {
+        let mut next_config = NextConfig::default();
+        assert_eq!(server_prelude_path(&next_config), None);
+
+        next_config.experimental.server_prelude = Some("./polyfills/server.js".to_string());
+        assert_eq!(
+            server_prelude_path(&next_config),
+            Some("polyfills/server.js")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_server_runtime_entries() -> Result<()> {
+        crate::register();
+        let tt = TurboTasks::new(MemoryBackend::default());
+        tt.run_once(async move {
+            let root = VirtualFileSystemVc::new().as_file_system().root();
+            let entry_paths = |next_config: NextConfig| async move {
+                let entries =
+                    get_server_runtime_entries(root, EnvMapVc::empty().into(), next_config.cell())
+                        .await?;
+                let mut paths = Vec::new();
+                for entry in entries.iter() {
+                    paths.push(entry.ident().path().await?.path.clone());
+                }
+                anyhow::Ok(paths)
+            };
+
+            let env_paths = entry_paths(NextConfig::default()).await?;
+            assert_eq!(env_paths.len(), 1);
+
+            let mut next_config = NextConfig::default();
+            next_config.experimental.server_prelude = Some("./polyfills/server.js".to_string());
+            let mut expected = env_paths;
+            // The prelude comes after the injected `process.env`, which it may use, and
+            // before any entry.
+            expected.push("polyfills/server.js".to_string());
+            assert_eq!(entry_paths(next_config).await?, expected);
+            Ok(())
+        })
+        .await
+    }
+
+    #[test]
+    fn test_edge_polyfill_paths() {
+        let mut next_config = NextConfig::default();
+        assert!(edge_polyfill_paths(ServerContextKind::Middleware, &next_config).is_empty());
+
+        next_config.experimental.edge_polyfills = Some(vec![
+            "./polyfills/edge.js".to_string(),
+            "polyfills/crypto.js".to_string(),
+        ]);
+        // The polyfill modules are evaluated in a Middleware entry...
+        assert_eq!(
+            edge_polyfill_paths(ServerContextKind::Middleware, &next_config),
+            vec!["polyfills/edge.js", "polyfills/crypto.js"]
+        );
+        // ...but never in a node.js entry.
+        assert!(edge_polyfill_paths(ServerContextKind::Pages, &next_config).is_empty());
+        assert!(edge_polyfill_paths(ServerContextKind::AppRSC, &next_config).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_server_contexts() -> Result<()> {
+        crate::register();
+        let tt = TurboTasks::new(MemoryBackend::default());
+        tt.run_once(async move {
+            let root = VirtualFileSystemVc::new().as_file_system().root();
+            let pages_dir = root.join("src/pages");
+            let app_dir = root.join("src/app");
+
+            assert!(
+                validate(root, ServerContextType::Middleware, NextMode::Build)
+                    .await
+                    .is_err()
+            );
+            assert!(
+                validate(root, ServerContextType::Middleware, NextMode::Development)
+                    .await
+                    .is_ok()
+            );
+            assert!(validate(
+                root,
+                ServerContextType::PagesData { pages_dir },
+                NextMode::Build
+            )
+            .await
+            .is_ok());
+            assert!(validate(
+                root,
+                ServerContextType::AppRSC { app_dir },
+                NextMode::Development
+            )
+            .await
+            .is_ok());
+
+            // Routes of the `app` directory can't be compiled as pages (or
+            // pages data), and vice versa.
+            assert!(validate(
+                root,
+                ServerContextType::PagesData { pages_dir: app_dir },
+                NextMode::Development
+            )
+            .await
+            .is_err());
+            assert!(validate(
+                root,
+                ServerContextType::AppSSR { app_dir: pages_dir },
+                NextMode::Build
+            )
+            .await
+            .is_err());
+            Ok(())
+        })
+        .await
+    }
+
+    #[test]
+    fn test_client_directive_transition() {
+        let mut next_config = NextConfig::default();
+        assert_eq!(
+            client_directive_transition(&next_config).as_deref(),
+            Some("server-to-client")
+        );
+
+        next_config.experimental.client_directive = Some(ClientDirectiveConfig::Transition {
+            transition: "prerender-to-client".to_string(),
+        });
+        assert_eq!(
+            client_directive_transition(&next_config).as_deref(),
+            Some("prerender-to-client")
+        );
+
+        next_config.experimental.client_directive = Some(ClientDirectiveConfig::Enabled(false));
+        assert_eq!(client_directive_transition(&next_config), None);
+    }
+
+    #[tokio::test]
+    async fn test_describe_server_module_options() -> Result<()> {
+        run_in_project(&[], |root| async move {
+            let describe = |next_config: NextConfig| {
+                describe_server_module_options(
+                    root,
+                    execution_context(root),
+                    Value::new(server_context_type(ServerContextKind::AppRSC, root)),
+                    NextMode::Development,
+                    next_config.cell(),
+                )
+            };
+
+            let mut next_config = NextConfig {
+                experimental: serde_json::from_str(r#"{ "mdxRs": true }"#)?,
+                ..Default::default()
+            };
+            let summary = describe(next_config.clone()).await?;
+            assert!(summary.mdx);
+            assert!(summary.postcss);
+            assert_eq!(
+                summary.transforms,
+                vec!["client-directive", "server-directive"]
+            );
+
+            // Disabling the transform leaves `'use client'` modules untransformed.
+            next_config.experimental.client_directive = Some(ClientDirectiveConfig::Enabled(false));
+            let summary = describe(next_config).await?;
+            assert_eq!(summary.transforms, vec!["server-directive"]);
+            Ok(())
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_app_rsc_transform_names() -> Result<()> {
+        run_in_project(
+            &[(
+                "node_modules/styled-components/package.json",
+                r#"{ "name": "styled-components" }"#,
+            )],
+            |root| async move {
+                let next_config = NextConfig {
+                    compiler: Some(serde_json::from_str(r#"{ "styledComponents": true }"#)?),
+                    ..Default::default()
+                };
+
+                // styled-components only runs where client components are compiled.
+                assert_eq!(
+                    app_transforms(root, ServerContextKind::AppRSC, next_config.clone()).await?,
+                    vec!["client-directive", "server-directive"]
+                );
+                assert_eq!(
+                    app_transforms(root, ServerContextKind::AppSSR, next_config).await?,
+                    vec!["styled-components", "styled-jsx", "server-directive"]
+                );
+                Ok(())
+            },
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_server_context_snapshots() -> Result<()> {
+        let snapshot = run_in_project(&[], |root| async move {
+            let snapshot = server_context_snapshots(
+                root,
+                execution_context(root),
+                NextConfig::default().cell(),
+            )
+            .await?;
+            Ok(snapshot.clone_value())
+        })
+        .await?;
+
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("src/next_server/snapshots/server_contexts.snap");
+        if std::env::var_os("UPDATE").is_some() {
+            std::fs::write(&path, snapshot)?;
+            return Ok(());
+        }
+
+        assert_eq!(
+            snapshot,
+            std::fs::read_to_string(&path)?,
+            "server contexts changed, run with UPDATE=1 to update {}",
+            path.display()
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_skip_source_transforms() -> Result<()> {
+        run_in_project(&[], |root| async move {
+            let mut next_config = NextConfig::default();
+            assert!(
+                !app_transforms(root, ServerContextKind::AppRSC, next_config.clone())
+                    .await?
+                    .is_empty()
+            );
+
+            next_config.experimental.skip_source_transforms = Some(true);
+            for kind in ServerContextKind::ALL {
+                assert!(app_transforms(root, kind, next_config.clone())
+                    .await?
+                    .is_empty());
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    #[test]
+    fn test_react_version_define() {
+        let mut defines = defines(NextMode::Build, &NextConfig::default());
+        defines.0.extend(react_version_define(Some("18.2.0")));
+        assert!(matches!(
+            defines.0.get(&vec![
+                "process".to_string(),
+                "env".to_string(),
+                "__NEXT_REACT_VERSION".to_string()
+            ]),
+            Some(CompileTimeDefineValue::String(version)) if version == "18"
+        ));
+
+        assert!(matches!(
+            react_version_define(Some("19.0.0-rc-f994737d14-20240522")),
+            Some((_, CompileTimeDefineValue::String(version))) if version == "19"
+        ));
+        assert!(react_version_define(Some("experimental")).is_none());
+        assert!(react_version_define(None).is_none());
+    }
+
+    #[test]
+    fn test_bundle_pages_router_dependencies() {
+        let dependency = "project/node_modules/lodash/index.js";
+        let server_externals = vec!["sharp".to_string()];
+        let mut next_config = NextConfig::default();
+        assert!(matches_external_predicate(
+            !bundles_pages_router_dependencies(&next_config),
+            &[],
+            dependency
+        ));
+
+        next_config.bundle_pages_router_dependencies = Some(true);
+        let all_except = !bundles_pages_router_dependencies(&next_config);
+        assert!(!matches_external_predicate(
+            all_except,
+            &server_externals,
+            dependency
+        ));
+        assert!(matches_external_predicate(
+            all_except,
+            &server_externals,
+            "project/node_modules/sharp/lib/index.js"
+        ));
+    }
+
+    #[test]
+    fn test_native_modules_disabled() {
+        let mut next_config = NextConfig::default();
+        assert_eq!(
+            native_modules_disabled_by(ServerContextKind::Pages, &next_config),
+            None
+        );
+
+        next_config.experimental.ssr_native_modules = Some(false);
+        assert_eq!(
+            native_modules_disabled_by(ServerContextKind::AppSSR, &next_config),
+            Some("experimental.ssrNativeModules")
+        );
+        assert_eq!(
+            native_modules_disabled_by(ServerContextKind::AppRSC, &next_config),
+            None
+        );
+
+        // `.node` imports fail to resolve with an issue in every context which
+        // supports native modules otherwise.
+        next_config.experimental.native_modules = Some(false);
+        for kind in [
+            ServerContextKind::Pages,
+            ServerContextKind::PagesData,
+            ServerContextKind::AppSSR,
+            ServerContextKind::AppRSC,
+        ] {
+            assert_eq!(
+                native_modules_disabled_by(kind, &next_config),
+                Some("experimental.nativeModules")
+            );
+        }
+    }
+
+    #[test]
+    fn test_resolves_module_field() {
+        let mut next_config = NextConfig::default();
+        assert!(resolves_module_field(
+            ServerContextKind::AppRoute,
+            &next_config
+        ));
+
+        next_config.experimental.app_route_module_resolution = Some(false);
+        assert!(!resolves_module_field(
+            ServerContextKind::AppRoute,
+            &next_config
+        ));
+        assert!(resolves_module_field(
+            ServerContextKind::AppRSC,
+            &next_config
+        ));
+    }
+
+    /// Returns the names of the source transforms the module options of a
+    /// server context of `kind` apply to app code.
+    async fn app_transforms(
+        root: FileSystemPathVc,
+        kind: ServerContextKind,
+        next_config: NextConfig,
+    ) -> Result<Vec<String>> {
+        let module_options = get_server_module_options(
+            root,
+            execution_context(root),
+            Value::new(server_context_type(kind, root)),
+            NextMode::Development,
+            next_config.cell(),
+        )
+        .await?;
+        Ok(module_options.transforms.clone())
+    }
+
+    #[tokio::test]
+    async fn test_build_asset_context_compiles_next_config_ts() -> Result<()> {
+        run_in_project(
+            &[
+                (
+                    "next.config.ts",
+                    "import type { NextConfig } from 'next'\nimport { basePath } from \
+                     './config/base-path'\n\nconst config: NextConfig = { basePath }\n\nexport \
+                     default config\n",
+                ),
+                (
+                    "config/base-path.ts",
+                    "export const basePath: string = '/docs'\n",
+                ),
+                ("node_modules/next/package.json", r#"{ "name": "next" }"#),
+                ("node_modules/next/index.js", "module.exports = {}\n"),
+            ],
+            |root| async move {
+                let config_asset = get_build_asset_context(root).process(
+                    SourceAssetVc::new(root.join("next.config.ts")).into(),
+                    Value::new(ReferenceType::Entry(EntryReferenceSubType::Undefined)),
+                );
+
+                let mut referenced = Vec::new();
+                for reference in config_asset.references().await?.iter() {
+                    for asset in reference.resolve_reference().primary_assets().await?.iter() {
+                        referenced.push(asset.ident().path().await?.path.clone());
+                    }
+                }
+                // The relative `.ts` import resolves, while the type import of `next`
+                // is stripped with the types.
+                assert_eq!(referenced, vec!["config/base-path.ts"]);
+                Ok(())
+            },
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_extra_resolve_plugins() -> Result<()> {
+        crate::register();
+        let tt = TurboTasks::new(MemoryBackend::default());
+        tt.run_once(async move {
+            let root = VirtualFileSystemVc::new().as_file_system().root();
+            // This plugin only reports unsupported modules, so it doesn't change how
+            // anything resolves.
+            let extra_plugin: ResolvePluginVc =
+                UnsupportedModulesResolvePluginVc::new_with_context_type(root, "extra").into();
+            let extra_plugin = extra_plugin.resolve().await?;
+            for kind in ServerContextKind::ALL {
+                let resolve_options_context = get_server_resolve_options_context(
+                    root,
+                    Value::new(server_context_type(kind, root)),
+                    NextMode::Development,
+                    NextConfig::default().cell(),
+                    execution_context(root),
+                    ResolvePluginsVc::cell(vec![extra_plugin]),
+                )
+                .await?;
+                // The extra plugins come after the built-in ones.
+                let plugins = &resolve_options_context.plugins;
+                assert_eq!(plugins.last(), Some(&extra_plugin), "{}", kind.as_str());
+                assert_eq!(
+                    plugins
+                        .iter()
+                        .filter(|plugin| **plugin == extra_plugin)
+                        .count(),
+                    1,
+                    "{}",
+                    kind.as_str()
+                );
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    /// Returns the paths `request` resolves to from `root` in a server context
+    /// of `kind`.
+    async fn resolved_paths(
+        root: FileSystemPathVc,
+        kind: ServerContextKind,
+        request: &str,
+    ) -> Result<Vec<String>> {
+        let resolve_options_context = get_server_resolve_options_context(
+            root,
+            Value::new(server_context_type(kind, root)),
+            NextMode::Development,
+            NextConfig::default().cell(),
+            execution_context(root),
+            ResolvePluginsVc::empty(),
+        );
+        let result = resolve(
+            root,
+            RequestVc::parse(Value::new(Pattern::Constant(request.to_string()))),
+            resolve_options(root, resolve_options_context),
+        );
+        let mut paths = Vec::new();
+        for asset in result.primary_assets().await?.iter() {
+            paths.push(asset.ident().path().await?.path.clone());
+        }
+        Ok(paths)
+    }
+
+    #[tokio::test]
+    async fn test_middleware_resolves_browser_field() -> Result<()> {
+        run_in_project(
+            &[
+                (
+                    "node_modules/shim-dep/package.json",
+                    r#"{ "name": "shim-dep", "main": "./node.js", "browser": "./browser.js" }"#,
+                ),
+                ("node_modules/shim-dep/node.js", "module.exports = 'node'\n"),
+                (
+                    "node_modules/shim-dep/browser.js",
+                    "module.exports = 'browser'\n",
+                ),
+            ],
+            |root| async move {
+                assert_eq!(
+                    resolved_paths(root, ServerContextKind::Middleware, "shim-dep").await?,
+                    vec!["node_modules/shim-dep/browser.js"]
+                );
+                assert_eq!(
+                    resolved_paths(root, ServerContextKind::AppSSR, "shim-dep").await?,
+                    vec!["node_modules/shim-dep/node.js"]
+                );
+                Ok(())
+            },
+        )
+        .await
+    }
+
+    /// Resolves `./addon.node` from the root of a project with that addon, in
+    /// a server context of `kind`, and returns the resolved paths and the
+    /// titles of the emitted issues.
+    async fn resolve_native_addon(
+        kind: ServerContextKind,
+        next_config: NextConfig,
+    ) -> Result<(Vec<String>, Vec<String>)> {
+        let project = write_project(&[("addon.node", "")])?;
+        let root = project_root(project.path());
+        let resolve_options_context = get_server_resolve_options_context(
+            root,
+            Value::new(server_context_type(kind, root)),
+            NextMode::Development,
+            next_config.cell(),
+            execution_context(root),
+            ResolvePluginsVc::empty(),
+        );
+        let result = resolve(
+            root,
+            RequestVc::parse(Value::new(Pattern::Constant("./addon.node".to_string()))),
+            resolve_options(root, resolve_options_context),
+        );
+
+        let mut paths = Vec::new();
+        for asset in result.primary_assets().await?.iter() {
+            paths.push(asset.ident().path().await?.path.clone());
+        }
+        let issues = IssueVc::peek_issues_with_path(result)
+            .await?
+            .strongly_consistent()
+            .await?;
+        let mut titles = Vec::new();
+        for issue in issues.iter() {
+            titles.push(issue.title().await?.clone_value());
+        }
+        Ok((paths, titles))
+    }
+
+    #[tokio::test]
+    async fn test_native_module_imports() -> Result<()> {
+        crate::register();
+        let tt = TurboTasks::new(MemoryBackend::default());
+        tt.run_once(async move {
+            let (paths, titles) =
+                resolve_native_addon(ServerContextKind::Pages, NextConfig::default()).await?;
+            assert_eq!(paths, vec!["addon.node"]);
+            assert!(titles.is_empty());
+
+            let mut next_config = NextConfig::default();
+            next_config.experimental.native_modules = Some(false);
+            let (paths, titles) =
+                resolve_native_addon(ServerContextKind::Pages, next_config).await?;
+            assert!(paths.is_empty());
+            assert_eq!(titles, vec!["Native module addon.node can't be used"]);
+            Ok(())
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_ssr_native_module_imports() -> Result<()> {
+        crate::register();
+        let tt = TurboTasks::new(MemoryBackend::default());
+        tt.run_once(async move {
+            let (paths, titles) =
+                resolve_native_addon(ServerContextKind::AppSSR, NextConfig::default()).await?;
+            assert_eq!(paths, vec!["addon.node"]);
+            assert!(titles.is_empty());
+
+            let mut next_config = NextConfig::default();
+            next_config.experimental.ssr_native_modules = Some(false);
+            let (paths, titles) =
+                resolve_native_addon(ServerContextKind::AppSSR, next_config.clone()).await?;
+            assert!(paths.is_empty());
+            assert_eq!(titles, vec!["Native module addon.node can't be used"]);
+            // Only SSR is affected, Server Components still load the addon.
+            let (paths, titles) =
+                resolve_native_addon(ServerContextKind::AppRSC, next_config).await?;
+            assert_eq!(paths, vec!["addon.node"]);
+            assert!(titles.is_empty());
+            Ok(())
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_module_issue_context_type() -> Result<()> {
+        run_in_project(
+            &[
+                (
+                    "node_modules/@vercel/og/package.json",
+                    r#"{ "name": "@vercel/og" }"#,
+                ),
+                ("node_modules/@vercel/og/index.js", "module.exports = {}\n"),
+            ],
+            |root| async move {
+                let resolve_options_context = get_server_resolve_options_context(
+                    root,
+                    Value::new(server_context_type(ServerContextKind::AppRSC, root)),
+                    NextMode::Development,
+                    NextConfig::default().cell(),
+                    execution_context(root),
+                    ResolvePluginsVc::empty(),
+                );
+                let result = resolve(
+                    root,
+                    RequestVc::parse(Value::new(Pattern::Constant("@vercel/og".to_string()))),
+                    resolve_options(root, resolve_options_context),
+                );
+
+                let issues = IssueVc::peek_issues_with_path(result)
+                    .await?
+                    .strongly_consistent()
+                    .await?;
+                let mut titles = Vec::new();
+                for issue in issues.iter() {
+                    titles.push(issue.title().await?.clone_value());
+                }
+                assert_eq!(titles, vec!["Unsupported module [AppRSC]"]);
+                Ok(())
+            },
+        )
+        .await
+    }
+
+    /// Returns the paths `react` resolves to from the app directory in an
+    /// AppRSC context.
+    async fn app_rsc_react_paths(
+        root: FileSystemPathVc,
+        next_config: NextConfig,
+    ) -> Result<Vec<String>> {
+        let app_dir = root.join("app");
+        let resolve_options_context = get_server_resolve_options_context(
+            root,
+            Value::new(server_context_type(ServerContextKind::AppRSC, root)),
+            NextMode::Development,
+            next_config.cell(),
+            execution_context(root),
+            ResolvePluginsVc::empty(),
+        );
+        let result = resolve(
+            app_dir,
+            RequestVc::parse(Value::new(Pattern::Constant("react".to_string()))),
+            resolve_options(app_dir, resolve_options_context),
+        );
+        let mut paths = Vec::new();
+        for asset in result.primary_assets().await?.iter() {
+            paths.push(asset.ident().path().await?.path.clone());
+        }
+        Ok(paths)
+    }
+
+    #[tokio::test]
+    async fn test_bundled_react_resolution() -> Result<()> {
+        run_in_project(
+            &[
+                ("app/page.js", "export default function Page() {}\n"),
+                ("node_modules/react/package.json", r#"{ "name": "react" }"#),
+                ("node_modules/react/index.js", "module.exports = {}\n"),
+                ("node_modules/next/package.json", r#"{ "name": "next" }"#),
+                (
+                    "node_modules/next/dist/compiled/react/package.json",
+                    r#"{ "name": "react" }"#,
+                ),
+                (
+                    "node_modules/next/dist/compiled/react/index.js",
+                    "module.exports = {}\n",
+                ),
+            ],
+            |root| async move {
+                assert_eq!(
+                    app_rsc_react_paths(root, NextConfig::default()).await?,
+                    vec!["node_modules/next/dist/compiled/react/index.js"]
+                );
+
+                let next_config = NextConfig {
+                    experimental: serde_json::from_str(r#"{ "bundledReact": false }"#)?,
+                    ..Default::default()
+                };
+                assert_eq!(
+                    app_rsc_react_paths(root, next_config).await?,
+                    vec!["node_modules/react/index.js"]
+                );
+                Ok(())
+            },
+        )
+        .await
+    }
+
+    /// Returns an asset context with the module and resolve options of a
+    /// server context of `kind` in the project at `root`.
+    async fn server_asset_context(
+        root: FileSystemPathVc,
+        kind: ServerContextKind,
+        next_config: NextConfig,
+    ) -> Result<ModuleAssetContextVc> {
+        let ty = Value::new(server_context_type(kind, root));
+        let next_config = next_config.cell();
+        let module_options = get_server_module_options(
+            root,
+            execution_context(root),
+            ty,
+            NextMode::Development,
+            next_config,
+        )
+        .await?;
+        Ok(ModuleAssetContextVc::new(
+            TransitionsByNameVc::cell(HashMap::new()),
+            CompileTimeInfo::builder(node_build_environment()).cell(),
+            module_options.context,
+            get_server_resolve_options_context(
+                root,
+                ty,
+                NextMode::Development,
+                next_config,
+                execution_context(root),
+                ResolvePluginsVc::empty(),
+            ),
+        ))
+    }
+
+    #[derive(Default)]
+    struct JsxElements(usize);
+
+    impl Visit for JsxElements {
+        fn visit_jsx_element(&mut self, element: &JSXElement) {
+            self.0 += 1;
+            element.visit_children_with(self);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_app_route_compiles_jsx() -> Result<()> {
+        run_in_project(
+            &[(
+                "app/api/og/route.js",
+                "import { ImageResponse } from 'next/og'\n\nexport function GET() {\n  return new \
+                 ImageResponse(<div>Hello</div>)\n}\n",
+            )],
+            |root| async move {
+                let context =
+                    server_asset_context(root, ServerContextKind::AppRoute, NextConfig::default())
+                        .await?;
+                let module = context.process(
+                    SourceAssetVc::new(root.join("app/api/og/route.js")).into(),
+                    Value::new(ReferenceType::Entry(EntryReferenceSubType::AppRoute)),
+                );
+                let module = EcmascriptModuleAssetVc::resolve_from(module)
+                    .await?
+                    .unwrap();
+                let ParseResult::Ok { program, .. } = &*module.parse().await? else {
+                    anyhow::bail!("route.js can't be parsed");
+                };
+                let mut elements = JsxElements::default();
+                program.visit_with(&mut elements);
+                // The JSX is compiled to calls of the JSX runtime.
+                assert_eq!(elements.0, 0);
+                Ok(())
+            },
+        )
+        .await
+    }
+
+    #[derive(Default)]
+    struct TypeAnnotations(usize);
+
+    impl Visit for TypeAnnotations {
+        fn visit_ts_type_ann(&mut self, _annotation: &TsTypeAnn) {
+            self.0 += 1;
+        }
+    }
+
+    /// Returns the number of type annotations left in the `index.ts` of each
+    /// of `packages` after parsing it in a pages server context.
+    async fn foreign_type_annotations(
+        root: FileSystemPathVc,
+        next_config: NextConfig,
+        packages: &[&str],
+    ) -> Result<Vec<usize>> {
+        let context = server_asset_context(root, ServerContextKind::Pages, next_config).await?;
+        let mut counts = Vec::new();
+        for package in packages {
+            let module = context.process(
+                SourceAssetVc::new(root.join(&format!("node_modules/{package}/index.ts"))).into(),
+                Value::new(ReferenceType::Undefined),
+            );
+            let module = EcmascriptModuleAssetVc::resolve_from(module)
+                .await?
+                .unwrap();
+            let ParseResult::Ok { program, .. } = &*module.parse().await? else {
+                anyhow::bail!("{package}/index.ts can't be parsed");
+            };
+            let mut annotations = TypeAnnotations::default();
+            program.visit_with(&mut annotations);
+            counts.push(annotations.0);
+        }
+        Ok(counts)
+    }
+
+    #[tokio::test]
+    async fn test_foreign_code_typescript_transform() -> Result<()> {
+        let index = "export const answer: number = 42\n";
+        run_in_project(
+            &[
+                ("node_modules/ts-dep/index.ts", index),
+                ("node_modules/other-dep/index.ts", index),
+            ],
+            |root| async move {
+            let packages = ["ts-dep", "other-dep"];
+            assert_eq!(
+                foreign_type_annotations(root, NextConfig::default(), &packages).await?,
+                vec![1, 1]
+            );
+
+            let next_config = NextConfig {
+                experimental: serde_json::from_str(
+                    r#"{ "turbo": { "foreignCodeTransforms": { "ts-dep": { "typescript": true } } } }"#,
+                )?,
+                ..Default::default()
+            };
+            assert_eq!(
+                foreign_type_annotations(root, next_config, &packages).await?,
+                vec![0, 1]
+            );
+            Ok(())
+        })
+        .await
+    }
+
+    /// Processes a module importing `./post.mdx` in a pages server context and
+    /// returns the titles of the issues emitted while resolving the import.
+    async fn markdown_import_issues(next_config: NextConfig) -> Result<Vec<String>> {
+        let project = write_project(&[
+            ("index.js", "import Post from './post.mdx'\n"),
+            ("post.mdx", "# Post\n"),
+        ])?;
+        let root = project_root(project.path());
+        let context = server_asset_context(root, ServerContextKind::Pages, next_config).await?;
+        let module = context.process(
+            SourceAssetVc::new(root.join("index.js")).into(),
+            Value::new(ReferenceType::Undefined),
+        );
+
+        let mut titles = Vec::new();
+        for reference in module.references().await?.iter() {
+            let assets = reference.resolve_reference().primary_assets();
+            let issues = IssueVc::peek_issues_with_path(assets)
+                .await?
+                .strongly_consistent()
+                .await?;
+            for issue in issues.iter() {
+                titles.push(issue.title().await?.clone_value());
+            }
+        }
+        Ok(titles)
+    }
+
+    #[tokio::test]
+    async fn test_markdown_import_without_mdx() -> Result<()> {
+        crate::register();
+        let tt = TurboTasks::new(MemoryBackend::default());
+        tt.run_once(async move {
+            assert_eq!(
+                markdown_import_issues(NextConfig::default()).await?,
+                vec!["Markdown module post.mdx can't be compiled [Pages]"]
+            );
+
+            let next_config = NextConfig {
+                experimental: serde_json::from_str(r#"{ "mdxRs": true }"#)?,
+                ..Default::default()
+            };
+            assert!(markdown_import_issues(next_config).await?.is_empty());
+            Ok(())
+        })
+        .await
+    }
+
+    /// Records the `context` and `ty` of every [super::server_context_span].
+    #[derive(Clone, Default)]
+    struct ContextSpans(Arc<Mutex<Vec<(String, String)>>>);
+
+    #[derive(Default)]
+    struct ContextSpanFields {
+        context: String,
+        ty: String,
+    }
+
+    impl FieldVisit for ContextSpanFields {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            match field.name() {
+                "context" => self.context = value.to_string(),
+                "ty" => self.ty = value.to_string(),
+                _ => {}
+            }
+        }
+
+        fn record_debug(&mut self, _field: &Field, _value: &dyn Debug) {}
+    }
+
+    impl<S> Layer<S> for ContextSpans
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        fn on_new_span(&self, attrs: &Attributes<'_>, _id: &Id, _ctx: Context<'_, S>) {
+            if attrs.metadata().name() != "server context" {
+                return;
+            }
+            let mut fields = ContextSpanFields::default();
+            attrs.record(&mut fields);
+            self.0.lock().unwrap().push((fields.context, fields.ty));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_server_context_spans() -> Result<()> {
+        crate::register();
+        let spans = ContextSpans::default();
+        let _subscriber =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(spans.clone()));
+        let tt = TurboTasks::new(MemoryBackend::default());
+        tt.run_once(async move {
+            let root = VirtualFileSystemVc::new().as_file_system().root();
+            get_server_resolve_options_context(
+                root,
+                Value::new(server_context_type(ServerContextKind::AppRSC, root)),
+                NextMode::Development,
+                NextConfig::default().cell(),
+                execution_context(root),
+                ResolvePluginsVc::empty(),
+            )
+            .await?;
+            app_transforms(root, ServerContextKind::AppRoute, NextConfig::default()).await?;
+            Ok(())
+        })
+        .await?;
+
+        let spans = spans.0.lock().unwrap();
+        assert!(spans.contains(&("resolve options".to_string(), "AppRSC".to_string())));
+        assert!(spans.contains(&("module options".to_string(), "AppRoute".to_string())));
+        Ok(())
+    }
+
+    /// Records when the spans of [super::server_lookup_span] are entered and
+    /// closed, in order.
+    #[derive(Clone, Default)]
+    struct LookupSpanEvents(Arc<Mutex<Vec<(&'static str, u64)>>>);
+
+    impl LookupSpanEvents {
+        fn record<S>(&self, event: &'static str, id: &Id, ctx: Context<'_, S>)
+        where
+            S: Subscriber + for<'a> LookupSpan<'a>,
+        {
+            if ctx
+                .span(id)
+                .map_or(false, |span| span.name() == "server context lookup")
+            {
+                self.0.lock().unwrap().push((event, id.into_u64()));
+            }
+        }
+    }
+
+    impl<S> Layer<S> for LookupSpanEvents
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+            self.record("enter", id, ctx);
+        }
+
+        fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+            self.record("close", &id, ctx);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_server_lookups_overlap() -> Result<()> {
+        let events = LookupSpanEvents::default();
+        let _subscriber =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(events.clone()));
+        run_in_project(&[], |root| async move {
+            app_transforms(root, ServerContextKind::Pages, NextConfig::default()).await?;
+            Ok(())
+        })
+        .await?;
+
+        // Every lookup is started before the first one finishes.
+        let events = events.0.lock().unwrap();
+        let first_close = events
+            .iter()
+            .position(|(event, _)| *event == "close")
+            .unwrap();
+        let started = events[..first_close]
+            .iter()
+            .map(|(_, id)| *id)
+            .collect::<HashSet<_>>();
+        assert_eq!(started.len(), 4);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_dedup_transforms() -> Result<()> {
+        crate::register();
+        let tt = TurboTasks::new(MemoryBackend::default());
+        tt.run_once(async move {
+            let root = VirtualFileSystemVc::new().as_file_system().root();
+            let next_config = NextConfig::default().cell();
+            let plugin = || {
+                Some(TransformPluginVc::cell(Box::new(
+                    ServerDirectiveTransformer::new(&StringVc::cell("TODO".to_string())),
+                )))
+            };
+            let mut builder = ServerModuleOptionsContextBuilder::new(
+                ServerContextKind::Pages,
+                execution_context(root),
+            )
+            .source_transforms(SourceTransformPlugins {
+                styled_jsx: plugin(),
+                relay: plugin(),
+                emotion: plugin(),
+                ..Default::default()
+            })
+            .time_transforms(true);
+            // emotion is both a base and a shared transform here.
+            builder.deltas.base_transforms =
+                &[SourceTransform::StyledJsx, SourceTransform::Emotion];
+            assert_eq!(
+                builder.app_transforms(),
+                vec![
+                    SourceTransform::StyledJsx,
+                    SourceTransform::Emotion,
+                    SourceTransform::Relay
+                ]
+            );
+
+            let module_options = builder
+                .build(
+                    ModuleOptionsContext::default(),
+                    vec![],
+                    next_config,
+                    foreign_code_context_condition(next_config).await?,
+                )
+                .await?;
+            assert_eq!(
+                module_options.transforms,
+                vec!["styled-jsx", "emotion", "relay"]
+            );
+            let transforms = module_options
+                .context
+                .await?
+                .custom_ecma_transform_plugins
+                .unwrap()
+                .await?;
+            assert_eq!(transforms.source_transforms.len(), 3);
+            Ok(())
+        })
+        .await
+    }
+}
@@ -3,6 +3,13 @@ pub(crate) mod resolve;
 pub(crate) mod transforms;
 
 pub use context::{
-    get_server_compile_time_info, get_server_module_options_context,
-    get_server_resolve_options_context, ServerContextType,
+    describe_server_module_options, get_build_asset_context, get_build_module_options_context,
+    get_build_resolve_options_context, get_server_compile_time_info,
+    get_server_module_options_context, get_server_resolve_options_context,
+    get_server_runtime_entries, ModuleOptionsSummary, ModuleOptionsSummaryVc, ResolvePlugins,
+    ResolvePluginsVc, ServerContextKind, ServerContextType,
+};
+pub use resolve::{
+    report_externals_summary, ContextExternals, ExternalsDecision, ExternalsDecisionCollectible,
+    ExternalsDecisionCollectibleVc, ExternalsDecisionVc,
 };
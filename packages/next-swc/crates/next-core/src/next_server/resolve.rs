@@ -1,14 +1,20 @@
+use std::collections::{BTreeMap, BTreeSet};
+
 use anyhow::Result;
 use once_cell::sync::Lazy;
 use regex::Regex;
-use turbo_tasks::primitives::{BoolVc, StringsVc};
+use turbo_tasks::{
+    primitives::{BoolVc, StringVc, StringsVc},
+    CollectiblesSource, CompletionVc,
+};
 use turbopack_binding::{
     turbo::tasks_fs::{glob::GlobVc, FileJsonContent, FileSystemPathVc},
     turbopack::core::{
-        asset::Asset,
+        asset::{Asset, AssetsVc},
+        issue::{Issue, IssueSeverity, IssueSeverityVc, IssueVc},
         resolve::{
             find_context_file,
-            node::node_cjs_resolve_options,
+            node::{node_cjs_resolve_options, node_esm_resolve_options},
             package_json,
             parse::{Request, RequestVc},
             plugin::{ResolvePlugin, ResolvePluginConditionVc, ResolvePluginVc},
@@ -18,6 +24,8 @@ use turbopack_binding::{
     },
 };
 
+use crate::next_config::{NextConfigVc, OutputType};
+
 /// The predicated based on which the [ExternalCjsModulesResolvePlugin] decides
 /// whether to mark a module as external.
 #[turbo_tasks::value(into = "shared")]
@@ -31,18 +39,248 @@ pub enum ExternalPredicate {
 /// Mark modules as external, so they're resolved at runtime instead of bundled.
 ///
 /// Modules matching the predicate are marked as external as long as it's
-/// possible to resolve them at runtime.
+/// possible to resolve them at runtime, and not in one of the
+/// `force_bundle_packages`. ESM modules are only marked as external when
+/// `esm_externals` is enabled, and if node.js resolves their `import` to the
+/// same file.
 #[turbo_tasks::value]
 pub(crate) struct ExternalCjsModulesResolvePlugin {
     root: FileSystemPathVc,
     predicate: ExternalPredicateVc,
+    force_bundle_packages: StringsVc,
+    esm_externals: bool,
+    /// The name of the server context kind, which the emitted
+    /// [ExternalsDecision]s are for.
+    context_type: String,
 }
 
 #[turbo_tasks::value_impl]
 impl ExternalCjsModulesResolvePluginVc {
     #[turbo_tasks::function]
-    pub fn new(root: FileSystemPathVc, predicate: ExternalPredicateVc) -> Self {
-        ExternalCjsModulesResolvePlugin { root, predicate }.cell()
+    pub fn new(
+        root: FileSystemPathVc,
+        predicate: ExternalPredicateVc,
+        force_bundle_packages: StringsVc,
+        esm_externals: bool,
+        context_type: &str,
+    ) -> Self {
+        ExternalCjsModulesResolvePlugin {
+            root,
+            predicate,
+            force_bundle_packages,
+            esm_externals,
+            context_type: context_type.to_string(),
+        }
+        .cell()
+    }
+}
+
+/// Whether an [ExternalCjsModulesResolvePlugin] externalized or bundled a
+/// module of `package`. The plugin emits these as collectibles, so they are
+/// kept with the resolve tasks and collected by [report_externals_summary].
+#[turbo_tasks::value(shared)]
+#[derive(Debug)]
+pub struct ExternalsDecision {
+    pub context_type: String,
+    pub package: String,
+    pub external: bool,
+}
+
+#[turbo_tasks::value_trait]
+pub trait ExternalsDecisionCollectible {
+    fn decision(&self) -> ExternalsDecisionVc;
+}
+
+#[turbo_tasks::value_impl]
+impl ExternalsDecisionCollectible for ExternalsDecision {
+    #[turbo_tasks::function]
+    fn decision(self_vc: ExternalsDecisionVc) -> ExternalsDecisionVc {
+        self_vc
+    }
+}
+
+/// The packages a server context externalized and bundled.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ContextExternals {
+    pub externalized: BTreeSet<String>,
+    pub bundled: BTreeSet<String>,
+}
+
+/// Aggregates `decisions` by server context. A package counts as externalized
+/// if any of its modules was.
+fn summarize_externals<'a>(
+    decisions: impl IntoIterator<Item = &'a ExternalsDecision>,
+) -> BTreeMap<String, ContextExternals> {
+    let mut contexts = BTreeMap::<String, ContextExternals>::new();
+    for decision in decisions {
+        let externals = contexts.entry(decision.context_type.clone()).or_default();
+        if decision.external {
+            externals.bundled.remove(&decision.package);
+            externals.externalized.insert(decision.package.clone());
+        } else if !externals.externalized.contains(&decision.package) {
+            externals.bundled.insert(decision.package.clone());
+        }
+    }
+    contexts
+}
+
+/// Describes the packages of each server context in `entries`, listing up to
+/// `sample` of each.
+fn externals_summary_description(
+    entries: &BTreeMap<String, ContextExternals>,
+    sample: usize,
+) -> String {
+    let describe = |packages: &BTreeSet<String>| {
+        let mut listed = packages
+            .iter()
+            .take(sample)
+            .map(|package| package.as_str())
+            .collect::<Vec<_>>();
+        if packages.len() > sample {
+            listed.push("...");
+        }
+        if listed.is_empty() {
+            packages.len().to_string()
+        } else {
+            format!("{} ({})", packages.len(), listed.join(", "))
+        }
+    };
+    entries
+        .iter()
+        .map(|(context_type, externals)| {
+            format!(
+                "- {context_type}: {} externalized, {} bundled",
+                describe(&externals.externalized),
+                describe(&externals.bundled)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Emits an info issue summarizing the [ExternalsDecision]s made while
+/// resolving the modules of the `assets` graphs, e.g. the server chunks of a
+/// build.
+#[turbo_tasks::function]
+pub async fn report_externals_summary(
+    project_path: FileSystemPathVc,
+    assets: Vec<AssetsVc>,
+) -> Result<CompletionVc> {
+    let mut decisions = Vec::new();
+    for assets in assets {
+        let collectibles = assets
+            .peek_collectibles::<ExternalsDecisionCollectibleVc>()
+            .strongly_consistent()
+            .await?;
+        for collectible in collectibles.iter() {
+            decisions.push(collectible.decision().await?);
+        }
+    }
+    let entries = summarize_externals(decisions.iter().map(|decision| &**decision));
+    if !entries.is_empty() {
+        ExternalsSummaryIssue {
+            path: project_path,
+            description: externals_summary_description(&entries, 5),
+        }
+        .cell()
+        .as_issue()
+        .emit();
+    }
+    Ok(CompletionVc::new())
+}
+
+#[turbo_tasks::value(shared)]
+struct ExternalsSummaryIssue {
+    path: FileSystemPathVc,
+    description: String,
+}
+
+#[turbo_tasks::value_impl]
+impl Issue for ExternalsSummaryIssue {
+    #[turbo_tasks::function]
+    fn severity(&self) -> IssueSeverityVc {
+        IssueSeverity::Info.into()
+    }
+
+    #[turbo_tasks::function]
+    fn category(&self) -> StringVc {
+        StringVc::cell("resolve".to_string())
+    }
+
+    #[turbo_tasks::function]
+    fn context(&self) -> FileSystemPathVc {
+        self.path
+    }
+
+    #[turbo_tasks::function]
+    fn title(&self) -> StringVc {
+        StringVc::cell("Externalized and bundled packages by server context".to_string())
+    }
+
+    #[turbo_tasks::function]
+    fn description(&self) -> StringVc {
+        StringVc::cell(self.description.clone())
+    }
+}
+
+/// Returns whether ESM packages can be externalized. Besides
+/// `experimental.esmExternals`, this is the case for `output: "standalone"`,
+/// where the server runs next to a traced copy of its `node_modules`, so
+/// packages don't need to be bundled to be available at runtime.
+pub(crate) fn should_externalize_esm(esm_externals: bool, output: Option<&OutputType>) -> bool {
+    esm_externals || matches!(output, Some(OutputType::Standalone))
+}
+
+/// Returns whether server contexts externalize ESM packages with
+/// `next_config`. See [should_externalize_esm].
+#[turbo_tasks::function]
+pub(crate) async fn externalizes_esm(next_config: NextConfigVc) -> Result<BoolVc> {
+    Ok(BoolVc::cell(should_externalize_esm(
+        *next_config.esm_externals().await?,
+        next_config.await?.output.as_ref(),
+    )))
+}
+
+/// The module type node.js loads a file as, which decides whether an external
+/// is `import`ed or `require`d.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ExternalModuleType {
+    CommonJs,
+    EcmaScript,
+}
+
+/// Returns the module type node.js loads a file with the given `extension` in
+/// a package with the given package.json `package_type` as, or `None` if it
+/// can't load it at all.
+///
+/// For a dual package, this is the module type of the importer: `import`s
+/// resolve to the entry of the `import` export condition, and `require`s to
+/// the entry of the `require` condition.
+fn external_module_type(
+    extension: Option<&str>,
+    package_type: Option<&str>,
+) -> Option<ExternalModuleType> {
+    match extension {
+        // node.js only supports these file extensions
+        Some("cjs" | "node" | "json") => Some(ExternalModuleType::CommonJs),
+        Some("js") if package_type == Some("module") => Some(ExternalModuleType::EcmaScript),
+        Some("js") => Some(ExternalModuleType::CommonJs),
+        Some("mjs") => Some(ExternalModuleType::EcmaScript),
+        _ => None,
+    }
+}
+
+/// Returns true if a module with the given file `extension` in a package with
+/// the given package.json `package_type` can be loaded by node.js at runtime.
+fn is_externalizable(
+    extension: Option<&str>,
+    package_type: Option<&str>,
+    esm_externals: bool,
+) -> bool {
+    match external_module_type(extension, package_type) {
+        Some(ExternalModuleType::CommonJs) => true,
+        Some(ExternalModuleType::EcmaScript) => esm_externals,
+        None => false,
     }
 }
 
@@ -51,8 +289,14 @@ async fn is_node_resolveable(
     context: FileSystemPathVc,
     request: RequestVc,
     expected: FileSystemPathVc,
+    esm: bool,
 ) -> Result<BoolVc> {
-    let node_resolve_result = resolve(context, request, node_cjs_resolve_options(context.root()));
+    let resolve_options = if esm {
+        node_esm_resolve_options(context.root())
+    } else {
+        node_cjs_resolve_options(context.root())
+    };
+    let node_resolve_result = resolve(context, request, resolve_options);
     let primary_node_assets = node_resolve_result.primary_assets().await?;
     let Some(node_asset) = primary_node_assets.first() else {
         // can't resolve request with node.js options
@@ -95,32 +339,49 @@ impl ResolvePlugin for ExternalCjsModulesResolvePlugin {
             return Ok(ResolveResultOptionVc::none());
         }
 
+        let result = self.resolve_external(fs_path, context, request).await?;
+        if let Some(package) = installed_package(&fs_path.await?.path) {
+            turbo_tasks::emit(
+                ExternalsDecision {
+                    context_type: self.context_type.clone(),
+                    package,
+                    external: result.await?.is_some(),
+                }
+                .cell()
+                .as_externals_decision_collectible(),
+            );
+        }
+        Ok(result)
+    }
+}
+
+impl ExternalCjsModulesResolvePlugin {
+    /// Returns the external reference for the module at `fs_path`, or none if
+    /// it's bundled.
+    async fn resolve_external(
+        &self,
+        fs_path: FileSystemPathVc,
+        context: FileSystemPathVc,
+        request: RequestVc,
+    ) -> Result<ResolveResultOptionVc> {
         let raw_fs_path = &*fs_path.await?;
 
         let predicate = self.predicate.await?;
-        match &*predicate {
-            ExternalPredicate::AllExcept(exceptions) => {
-                let exception_glob = packages_glob(*exceptions).await?;
-
-                if exception_glob.execute(&raw_fs_path.path) {
-                    return Ok(ResolveResultOptionVc::none());
-                }
-            }
-            ExternalPredicate::Only(externals) => {
-                let external_glob = packages_glob(*externals).await?;
-
-                if !external_glob.execute(&raw_fs_path.path) {
-                    return Ok(ResolveResultOptionVc::none());
-                }
-            }
+        let (all_except, packages) = match &*predicate {
+            ExternalPredicate::AllExcept(exceptions) => (true, exceptions.await?),
+            ExternalPredicate::Only(externals) => (false, externals.await?),
+        };
+        if !is_marked_external(
+            all_except,
+            &packages,
+            &self.force_bundle_packages.await?,
+            &raw_fs_path.path,
+        ) {
+            return Ok(ResolveResultOptionVc::none());
         }
 
-        // node.js only supports these file extensions
-        // mjs is an esm module and we can't bundle that yet
-        if !matches!(
-            raw_fs_path.extension(),
-            Some("cjs" | "js" | "node" | "json")
-        ) {
+        // esm modules can only be externalized when enabled, they're bundled otherwise
+        if !is_externalizable(raw_fs_path.extension(), None, self.esm_externals) {
             return Ok(ResolveResultOptionVc::none());
         }
 
@@ -135,14 +396,21 @@ impl ResolvePlugin for ExternalCjsModulesResolvePlugin {
             return Ok(ResolveResultOptionVc::none());
         };
 
-        // always bundle esm modules
-        if let Some("module") = package["type"].as_str() {
+        let module_type = external_module_type(raw_fs_path.extension(), package["type"].as_str());
+        if !is_externalizable(
+            raw_fs_path.extension(),
+            package["type"].as_str(),
+            self.esm_externals,
+        ) {
             return Ok(ResolveResultOptionVc::none());
         }
+        // an esm entry is only reached by an `import`, which node.js resolves with the
+        // `import` condition, and a cjs entry by a `require`
+        let esm = module_type == Some(ExternalModuleType::EcmaScript);
 
         // check if we can resolve the package from the project dir with node.js resolve
         // options (might be hidden by pnpm)
-        if *is_node_resolveable(self.root.root(), request, fs_path).await? {
+        if *is_node_resolveable(self.root.root(), request, fs_path, esm).await? {
             // mark as external
             return Ok(ResolveResultOptionVc::some(
                 ResolveResult::primary(PrimaryResolveResult::OriginalReferenceExternal).cell(),
@@ -159,7 +427,7 @@ impl ResolvePlugin for ExternalCjsModulesResolvePlugin {
                 // we could load it directly as external, but we want to make sure node.js would
                 // resolve it the same way e. g. that we didn't follow any special resolve
                 // options, to come here like the `module` field in package.json
-                if *is_node_resolveable(context, request, fs_path).await? {
+                if *is_node_resolveable(context, request, fs_path, esm).await? {
                     // mark as external
                     return Ok(ResolveResultOptionVc::some(
                         ResolveResult::primary(
@@ -176,10 +444,658 @@ impl ResolvePlugin for ExternalCjsModulesResolvePlugin {
     }
 }
 
+/// Returns whether `package` matches `pattern`, which is either a package name
+/// or `@scope/*` to match all packages of a scope.
+fn matches_package_pattern(pattern: &str, package: &str) -> bool {
+    match pattern.strip_suffix("/*") {
+        Some(scope) if scope.starts_with('@') => package
+            .strip_prefix(scope)
+            .and_then(|name| name.strip_prefix('/'))
+            .map_or(false, |name| !name.is_empty()),
+        _ => pattern == package,
+    }
+}
+
+/// Returns the pairs of entries in `transpile_packages` and
+/// `external_packages` which match the same packages. Such packages would be
+/// bundled in some server contexts and externalized in others.
+fn conflicting_packages(
+    transpile_packages: &[String],
+    external_packages: &[String],
+) -> Vec<(String, String)> {
+    transpile_packages
+        .iter()
+        .flat_map(|transpile| {
+            external_packages
+                .iter()
+                .filter(|external| {
+                    matches_package_pattern(transpile, external)
+                        || matches_package_pattern(external, transpile)
+                })
+                .map(|external| (transpile.clone(), external.clone()))
+        })
+        .collect()
+}
+
+/// Emits an issue for packages which are listed in both `transpilePackages`
+/// and `experimental.serverComponentsExternalPackages`.
 #[turbo_tasks::function]
-async fn packages_glob(packages: StringsVc) -> Result<GlobVc> {
-    Ok(GlobVc::new(&format!(
-        "**/node_modules/{{{}}}/**",
-        packages.await?.join(",")
-    )))
+pub async fn validate_external_packages(
+    project_path: FileSystemPathVc,
+    next_config: NextConfigVc,
+) -> Result<CompletionVc> {
+    let conflicts = conflicting_packages(
+        &next_config.transpile_packages().await?,
+        &next_config.server_component_externals().await?,
+    );
+    if !conflicts.is_empty() {
+        ConflictingPackageListsIssue {
+            path: project_path,
+            conflicts,
+        }
+        .cell()
+        .as_issue()
+        .emit();
+    }
+    Ok(CompletionVc::new())
+}
+
+#[turbo_tasks::value(shared)]
+struct ConflictingPackageListsIssue {
+    path: FileSystemPathVc,
+    /// Pairs of matching `transpilePackages` and
+    /// `serverComponentsExternalPackages` entries.
+    conflicts: Vec<(String, String)>,
+}
+
+#[turbo_tasks::value_impl]
+impl Issue for ConflictingPackageListsIssue {
+    #[turbo_tasks::function]
+    fn severity(&self) -> IssueSeverityVc {
+        IssueSeverity::Error.into()
+    }
+
+    #[turbo_tasks::function]
+    fn category(&self) -> StringVc {
+        StringVc::cell("config".to_string())
+    }
+
+    #[turbo_tasks::function]
+    fn context(&self) -> FileSystemPathVc {
+        self.path
+    }
+
+    #[turbo_tasks::function]
+    fn title(&self) -> StringVc {
+        StringVc::cell(
+            "Packages are listed in both transpilePackages and \
+             experimental.serverComponentsExternalPackages"
+                .to_string(),
+        )
+    }
+
+    #[turbo_tasks::function]
+    fn description(&self) -> StringVc {
+        let conflicts = self
+            .conflicts
+            .iter()
+            .map(|(transpile, external)| {
+                if transpile == external {
+                    format!("- {transpile}")
+                } else {
+                    format!("- {transpile} (transpilePackages) and {external} (external packages)")
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        StringVc::cell(format!(
+            "A package can either be transpiled and bundled, or externalized and loaded at \
+             runtime, and which of both happens currently depends on the kind of server context. \
+             Remove each of these packages from one of the lists:\n{conflicts}"
+        ))
+    }
+}
+
+/// Returns whether `path` is within a package in `node_modules` (at any depth)
+/// which matches one of the `patterns`.
+fn is_in_listed_package(patterns: &[String], path: &str) -> bool {
+    path.split("node_modules/")
+        .skip(1)
+        .filter_map(package_name)
+        .any(|package| {
+            patterns
+                .iter()
+                .any(|pattern| matches_package_pattern(pattern, &package))
+        })
+}
+
+/// Returns the name of the package `rest`, a path relative to a
+/// `node_modules` directory, is in.
+fn package_name(rest: &str) -> Option<String> {
+    let mut segments = rest.splitn(3, '/');
+    match (segments.next(), segments.next()) {
+        (Some(scope), Some(name)) if scope.starts_with('@') => Some(format!("{scope}/{name}")),
+        (Some(name), _) => Some(name.to_string()),
+        _ => None,
+    }
+}
+
+/// Returns the name of the package the module at `path` is in, i.e. of the
+/// innermost `node_modules` directory.
+fn installed_package(path: &str) -> Option<String> {
+    path.rsplit_once("node_modules/")
+        .and_then(|(_, rest)| package_name(rest))
+}
+
+/// Returns whether a module at `path` is marked as external: with
+/// `all_except`, unless it's in one of the `packages`, otherwise only if it is.
+pub(crate) fn matches_external_predicate(
+    all_except: bool,
+    packages: &[String],
+    path: &str,
+) -> bool {
+    is_in_listed_package(packages, path) != all_except
+}
+
+/// Returns whether a module at `path` is marked as external by the predicate
+/// (see [matches_external_predicate]), and not in one of the
+/// `force_bundle_packages`, which take precedence.
+fn is_marked_external(
+    all_except: bool,
+    packages: &[String],
+    force_bundle_packages: &[String],
+    path: &str,
+) -> bool {
+    matches_external_predicate(all_except, packages, path)
+        && !is_in_listed_package(force_bundle_packages, path)
+}
+
+/// Fails the resolution of native Node.js addons (`.node` files), for contexts
+/// where native modules are disabled.
+#[turbo_tasks::value]
+pub(crate) struct DisabledNativeModulesResolvePlugin {
+    root: FileSystemPathVc,
+    /// The config option which disabled native modules.
+    option: String,
+}
+
+#[turbo_tasks::value_impl]
+impl DisabledNativeModulesResolvePluginVc {
+    #[turbo_tasks::function]
+    pub fn new(root: FileSystemPathVc, option: &str) -> Self {
+        DisabledNativeModulesResolvePlugin {
+            root,
+            option: option.to_string(),
+        }
+        .cell()
+    }
+}
+
+#[turbo_tasks::value_impl]
+impl ResolvePlugin for DisabledNativeModulesResolvePlugin {
+    #[turbo_tasks::function]
+    fn after_resolve_condition(&self) -> ResolvePluginConditionVc {
+        ResolvePluginConditionVc::new(self.root.root(), GlobVc::new("**/*.node"))
+    }
+
+    #[turbo_tasks::function]
+    fn after_resolve(
+        &self,
+        fs_path: FileSystemPathVc,
+        context: FileSystemPathVc,
+        _request: RequestVc,
+    ) -> ResolveResultOptionVc {
+        NativeModuleDisabledIssue {
+            context,
+            path: fs_path,
+            option: self.option.clone(),
+        }
+        .cell()
+        .as_issue()
+        .emit();
+
+        ResolveResultOptionVc::some(ResolveResult::unresolveable().cell())
+    }
+}
+
+/// Returns whether `path` is outside of `project_dir` and not in a
+/// `node_modules` directory. Both are relative to the root of the project's
+/// filesystem, which may be a parent of the project directory.
+fn is_external_dir_path(project_dir: &str, path: &str) -> bool {
+    let inside_project = project_dir.is_empty()
+        || path == project_dir
+        || path
+            .strip_prefix(project_dir)
+            .map_or(false, |rest| rest.starts_with('/'));
+    !inside_project && !path.split('/').any(|segment| segment == "node_modules")
+}
+
+/// Fails the resolution of files outside of the project directory, unless
+/// `experimental.externalDir` is enabled.
+#[turbo_tasks::value]
+pub(crate) struct ExternalDirResolvePlugin {
+    project_path: FileSystemPathVc,
+}
+
+#[turbo_tasks::value_impl]
+impl ExternalDirResolvePluginVc {
+    #[turbo_tasks::function]
+    pub fn new(project_path: FileSystemPathVc) -> Self {
+        ExternalDirResolvePlugin { project_path }.cell()
+    }
+}
+
+#[turbo_tasks::value_impl]
+impl ResolvePlugin for ExternalDirResolvePlugin {
+    #[turbo_tasks::function]
+    fn after_resolve_condition(&self) -> ResolvePluginConditionVc {
+        ResolvePluginConditionVc::new(self.project_path.root(), GlobVc::new("**"))
+    }
+
+    #[turbo_tasks::function]
+    async fn after_resolve(
+        &self,
+        fs_path: FileSystemPathVc,
+        context: FileSystemPathVc,
+        _request: RequestVc,
+    ) -> Result<ResolveResultOptionVc> {
+        if !is_external_dir_path(&self.project_path.await?.path, &fs_path.await?.path) {
+            return Ok(ResolveResultOptionVc::none());
+        }
+
+        ExternalDirImportIssue {
+            context,
+            path: fs_path,
+        }
+        .cell()
+        .as_issue()
+        .emit();
+
+        Ok(ResolveResultOptionVc::some(
+            ResolveResult::unresolveable().cell(),
+        ))
+    }
+}
+
+#[turbo_tasks::value(shared)]
+struct ExternalDirImportIssue {
+    context: FileSystemPathVc,
+    path: FileSystemPathVc,
+}
+
+#[turbo_tasks::value_impl]
+impl Issue for ExternalDirImportIssue {
+    #[turbo_tasks::function]
+    fn severity(&self) -> IssueSeverityVc {
+        IssueSeverity::Error.into()
+    }
+
+    #[turbo_tasks::function]
+    fn category(&self) -> StringVc {
+        StringVc::cell("resolve".to_string())
+    }
+
+    #[turbo_tasks::function]
+    fn context(&self) -> FileSystemPathVc {
+        self.context
+    }
+
+    #[turbo_tasks::function]
+    async fn title(&self) -> Result<StringVc> {
+        Ok(StringVc::cell(format!(
+            "Module {} is outside of the project directory",
+            self.path.await?.path
+        )))
+    }
+
+    #[turbo_tasks::function]
+    fn description(&self) -> StringVc {
+        StringVc::cell(
+            "Importing files from outside of the project directory requires \
+             \"experimental.externalDir\" to be enabled in next.config.js."
+                .to_string(),
+        )
+    }
+}
+
+#[turbo_tasks::value(shared)]
+struct NativeModuleDisabledIssue {
+    context: FileSystemPathVc,
+    path: FileSystemPathVc,
+    option: String,
+}
+
+#[turbo_tasks::value_impl]
+impl Issue for NativeModuleDisabledIssue {
+    #[turbo_tasks::function]
+    fn severity(&self) -> IssueSeverityVc {
+        IssueSeverity::Error.into()
+    }
+
+    #[turbo_tasks::function]
+    fn category(&self) -> StringVc {
+        StringVc::cell("resolve".to_string())
+    }
+
+    #[turbo_tasks::function]
+    fn context(&self) -> FileSystemPathVc {
+        self.context
+    }
+
+    #[turbo_tasks::function]
+    async fn title(&self) -> Result<StringVc> {
+        Ok(StringVc::cell(format!(
+            "Native module {} can't be used",
+            self.path.await?.path
+        )))
+    }
+
+    #[turbo_tasks::function]
+    fn description(&self) -> StringVc {
+        StringVc::cell(native_modules_disabled_description(&self.option))
+    }
+}
+
+fn native_modules_disabled_description(option: &str) -> String {
+    if option == "experimental.ssrNativeModules" {
+        "Native modules are disabled for server-side rendering by \
+         \"experimental.ssrNativeModules\". Move the import into a server component or route \
+         handler, or enable the option."
+            .to_string()
+    } else {
+        format!(
+            "Native modules are disabled in all server contexts by \"{option}\". Replace the \
+             package with one which doesn't need a native addon, or enable the option."
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use turbo_tasks::primitives::StringsVc;
+    use turbopack_binding::{
+        turbo::tasks_fs::FileSystemPathVc,
+        turbopack::core::resolve::{parse::RequestVc, plugin::ResolvePluginVc},
+    };
+
+    use super::{
+        conflicting_packages, external_module_type, externalizes_esm,
+        externals_summary_description, installed_package, is_external_dir_path, is_externalizable,
+        is_in_listed_package, is_marked_external, should_externalize_esm, summarize_externals,
+        ExternalCjsModulesResolvePluginVc, ExternalModuleType, ExternalPredicate,
+        ExternalsDecision,
+    };
+    use crate::{
+        next_config::{EsmExternals, NextConfig, OutputType},
+        test_util::run_in_project,
+    };
+
+    /// A package which can only be `import`ed.
+    const ESM_ONLY_PACKAGE: &[(&str, &str)] = &[
+        (
+            "node_modules/esm-only/package.json",
+            r#"{ "name": "esm-only", "type": "module", "exports": { "import": "./index.js" } }"#,
+        ),
+        ("node_modules/esm-only/index.js", "export default 42;"),
+    ];
+
+    /// Returns whether the externals plugin of a pages context externalizes
+    /// the import of `request` from the project root, which resolved to
+    /// `path`.
+    async fn is_externalized(
+        root: FileSystemPathVc,
+        request: &str,
+        path: &str,
+        esm_externals: bool,
+    ) -> Result<bool> {
+        let plugin: ResolvePluginVc = ExternalCjsModulesResolvePluginVc::new(
+            root,
+            ExternalPredicate::AllExcept(StringsVc::cell(vec![])).cell(),
+            StringsVc::cell(vec![]),
+            esm_externals,
+            "Pages",
+        )
+        .into();
+        let result = plugin
+            .after_resolve(
+                root.join(path),
+                root,
+                RequestVc::parse_string(request.to_string()),
+            )
+            .await?;
+        Ok(result.is_some())
+    }
+
+    #[tokio::test]
+    async fn test_esm_externals_resolve_with_import_condition() -> Result<()> {
+        // The package can only be `import`ed, so it can't be externalized by
+        // checking it against node.js' CommonJS resolution.
+        run_in_project(ESM_ONLY_PACKAGE, |root| async move {
+            let path = "node_modules/esm-only/index.js";
+
+            // `esmExternals` defaults to `true`, like in Next.js.
+            assert!(*NextConfig::default().cell().esm_externals().await?);
+            assert!(is_externalized(root, "esm-only", path, true).await?);
+            assert!(!is_externalized(root, "esm-only", path, false).await?);
+            Ok(())
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_standalone_output_externalizes_esm() -> Result<()> {
+        run_in_project(ESM_ONLY_PACKAGE, |root| async move {
+            let path = "node_modules/esm-only/index.js";
+            let mut next_config = NextConfig::default();
+            next_config.experimental.esm_externals = Some(EsmExternals::Boolean(false));
+
+            let esm_externals = *externalizes_esm(next_config.clone().cell()).await?;
+            assert!(!is_externalized(root, "esm-only", path, esm_externals).await?);
+
+            // The traced `node_modules` of a standalone server can be imported
+            // at runtime, even with `esmExternals: false`.
+            next_config.output = Some(OutputType::Standalone);
+            let esm_externals = *externalizes_esm(next_config.cell()).await?;
+            assert!(is_externalized(root, "esm-only", path, esm_externals).await?);
+            Ok(())
+        })
+        .await
+    }
+
+    #[test]
+    fn test_cjs_is_externalizable() {
+        assert!(is_externalizable(Some("js"), None, false));
+        assert!(is_externalizable(Some("cjs"), Some("module"), false));
+        assert!(!is_externalizable(Some("ts"), None, true));
+    }
+
+    #[test]
+    fn test_esm_is_externalizable_with_esm_externals() {
+        assert!(!is_externalizable(Some("js"), Some("module"), false));
+        assert!(!is_externalizable(Some("mjs"), None, false));
+        assert!(is_externalizable(Some("js"), Some("module"), true));
+        assert!(is_externalizable(Some("mjs"), None, true));
+    }
+
+    #[test]
+    fn test_force_bundle_packages() {
+        let externals = vec!["sharp".to_string(), "@prisma/client".to_string()];
+        let force_bundle_packages = vec!["sharp".to_string()];
+        assert!(is_marked_external(
+            false,
+            &externals,
+            &[],
+            "project/node_modules/sharp/lib/index.js"
+        ));
+        // Listed in both, the package is bundled.
+        assert!(!is_marked_external(
+            false,
+            &externals,
+            &force_bundle_packages,
+            "project/node_modules/sharp/lib/index.js"
+        ));
+        assert!(is_marked_external(
+            false,
+            &externals,
+            &force_bundle_packages,
+            "project/node_modules/@prisma/client/index.js"
+        ));
+        assert!(!is_marked_external(
+            true,
+            &[],
+            &force_bundle_packages,
+            "project/node_modules/sharp/lib/index.js"
+        ));
+    }
+
+    #[test]
+    fn test_external_module_type() {
+        // The `import` entry of a dual package is loaded as ESM...
+        assert_eq!(
+            external_module_type(Some("mjs"), None),
+            Some(ExternalModuleType::EcmaScript)
+        );
+        assert_eq!(
+            external_module_type(Some("js"), Some("module")),
+            Some(ExternalModuleType::EcmaScript)
+        );
+        // ...and its `require` entry as CommonJS.
+        assert_eq!(
+            external_module_type(Some("cjs"), Some("module")),
+            Some(ExternalModuleType::CommonJs)
+        );
+        assert_eq!(
+            external_module_type(Some("js"), None),
+            Some(ExternalModuleType::CommonJs)
+        );
+        assert_eq!(external_module_type(Some("ts"), None), None);
+    }
+
+    #[test]
+    fn test_standalone_externalizes_esm() {
+        let esm_externals = should_externalize_esm(false, None);
+        assert!(!is_externalizable(Some("mjs"), None, esm_externals));
+
+        let esm_externals = should_externalize_esm(false, Some(&OutputType::Standalone));
+        assert!(is_externalizable(Some("mjs"), None, esm_externals));
+
+        let esm_externals = should_externalize_esm(false, Some(&OutputType::Export));
+        assert!(!is_externalizable(Some("mjs"), None, esm_externals));
+    }
+
+    #[test]
+    fn test_transpile_packages_scope_wildcard() {
+        let transpile_packages = vec!["@acme/*".to_string()];
+        assert!(is_in_listed_package(
+            &transpile_packages,
+            "project/node_modules/@acme/button/index.js"
+        ));
+        assert!(is_in_listed_package(
+            &transpile_packages,
+            "project/node_modules/.pnpm/@acme+button@1.0.0/node_modules/@acme/button/index.js"
+        ));
+        assert!(!is_in_listed_package(
+            &transpile_packages,
+            "project/node_modules/@other/x/index.js"
+        ));
+        assert!(!is_in_listed_package(
+            &transpile_packages,
+            "project/node_modules/acme/index.js"
+        ));
+    }
+
+    #[test]
+    fn test_transpile_packages_exact_name() {
+        let transpile_packages = vec!["lodash".to_string(), "@acme/button".to_string()];
+        assert!(is_in_listed_package(
+            &transpile_packages,
+            "project/node_modules/lodash/index.js"
+        ));
+        assert!(is_in_listed_package(
+            &transpile_packages,
+            "project/node_modules/@acme/button/index.js"
+        ));
+        assert!(!is_in_listed_package(
+            &transpile_packages,
+            "project/node_modules/lodash-es/index.js"
+        ));
+        assert!(!is_in_listed_package(
+            &transpile_packages,
+            "project/node_modules/@acme/card/index.js"
+        ));
+    }
+
+    #[test]
+    fn test_external_dir_path() {
+        assert!(is_external_dir_path("apps/web", "packages/shared/index.ts"));
+        assert!(is_external_dir_path("apps/web", "apps/website/index.ts"));
+        assert!(!is_external_dir_path("apps/web", "apps/web/lib/util.ts"));
+        assert!(!is_external_dir_path(
+            "apps/web",
+            "node_modules/react/index.js"
+        ));
+        assert!(!is_external_dir_path("", "lib/util.ts"));
+    }
+
+    #[test]
+    fn test_conflicting_packages() {
+        let transpile = vec!["ui".to_string(), "@acme/*".to_string()];
+        let external = vec![
+            "ui".to_string(),
+            "@acme/db".to_string(),
+            "sharp".to_string(),
+        ];
+        assert_eq!(
+            conflicting_packages(&transpile, &external),
+            vec![
+                ("ui".to_string(), "ui".to_string()),
+                ("@acme/*".to_string(), "@acme/db".to_string()),
+            ]
+        );
+        assert!(conflicting_packages(&transpile, &["sharp".to_string()]).is_empty());
+    }
+
+    #[test]
+    fn test_externals_summary() {
+        // Decisions are emitted for the package of the innermost
+        // `node_modules` directory, and not for project files.
+        assert_eq!(
+            installed_package("node_modules/.pnpm/lodash@4.17.21/node_modules/lodash/get.js")
+                .as_deref(),
+            Some("lodash")
+        );
+        assert_eq!(
+            installed_package("node_modules/@acme/ui/dist/index.mjs").as_deref(),
+            Some("@acme/ui")
+        );
+        assert_eq!(installed_package("src/util.js"), None);
+
+        let decision = |context_type: &str, package: &str, external| ExternalsDecision {
+            context_type: context_type.to_string(),
+            package: package.to_string(),
+            external,
+        };
+        let decisions = [
+            decision("AppRSC", "react-dom", true),
+            decision("AppRSC", "@acme/ui", false),
+            decision("AppRSC", "sharp", true),
+            // A package with both externalized and bundled modules is externalized.
+            decision("AppRSC", "sharp", false),
+            decision("Pages", "lodash", true),
+        ];
+
+        let entries = summarize_externals(&decisions);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries["AppRSC"].externalized.len(), 2);
+        assert_eq!(entries["AppRSC"].bundled.len(), 1);
+        assert_eq!(entries["Pages"].externalized.len(), 1);
+        assert!(entries["Pages"].bundled.is_empty());
+
+        assert_eq!(
+            externals_summary_description(&entries, 1),
+            "- AppRSC: 2 externalized (react-dom, ...), 1 bundled (@acme/ui)\n- Pages: 1 \
+             externalized (lodash), 0 bundled"
+        );
+    }
 }
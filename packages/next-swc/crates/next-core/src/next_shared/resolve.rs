@@ -6,7 +6,7 @@ use turbo_tasks_fs::glob::GlobVc;
 use turbopack_binding::{
     turbo::tasks_fs::FileSystemPathVc,
     turbopack::core::{
-        issue::unsupported_module::UnsupportedModuleIssue,
+        issue::{unsupported_module::UnsupportedModuleIssue, IssueVc},
         resolve::{
             parse::{Request, RequestVc},
             pattern::Pattern,
@@ -16,6 +16,8 @@ use turbopack_binding::{
     },
 };
 
+use crate::util::issue_with_context_type;
+
 lazy_static! {
     static ref UNSUPPORTED_PACKAGES: HashSet<&'static str> = ["@vercel/og"].into();
     static ref UNSUPPORTED_PACKAGE_PATHS: HashSet<(&'static str, &'static str)> = [].into();
@@ -24,13 +26,31 @@ lazy_static! {
 #[turbo_tasks::value]
 pub(crate) struct UnsupportedModulesResolvePlugin {
     root: FileSystemPathVc,
+    /// The type of the context this plugin resolves in, which is included in
+    /// the title of the issues it emits.
+    context_type: Option<String>,
 }
 
 #[turbo_tasks::value_impl]
 impl UnsupportedModulesResolvePluginVc {
     #[turbo_tasks::function]
     pub fn new(root: FileSystemPathVc) -> Self {
-        UnsupportedModulesResolvePlugin { root }.cell()
+        UnsupportedModulesResolvePlugin {
+            root,
+            context_type: None,
+        }
+        .cell()
+    }
+
+    /// Like [UnsupportedModulesResolvePluginVc::new], but tags the issues it
+    /// emits with `context_type`.
+    #[turbo_tasks::function]
+    pub fn new_with_context_type(root: FileSystemPathVc, context_type: &str) -> Self {
+        UnsupportedModulesResolvePlugin {
+            root,
+            context_type: Some(context_type.to_string()),
+        }
+        .cell()
     }
 }
 
@@ -56,26 +76,26 @@ impl ResolvePlugin for UnsupportedModulesResolvePlugin {
         {
             // Warn if the package is known not to be supported by Turbopack at the moment.
             if UNSUPPORTED_PACKAGES.contains(module.as_str()) {
-                UnsupportedModuleIssue {
+                let issue = UnsupportedModuleIssue {
                     context,
                     package: module.into(),
                     package_path: None,
                 }
                 .cell()
-                .as_issue()
-                .emit();
+                .as_issue();
+                issue_with_context_type(issue, self.context_type.as_deref()).emit();
             }
 
             if let Pattern::Constant(path) = path {
                 if UNSUPPORTED_PACKAGE_PATHS.contains(&(module, path)) {
-                    UnsupportedModuleIssue {
+                    let issue = UnsupportedModuleIssue {
                         context,
                         package: module.into(),
                         package_path: Some(path.to_owned()),
                     }
                     .cell()
-                    .as_issue()
-                    .emit();
+                    .as_issue();
+                    issue_with_context_type(issue, self.context_type.as_deref()).emit();
                 }
             }
         }
@@ -0,0 +1,164 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use swc_core::{
+    common::DUMMY_SP,
+    ecma::ast::{
+        Decl, ExportDecl, Ident, ImportDecl, ImportDefaultSpecifier, ImportSpecifier, Module,
+        ModuleDecl, ModuleItem, Pat, Program, Stmt,
+    },
+};
+use turbopack_binding::turbopack::ecmascript::{
+    CustomTransformer, OptionTransformPluginVc, TransformContext, TransformPluginVc,
+};
+
+use crate::next_config::NextConfigVc;
+
+/// Returns whether the pragmas in `comments` select the classic JSX runtime
+/// with the default `React.createElement` factory, which needs `React` to be
+/// in scope. Like Babel, a custom `@jsx` factory doesn't need it.
+fn needs_react_in_scope<'a>(comments: impl IntoIterator<Item = &'a str>) -> bool {
+    let mut classic = false;
+    let mut react_factory = true;
+    for comment in comments {
+        let words = comment.split_whitespace().collect::<Vec<_>>();
+        for pragma in words.windows(2) {
+            match pragma[0] {
+                "@jsxRuntime" => classic = pragma[1] == "classic",
+                "@jsx" => react_factory = pragma[1].starts_with("React."),
+                _ => {}
+            }
+        }
+    }
+    classic && react_factory
+}
+
+/// Returns whether `module` declares a top-level `React` binding.
+fn declares_react(module: &Module) -> bool {
+    module.body.iter().any(|item| match item {
+        ModuleItem::ModuleDecl(ModuleDecl::Import(import)) => {
+            import.specifiers.iter().any(|specifier| match specifier {
+                ImportSpecifier::Named(named) => &*named.local.sym == "React",
+                ImportSpecifier::Default(default) => &*default.local.sym == "React",
+                ImportSpecifier::Namespace(namespace) => &*namespace.local.sym == "React",
+            })
+        }
+        ModuleItem::Stmt(Stmt::Decl(decl))
+        | ModuleItem::ModuleDecl(ModuleDecl::ExportDecl(ExportDecl { decl, .. })) => match decl {
+            Decl::Var(var) => var.decls.iter().any(|declarator| match &declarator.name {
+                Pat::Ident(ident) => &*ident.id.sym == "React",
+                _ => false,
+            }),
+            Decl::Fn(function) => &*function.ident.sym == "React",
+            Decl::Class(class) => &*class.ident.sym == "React",
+            _ => false,
+        },
+        _ => false,
+    })
+}
+
+/// Prepends `import React from "react"` to `module`, unless it already
+/// declares `React`.
+fn insert_react_import(module: &mut Module) {
+    if declares_react(module) {
+        return;
+    }
+
+    module.body.insert(
+        0,
+        ModuleItem::ModuleDecl(ModuleDecl::Import(ImportDecl {
+            span: DUMMY_SP,
+            specifiers: vec![ImportSpecifier::Default(ImportDefaultSpecifier {
+                span: DUMMY_SP,
+                local: Ident::new("React".into(), DUMMY_SP),
+            })],
+            src: Box::new("react".into()),
+            type_only: false,
+            asserts: None,
+        })),
+    );
+}
+
+/// Returns a source transform plugin which imports `React` into files opting
+/// into the classic JSX runtime with a `@jsxRuntime classic` pragma, when
+/// `experimental.autoImportReact` is enabled. Files using the automatic
+/// runtime are left untouched.
+#[turbo_tasks::function]
+pub async fn get_auto_import_react_transform_plugin(
+    next_config: NextConfigVc,
+) -> Result<OptionTransformPluginVc> {
+    if !next_config
+        .await?
+        .experimental
+        .auto_import_react
+        .unwrap_or(false)
+    {
+        return Ok(OptionTransformPluginVc::cell(None));
+    }
+
+    Ok(OptionTransformPluginVc::cell(Some(
+        TransformPluginVc::cell(Box::new(AutoImportReact)),
+    )))
+}
+
+#[derive(Debug)]
+struct AutoImportReact;
+
+#[async_trait]
+impl CustomTransformer for AutoImportReact {
+    async fn transform(&self, program: &mut Program, ctx: &TransformContext<'_>) -> Result<()> {
+        let Program::Module(module) = program else {
+            return Ok(());
+        };
+
+        let comments = ctx
+            .comments
+            .leading
+            .iter()
+            .flat_map(|entry| entry.value().clone())
+            .collect::<Vec<_>>();
+        if needs_react_in_scope(comments.iter().map(|comment| &*comment.text)) {
+            insert_react_import(module);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use swc_core::{
+        common::DUMMY_SP,
+        ecma::ast::{Module, ModuleDecl, ModuleItem},
+    };
+
+    use super::{insert_react_import, needs_react_in_scope};
+
+    #[test]
+    fn test_classic_runtime_pragma() {
+        assert!(needs_react_in_scope(["* @jsxRuntime classic "]));
+        assert!(needs_react_in_scope([
+            "* @jsxRuntime classic\n * @jsx React.createElement "
+        ]));
+        assert!(!needs_react_in_scope(["* @jsxRuntime automatic "]));
+        assert!(!needs_react_in_scope(["* @jsxRuntime classic\n * @jsx h "]));
+        assert!(!needs_react_in_scope(["* regular comment "]));
+        assert!(!needs_react_in_scope([]));
+    }
+
+    #[test]
+    fn test_insert_react_import_once() {
+        let mut module = Module {
+            span: DUMMY_SP,
+            body: vec![],
+            shebang: None,
+        };
+        insert_react_import(&mut module);
+        insert_react_import(&mut module);
+
+        assert_eq!(module.body.len(), 1);
+        let ModuleItem::ModuleDecl(ModuleDecl::Import(import)) = &module.body[0] else {
+            panic!("expected an import");
+        };
+        assert_eq!(&*import.src.value, "react");
+    }
+}
@@ -1,14 +1,19 @@
 use anyhow::Result;
-use turbopack_binding::turbopack::{
-    ecmascript::{OptionTransformPluginVc, TransformPluginVc},
-    ecmascript_plugin::transform::emotion::{EmotionTransformConfig, EmotionTransformer},
+use turbopack_binding::{
+    turbo::tasks_fs::FileSystemPathVc,
+    turbopack::{
+        ecmascript::{OptionTransformPluginVc, TransformPluginVc},
+        ecmascript_plugin::transform::emotion::{EmotionTransformConfig, EmotionTransformer},
+    },
 };
 
+use super::is_transform_package_resolvable;
 use crate::next_config::{EmotionTransformOptionsOrBoolean, NextConfigVc};
 
 #[turbo_tasks::function]
 pub async fn get_emotion_transform_plugin(
     next_config: NextConfigVc,
+    project_path: FileSystemPathVc,
 ) -> Result<OptionTransformPluginVc> {
     let transform_plugin = next_config
         .await?
@@ -45,5 +50,12 @@ pub async fn get_emotion_transform_plugin(
         })
         .unwrap_or_default();
 
+    if transform_plugin.await?.is_some()
+        && !is_transform_package_resolvable(project_path, "compiler.emotion", "@emotion/react")
+            .await?
+    {
+        return Ok(OptionTransformPluginVc::cell(None));
+    }
+
     Ok(transform_plugin)
 }
@@ -0,0 +1,147 @@
+use anyhow::Result;
+use turbo_tasks::primitives::StringVc;
+use turbopack_binding::{
+    turbo::tasks_fs::FileSystemPathVc,
+    turbopack::{
+        core::{
+            asset::{Asset, AssetVc},
+            issue::{Issue, IssueSeverity, IssueSeverityVc, IssueVc},
+            resolve::ModulePartVc,
+        },
+        turbopack::{
+            module_options::{
+                CustomModuleType, CustomModuleTypeVc, ModuleRule, ModuleRuleCondition,
+                ModuleRuleEffect, ModuleType,
+            },
+            ModuleAssetContextVc,
+        },
+    },
+};
+
+use crate::{next_config::NextConfigVc, util::issue_with_context_type};
+
+/// Returns a rule which reports `.md`/`.mdx` modules, unless they can be
+/// compiled because MDX is enabled or a loader handles them. Without it they
+/// fail with an opaque parse error.
+pub async fn get_markdown_unsupported_rule(
+    next_config: NextConfigVc,
+    context_type: Option<&str>,
+) -> Result<Option<ModuleRule>> {
+    let mdx_rs = *next_config.mdx_rs().await?;
+    let rule_keys: Vec<String> = match *next_config.webpack_rules().await? {
+        Some(rules) => rules.await?.keys().cloned().collect(),
+        None => vec![],
+    };
+    if !is_markdown_unsupported(mdx_rs, rule_keys.iter().map(String::as_str)) {
+        return Ok(None);
+    }
+
+    Ok(Some(ModuleRule::new(
+        ModuleRuleCondition::any(vec![
+            ModuleRuleCondition::ResourcePathEndsWith(".md".to_string()),
+            ModuleRuleCondition::ResourcePathEndsWith(".mdx".to_string()),
+        ]),
+        vec![ModuleRuleEffect::ModuleType(ModuleType::Custom(
+            MarkdownUnsupportedModuleType {
+                context_type: context_type.map(ToString::to_string),
+            }
+            .cell()
+            .into(),
+        ))],
+    )))
+}
+
+/// Returns whether markdown (`.md`/`.mdx`) modules can't be compiled, because
+/// neither `experimental.mdxRs` is enabled nor does any loader rule mention
+/// these extensions.
+fn is_markdown_unsupported<'a>(mdx_rs: bool, mut rule_keys: impl Iterator<Item = &'a str>) -> bool {
+    !mdx_rs
+        && !rule_keys.any(|key| {
+            key.split(|c: char| !c.is_ascii_alphanumeric())
+                .any(|extension| extension == "md" || extension == "mdx")
+        })
+}
+
+/// Module type of markdown modules which can't be compiled. It reports them
+/// and keeps their source as is.
+#[turbo_tasks::value]
+struct MarkdownUnsupportedModuleType {
+    context_type: Option<String>,
+}
+
+#[turbo_tasks::value_impl]
+impl CustomModuleType for MarkdownUnsupportedModuleType {
+    #[turbo_tasks::function]
+    fn create_module(
+        &self,
+        source: AssetVc,
+        _context: ModuleAssetContextVc,
+        _part: Option<ModulePartVc>,
+    ) -> AssetVc {
+        let issue = MarkdownUnsupportedIssue {
+            path: source.ident().path(),
+        }
+        .cell()
+        .as_issue();
+        issue_with_context_type(issue, self.context_type.as_deref()).emit();
+        source
+    }
+}
+
+#[turbo_tasks::value(shared)]
+struct MarkdownUnsupportedIssue {
+    path: FileSystemPathVc,
+}
+
+#[turbo_tasks::value_impl]
+impl Issue for MarkdownUnsupportedIssue {
+    #[turbo_tasks::function]
+    fn severity(&self) -> IssueSeverityVc {
+        IssueSeverity::Error.into()
+    }
+
+    #[turbo_tasks::function]
+    fn category(&self) -> StringVc {
+        StringVc::cell("module type".to_string())
+    }
+
+    #[turbo_tasks::function]
+    fn context(&self) -> FileSystemPathVc {
+        self.path
+    }
+
+    #[turbo_tasks::function]
+    async fn title(&self) -> Result<StringVc> {
+        Ok(StringVc::cell(format!(
+            "Markdown module {} can't be compiled",
+            self.path.await?.path
+        )))
+    }
+
+    #[turbo_tasks::function]
+    fn description(&self) -> StringVc {
+        StringVc::cell(
+            "Importing .md and .mdx files requires MDX support. Enable \"experimental.mdxRs\" or \
+             add a loader for these files to \"experimental.turbo.rules\"."
+                .to_string(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_markdown_unsupported;
+
+    #[test]
+    fn test_markdown_unsupported_with_mdx_disabled() {
+        assert!(is_markdown_unsupported(false, [].into_iter()));
+        assert!(is_markdown_unsupported(false, ["*.svg"].into_iter()));
+    }
+
+    #[test]
+    fn test_markdown_supported() {
+        assert!(!is_markdown_unsupported(true, [].into_iter()));
+        assert!(!is_markdown_unsupported(false, ["*.mdx"].into_iter()));
+        assert!(!is_markdown_unsupported(false, ["*.{md,mdx}"].into_iter()));
+    }
+}
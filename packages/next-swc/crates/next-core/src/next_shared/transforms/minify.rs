@@ -0,0 +1,91 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use swc_core::ecma::{
+    ast::{Module, Program},
+    minifier::{
+        optimize,
+        option::{ExtraOptions, MinifyOptions},
+    },
+};
+use turbopack_binding::turbopack::ecmascript::{
+    CustomTransformer, OptionTransformPluginVc, TransformContext, TransformPluginVc,
+};
+
+use crate::{
+    mode::NextMode,
+    next_config::{NextConfig, NextConfigVc},
+};
+
+/// Returns whether server output is minified. It is in production builds,
+/// unless `experimental.serverMinification` is `false`, e.g. to debug
+/// production issues with readable output.
+fn is_server_minification_enabled(next_config: &NextConfig, mode: NextMode) -> bool {
+    mode == NextMode::Build && next_config.experimental.server_minification.unwrap_or(true)
+}
+
+/// Returns an output transform plugin which minifies server code in production
+/// builds.
+#[turbo_tasks::function]
+pub async fn get_server_minify_transform_plugin(
+    next_config: NextConfigVc,
+    mode: NextMode,
+) -> Result<OptionTransformPluginVc> {
+    if !is_server_minification_enabled(&*next_config.await?, mode) {
+        return Ok(OptionTransformPluginVc::cell(None));
+    }
+
+    Ok(OptionTransformPluginVc::cell(Some(
+        TransformPluginVc::cell(Box::new(Minify)),
+    )))
+}
+
+#[derive(Debug)]
+struct Minify;
+
+#[async_trait]
+impl CustomTransformer for Minify {
+    async fn transform(&self, program: &mut Program, ctx: &TransformContext<'_>) -> Result<()> {
+        let unminified = std::mem::replace(program, Program::Module(Module::dummy()));
+        *program = optimize(
+            unminified,
+            ctx.source_map.clone(),
+            Some(ctx.comments),
+            None,
+            &MinifyOptions {
+                compress: Some(Default::default()),
+                mangle: Some(Default::default()),
+                ..Default::default()
+            },
+            &ExtraOptions {
+                unresolved_mark: ctx.unresolved_mark,
+                top_level_mark: ctx.top_level_mark,
+            },
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_server_minification_enabled;
+    use crate::{mode::NextMode, next_config::NextConfig};
+
+    #[test]
+    fn test_server_minification() {
+        let mut next_config = NextConfig::default();
+        assert!(is_server_minification_enabled(
+            &next_config,
+            NextMode::Build
+        ));
+        assert!(!is_server_minification_enabled(
+            &next_config,
+            NextMode::Development
+        ));
+
+        next_config.experimental.server_minification = Some(false);
+        assert!(!is_server_minification_enabled(
+            &next_config,
+            NextMode::Build
+        ));
+    }
+}
@@ -1,24 +1,46 @@
+pub(crate) mod auto_import_react;
 pub(crate) mod emotion;
+pub(crate) mod markdown;
+pub(crate) mod minify;
 pub(crate) mod modularize_imports;
 pub(crate) mod next_dynamic;
 pub(crate) mod next_font;
 pub(crate) mod next_strip_page_exports;
 pub(crate) mod relay;
+pub(crate) mod strip_comments;
 pub(crate) mod styled_components;
 pub(crate) mod styled_jsx;
+pub(crate) mod timing;
 
+use anyhow::Result;
+pub use markdown::get_markdown_unsupported_rule;
 pub use modularize_imports::{get_next_modularize_imports_rule, ModularizeImportPackageConfig};
 pub use next_dynamic::get_next_dynamic_transform_rule;
 pub use next_font::get_next_font_transform_rule;
 pub use next_strip_page_exports::get_next_pages_transforms_rule;
-pub use relay::get_relay_transform_plugin;
-use turbo_tasks::Value;
-use turbopack_binding::turbopack::{
-    core::reference_type::{ReferenceType, UrlReferenceSubType},
-    turbopack::module_options::{ModuleRule, ModuleRuleCondition, ModuleRuleEffect, ModuleType},
+pub use relay::{get_relay_transform_plugin, get_relay_transform_rule};
+use turbo_tasks::{primitives::StringVc, Value};
+use turbopack_binding::{
+    turbo::tasks_fs::FileSystemPathVc,
+    turbopack::{
+        core::{
+            issue::{Issue, IssueSeverity, IssueSeverityVc, IssueVc},
+            reference_type::{ReferenceType, UrlReferenceSubType},
+        },
+        ecmascript::{EcmascriptInputTransform, EcmascriptInputTransformsVc},
+        turbopack::module_options::{
+            ModuleRule, ModuleRuleCondition, ModuleRuleEffect, ModuleType,
+        },
+        wasm::source::WebAssemblySourceType,
+    },
 };
 
-use crate::next_image::{module::BlurPlaceholderMode, StructuredImageModuleTypeVc};
+use crate::{
+    next_config::NextConfigVc,
+    next_image::{module::BlurPlaceholderMode, StructuredImageModuleTypeVc},
+    next_import_map::is_package_resolvable,
+    transform_options::get_swcrc_target_environment,
+};
 
 /// Returns a rule which applies the Next.js dynamic transform.
 pub fn get_next_image_rule() -> ModuleRule {
@@ -39,6 +61,37 @@ pub fn get_next_image_rule() -> ModuleRule {
     )
 }
 
+/// Returns a rule which turns `.wasm` imports into WebAssembly modules. They
+/// are instantiated asynchronously and export the exports of the binary.
+pub fn get_next_wasm_rule() -> ModuleRule {
+    ModuleRule::new(
+        ModuleRuleCondition::ResourcePathEndsWith(".wasm".to_string()),
+        vec![ModuleRuleEffect::ModuleType(ModuleType::WebAssembly {
+            source_ty: WebAssemblySourceType::Binary,
+        })],
+    )
+}
+
+/// Returns a rule which compiles JavaScript and TypeScript down to the
+/// `jsc.target` of the project's `.swcrc`, if `experimental.swcrc` is enabled
+/// and it sets one.
+pub async fn get_swcrc_target_rule(
+    project_path: FileSystemPathVc,
+    next_config: NextConfigVc,
+) -> Result<Option<ModuleRule>> {
+    let Some(environment) = *get_swcrc_target_environment(project_path, next_config).await? else {
+        return Ok(None);
+    };
+    Ok(Some(ModuleRule::new(
+        module_rule_match_js_no_url(),
+        vec![ModuleRuleEffect::AddEcmascriptTransforms(
+            EcmascriptInputTransformsVc::cell(vec![EcmascriptInputTransform::PresetEnv(
+                environment,
+            )]),
+        )],
+    )))
+}
+
 pub(crate) fn module_rule_match_js_no_url() -> ModuleRuleCondition {
     ModuleRuleCondition::all(vec![
         ModuleRuleCondition::not(ModuleRuleCondition::ReferenceType(ReferenceType::Url(
@@ -52,3 +105,135 @@ pub(crate) fn module_rule_match_js_no_url() -> ModuleRuleCondition {
         ]),
     ])
 }
+
+/// Returns true if `package_name`, which the transform enabled by
+/// `config_key` operates on, can be resolved from `project_path`. Emits a
+/// warning issue if it can't.
+pub(crate) async fn is_transform_package_resolvable(
+    project_path: FileSystemPathVc,
+    config_key: &str,
+    package_name: &str,
+) -> Result<bool> {
+    if *is_package_resolvable(project_path, package_name).await? {
+        return Ok(true);
+    }
+
+    MissingTransformPackageIssue {
+        path: project_path,
+        config_key: config_key.to_string(),
+        package_name: package_name.to_string(),
+    }
+    .cell()
+    .as_issue()
+    .emit();
+
+    Ok(false)
+}
+
+#[turbo_tasks::value(shared)]
+struct MissingTransformPackageIssue {
+    path: FileSystemPathVc,
+    config_key: String,
+    package_name: String,
+}
+
+#[turbo_tasks::value_impl]
+impl Issue for MissingTransformPackageIssue {
+    #[turbo_tasks::function]
+    fn severity(&self) -> IssueSeverityVc {
+        IssueSeverity::Warning.into()
+    }
+
+    #[turbo_tasks::function]
+    fn category(&self) -> StringVc {
+        StringVc::cell("config".to_string())
+    }
+
+    #[turbo_tasks::function]
+    fn context(&self) -> FileSystemPathVc {
+        self.path
+    }
+
+    #[turbo_tasks::function]
+    fn title(&self) -> StringVc {
+        StringVc::cell(format!(
+            "\"{}\" is enabled, but \"{}\" could not be resolved",
+            self.config_key, self.package_name
+        ))
+    }
+
+    #[turbo_tasks::function]
+    fn description(&self) -> StringVc {
+        StringVc::cell(format!(
+            "The transform for \"{}\" was skipped. Install \"{}\" or remove \"{}\" from your \
+             Next.js config.",
+            self.package_name, self.package_name, self.config_key
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use serde_json::json;
+    use turbopack_binding::turbopack::core::issue::IssueVc;
+
+    use super::{
+        emotion::get_emotion_transform_plugin,
+        styled_components::get_styled_components_transform_plugin,
+    };
+    use crate::{next_config::NextConfig, test_util::run_in_project};
+
+    /// Returns whether the styled-components and emotion transforms are
+    /// enabled for a project of `files`, which enables both in its config,
+    /// along with the titles of the issues emitted for them.
+    async fn enabled_transforms(files: &[(&str, &str)]) -> Result<(bool, bool, Vec<String>)> {
+        run_in_project(files, |root| async move {
+            let next_config = NextConfig {
+                compiler: Some(serde_json::from_value(json!({
+                    "styledComponents": true,
+                    "emotion": true,
+                }))?),
+                ..Default::default()
+            }
+            .cell();
+            let mut enabled = Vec::new();
+            let mut titles = Vec::new();
+            for plugin in [
+                get_styled_components_transform_plugin(next_config, root),
+                get_emotion_transform_plugin(next_config, root),
+            ] {
+                enabled.push(plugin.await?.is_some());
+                let issues = IssueVc::peek_issues_with_path(plugin)
+                    .await?
+                    .strongly_consistent()
+                    .await?;
+                for issue in issues.iter() {
+                    titles.push(issue.title().await?.clone_value());
+                }
+            }
+            Ok((enabled[0], enabled[1], titles))
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_transform_skipped_without_package() -> Result<()> {
+        assert_eq!(
+            enabled_transforms(&[(
+                "node_modules/styled-components/package.json",
+                r#"{ "name": "styled-components" }"#,
+            )])
+            .await?,
+            (
+                true,
+                false,
+                vec![
+                    "\"compiler.emotion\" is enabled, but \"@emotion/react\" could not be resolved"
+                        .to_string()
+                ]
+            )
+        );
+        Ok(())
+    }
+}
@@ -1,18 +1,45 @@
 use anyhow::Result;
-use turbopack_binding::turbopack::{
-    ecmascript::{OptionTransformPluginVc, TransformPluginVc},
-    ecmascript_plugin::transform::relay::RelayTransformer,
+use turbopack_binding::{
+    turbo::tasks_fs::FileSystemPathVc,
+    turbopack::{
+        ecmascript::{
+            EcmascriptInputTransform, EcmascriptInputTransformsVc, OptionTransformPluginVc,
+            TransformPluginVc,
+        },
+        ecmascript_plugin::transform::relay::RelayTransformer,
+        turbopack::module_options::{ModuleRule, ModuleRuleCondition, ModuleRuleEffect},
+    },
 };
 
-use crate::next_config::NextConfigVc;
+use super::module_rule_match_js_no_url;
+use crate::next_config::{NextConfig, NextConfigVc};
 
-/// Returns a transform plugin for the relay graphql transform.
+/// Returns the directories, relative to the project root, the relay transform
+/// is scoped to by `experimental.relayInclude`. `None` means it applies to all
+/// files.
+fn relay_include_dirs(next_config: &NextConfig) -> Option<Vec<&str>> {
+    let include = next_config.experimental.relay_include.as_ref()?;
+    Some(
+        include
+            .iter()
+            .map(|dir| dir.trim_start_matches("./").trim_end_matches('/'))
+            .collect(),
+    )
+}
+
+/// Returns a transform plugin for the relay graphql transform. When the
+/// transform is scoped to directories, it's applied by
+/// [get_relay_transform_rule] instead.
 #[turbo_tasks::function]
 pub async fn get_relay_transform_plugin(
     next_config: NextConfigVc,
 ) -> Result<OptionTransformPluginVc> {
+    let next_config = &*next_config.await?;
+    if relay_include_dirs(next_config).is_some() {
+        return Ok(OptionTransformPluginVc::cell(None));
+    }
+
     let transform_plugin = next_config
-        .await?
         .compiler
         .as_ref()
         .map(|value| {
@@ -30,3 +57,61 @@ pub async fn get_relay_transform_plugin(
 
     Ok(transform_plugin)
 }
+
+/// Returns a rule which applies the relay graphql transform to the files in the
+/// `experimental.relayInclude` directories only.
+pub async fn get_relay_transform_rule(
+    project_path: FileSystemPathVc,
+    next_config: NextConfigVc,
+) -> Result<Option<ModuleRule>> {
+    let next_config = &*next_config.await?;
+    let Some(config) = next_config
+        .compiler
+        .as_ref()
+        .and_then(|compiler| compiler.relay.as_ref())
+    else {
+        return Ok(None);
+    };
+    let Some(include_dirs) = relay_include_dirs(next_config) else {
+        return Ok(None);
+    };
+
+    let mut in_include_dirs = Vec::with_capacity(include_dirs.len());
+    for dir in include_dirs {
+        in_include_dirs.push(ModuleRuleCondition::ResourcePathInExactDirectory(
+            project_path.join(dir).await?,
+        ));
+    }
+
+    let transformer = EcmascriptInputTransform::Plugin(TransformPluginVc::cell(Box::new(
+        RelayTransformer::new(config),
+    )));
+    Ok(Some(ModuleRule::new(
+        ModuleRuleCondition::all(vec![
+            ModuleRuleCondition::any(in_include_dirs),
+            module_rule_match_js_no_url(),
+        ]),
+        vec![ModuleRuleEffect::AddEcmascriptTransforms(
+            EcmascriptInputTransformsVc::cell(vec![transformer]),
+        )],
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::relay_include_dirs;
+    use crate::next_config::NextConfig;
+
+    #[test]
+    fn test_relay_include_dirs() {
+        let mut next_config = NextConfig::default();
+        assert_eq!(relay_include_dirs(&next_config), None);
+
+        next_config.experimental.relay_include =
+            Some(vec!["./src/graphql/".to_string(), "components".to_string()]);
+        assert_eq!(
+            relay_include_dirs(&next_config),
+            Some(vec!["src/graphql", "components"])
+        );
+    }
+}
@@ -0,0 +1,119 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use swc_core::{
+    common::comments::{Comment, CommentKind, SwcComments},
+    ecma::ast::Program,
+};
+use turbopack_binding::turbopack::ecmascript::{
+    CustomTransformer, OptionTransformPluginVc, TransformContext, TransformPluginVc,
+};
+
+use crate::{
+    mode::NextMode,
+    next_config::{CompilerConfig, NextConfigVc},
+};
+
+/// Returns whether license banners are kept when comments are stripped. They
+/// are unless `compiler.preserveBanner` is `false`.
+fn is_preserve_banner_enabled(compiler: Option<&CompilerConfig>) -> bool {
+    compiler
+        .and_then(|compiler| compiler.preserve_banner)
+        .unwrap_or(true)
+}
+
+/// Returns whether `comment` is a legal comment, which is either a `/*!`
+/// block comment or one marked with `@license` or `@preserve`.
+fn is_banner_comment(comment: &Comment) -> bool {
+    (comment.kind == CommentKind::Block && comment.text.starts_with('!'))
+        || comment.text.contains("@license")
+        || comment.text.contains("@preserve")
+}
+
+/// Removes all comments, except for license banners when `preserve_banner` is
+/// set.
+fn strip_comments(comments: &SwcComments, preserve_banner: bool) {
+    for map in [&comments.leading, &comments.trailing] {
+        for mut entry in map.iter_mut() {
+            entry
+                .value_mut()
+                .retain(|comment| preserve_banner && is_banner_comment(comment));
+        }
+    }
+}
+
+/// Returns an output transform plugin which strips comments from production
+/// output. Nothing is stripped in development, where comments help debugging.
+#[turbo_tasks::function]
+pub async fn get_strip_comments_transform_plugin(
+    next_config: NextConfigVc,
+    mode: NextMode,
+) -> Result<OptionTransformPluginVc> {
+    if mode != NextMode::Build {
+        return Ok(OptionTransformPluginVc::cell(None));
+    }
+
+    Ok(OptionTransformPluginVc::cell(Some(
+        TransformPluginVc::cell(Box::new(StripComments {
+            preserve_banner: is_preserve_banner_enabled(next_config.await?.compiler.as_ref()),
+        })),
+    )))
+}
+
+#[derive(Debug)]
+struct StripComments {
+    preserve_banner: bool,
+}
+
+#[async_trait]
+impl CustomTransformer for StripComments {
+    async fn transform(&self, _program: &mut Program, ctx: &TransformContext<'_>) -> Result<()> {
+        strip_comments(ctx.comments, self.preserve_banner);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use swc_core::common::{
+        comments::{Comment, CommentKind, Comments, SwcComments},
+        BytePos, DUMMY_SP,
+    };
+
+    use super::{is_preserve_banner_enabled, strip_comments};
+    use crate::next_config::CompilerConfig;
+
+    fn block_comment(text: &str) -> Comment {
+        Comment {
+            kind: CommentKind::Block,
+            span: DUMMY_SP,
+            text: text.into(),
+        }
+    }
+
+    fn comments() -> SwcComments {
+        let comments = SwcComments::default();
+        comments.add_leading(BytePos(1), block_comment("! license"));
+        comments.add_leading(BytePos(1), block_comment(" regular "));
+        comments
+    }
+
+    #[test]
+    fn test_preserves_banner() {
+        let comments = comments();
+        strip_comments(&comments, true);
+        assert_eq!(
+            comments.get_leading(BytePos(1)),
+            Some(vec![block_comment("! license")])
+        );
+    }
+
+    #[test]
+    fn test_strips_banner_when_disabled() {
+        let comments = comments();
+        strip_comments(&comments, false);
+        assert_eq!(comments.get_leading(BytePos(1)), Some(vec![]));
+        assert!(!is_preserve_banner_enabled(Some(
+            &serde_json::from_str::<CompilerConfig>(r#"{ "preserveBanner": false }"#).unwrap()
+        )));
+    }
+}
@@ -1,16 +1,21 @@
 use anyhow::Result;
-use turbopack_binding::turbopack::{
-    ecmascript::{OptionTransformPluginVc, TransformPluginVc},
-    ecmascript_plugin::transform::styled_components::{
-        StyledComponentsTransformConfig, StyledComponentsTransformer,
+use turbopack_binding::{
+    turbo::tasks_fs::FileSystemPathVc,
+    turbopack::{
+        ecmascript::{OptionTransformPluginVc, TransformPluginVc},
+        ecmascript_plugin::transform::styled_components::{
+            StyledComponentsTransformConfig, StyledComponentsTransformer,
+        },
     },
 };
 
+use super::is_transform_package_resolvable;
 use crate::next_config::{NextConfigVc, StyledComponentsTransformOptionsOrBoolean};
 
 #[turbo_tasks::function]
 pub async fn get_styled_components_transform_plugin(
     next_config: NextConfigVc,
+    project_path: FileSystemPathVc,
 ) -> Result<OptionTransformPluginVc> {
     let transform_plugin = next_config
         .await?
@@ -46,5 +51,16 @@ pub async fn get_styled_components_transform_plugin(
         })
         .unwrap_or_default();
 
+    if transform_plugin.await?.is_some()
+        && !is_transform_package_resolvable(
+            project_path,
+            "compiler.styledComponents",
+            "styled-components",
+        )
+        .await?
+    {
+        return Ok(OptionTransformPluginVc::cell(None));
+    }
+
     Ok(transform_plugin)
 }
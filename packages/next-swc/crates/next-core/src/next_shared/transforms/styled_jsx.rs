@@ -4,10 +4,52 @@ use turbopack_binding::turbopack::{
     ecmascript_plugin::transform::styled_jsx::StyledJsxTransformer,
 };
 
-/// Returns a transform plugin for the relay graphql transform.
+use crate::next_config::{CompilerConfig, NextConfigVc};
+
+/// Returns whether the styled-jsx transform is enabled. It is unless
+/// `compiler.styledJsx` is `false`.
+pub(crate) fn is_styled_jsx_enabled(compiler: Option<&CompilerConfig>) -> bool {
+    compiler
+        .and_then(|compiler| compiler.styled_jsx)
+        .unwrap_or(true)
+}
+
+/// Returns a transform plugin for the styled-jsx transform.
 #[turbo_tasks::function]
-pub async fn get_styled_jsx_transform_plugin() -> Result<OptionTransformPluginVc> {
+pub async fn get_styled_jsx_transform_plugin(
+    next_config: NextConfigVc,
+) -> Result<OptionTransformPluginVc> {
+    if !is_styled_jsx_enabled(next_config.await?.compiler.as_ref()) {
+        return Ok(OptionTransformPluginVc::cell(None));
+    }
+
     Ok(OptionTransformPluginVc::cell(Some(
         TransformPluginVc::cell(Box::new(StyledJsxTransformer::new())),
     )))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::is_styled_jsx_enabled;
+    use crate::next_config::CompilerConfig;
+
+    fn compiler_config(json: &str) -> CompilerConfig {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_styled_jsx_enabled_by_default() {
+        assert!(is_styled_jsx_enabled(None));
+        assert!(is_styled_jsx_enabled(Some(&compiler_config("{}"))));
+        assert!(is_styled_jsx_enabled(Some(&compiler_config(
+            r#"{ "styledJsx": true }"#
+        ))));
+    }
+
+    #[test]
+    fn test_styled_jsx_disabled() {
+        assert!(!is_styled_jsx_enabled(Some(&compiler_config(
+            r#"{ "styledJsx": false }"#
+        ))));
+    }
+}
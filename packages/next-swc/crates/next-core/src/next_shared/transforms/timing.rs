@@ -0,0 +1,162 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use swc_core::ecma::ast::Program;
+use tracing::Instrument;
+use turbopack_binding::turbopack::ecmascript::{
+    CustomTransformer, TransformContext, TransformPluginVc,
+};
+
+/// Wraps `plugin` so each of its runs is traced in a `transform` span named
+/// after the transform, when `enabled`. The execution time of the transforms
+/// is the duration of these spans, which is aggregated by the trace consumer.
+pub fn timed_transform_plugin(
+    name: &'static str,
+    plugin: Option<TransformPluginVc>,
+    enabled: bool,
+) -> Option<TransformPluginVc> {
+    if !enabled {
+        return plugin;
+    }
+    plugin.map(|inner| TransformPluginVc::cell(Box::new(TimedTransformer { name, inner })))
+}
+
+#[derive(Debug)]
+struct TimedTransformer {
+    name: &'static str,
+    inner: TransformPluginVc,
+}
+
+#[async_trait]
+impl CustomTransformer for TimedTransformer {
+    async fn transform(&self, program: &mut Program, ctx: &TransformContext<'_>) -> Result<()> {
+        let inner = self.inner.await?;
+        inner
+            .transform(program, ctx)
+            .instrument(tracing::info_span!("transform", name = self.name))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        fmt::Debug,
+        sync::{Arc, Mutex},
+    };
+
+    use anyhow::Result;
+    use tracing::{
+        field::{Field, Visit},
+        span::{Attributes, Id},
+        Subscriber,
+    };
+    use tracing_subscriber::{
+        layer::{Context, SubscriberExt},
+        registry::LookupSpan,
+        Layer,
+    };
+    use turbo_tasks::Value;
+    use turbopack_binding::{
+        turbo::tasks_fs::FileSystemPathVc,
+        turbopack::{
+            core::{
+                asset::Asset, compile_time_info::CompileTimeInfo, context::AssetContext,
+                reference_type::ReferenceType, source_asset::SourceAssetVc,
+            },
+            ecmascript::TransformPluginVc,
+            ecmascript_plugin::transform::styled_jsx::StyledJsxTransformer,
+            turbopack::{
+                evaluate_context::node_build_environment,
+                module_options::{
+                    CustomEcmascriptTransformPlugins, CustomEcmascriptTransformPluginsVc,
+                    ModuleOptionsContext,
+                },
+                resolve_options_context::ResolveOptionsContext,
+                transition::TransitionsByNameVc,
+                ModuleAssetContextVc,
+            },
+        },
+    };
+
+    use super::timed_transform_plugin;
+    use crate::test_util::run_in_project;
+
+    /// Records the `name` of every `transform` span.
+    #[derive(Clone, Default)]
+    struct TransformSpans(Arc<Mutex<Vec<String>>>);
+
+    struct NameVisitor<'a>(&'a mut Option<String>);
+
+    impl Visit for NameVisitor<'_> {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            if field.name() == "name" {
+                *self.0 = Some(value.to_string());
+            }
+        }
+
+        fn record_debug(&mut self, _field: &Field, _value: &dyn Debug) {}
+    }
+
+    impl<S> Layer<S> for TransformSpans
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        fn on_new_span(&self, attrs: &Attributes<'_>, _id: &Id, _ctx: Context<'_, S>) {
+            if attrs.metadata().name() != "transform" {
+                return;
+            }
+            let mut name = None;
+            attrs.record(&mut NameVisitor(&mut name));
+            self.0.lock().unwrap().extend(name);
+        }
+    }
+
+    /// Parses `index.js` in `project_dir` with the styled-jsx transform,
+    /// wrapped by [timed_transform_plugin].
+    async fn transform_module(root: FileSystemPathVc, time_transforms: bool) -> Result<()> {
+        let plugin = timed_transform_plugin(
+            "styled-jsx",
+            Some(TransformPluginVc::cell(Box::new(
+                StyledJsxTransformer::new(),
+            ))),
+            time_transforms,
+        );
+        let module_options_context = ModuleOptionsContext {
+            custom_ecma_transform_plugins: Some(CustomEcmascriptTransformPluginsVc::cell(
+                CustomEcmascriptTransformPlugins {
+                    source_transforms: plugin.into_iter().collect(),
+                    output_transforms: vec![],
+                },
+            )),
+            ..Default::default()
+        };
+        let context = ModuleAssetContextVc::new(
+            TransitionsByNameVc::cell(HashMap::new()),
+            CompileTimeInfo::builder(node_build_environment()).cell(),
+            module_options_context.cell(),
+            ResolveOptionsContext::default().cell(),
+        );
+        let module = context.process(
+            SourceAssetVc::new(root.join("index.js")).into(),
+            Value::new(ReferenceType::Undefined),
+        );
+        // The references are only known once the module is parsed and transformed.
+        module.references().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_timed_transform_plugin() -> Result<()> {
+        let spans = TransformSpans::default();
+        let _subscriber =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(spans.clone()));
+        let files = [("index.js", "export const answer = 42\n")];
+        run_in_project(&files, |root| transform_module(root, false)).await?;
+        assert!(spans.0.lock().unwrap().is_empty());
+
+        run_in_project(&files, |root| transform_module(root, true)).await?;
+        assert_eq!(*spans.0.lock().unwrap(), vec!["styled-jsx"]);
+        Ok(())
+    }
+}
@@ -0,0 +1,277 @@
+use anyhow::{anyhow, Result};
+use serde_json::Value as JsonValue;
+use sourcemap::SourceMap as RegularSourceMap;
+use swc_core::{
+    common::{sync::Lrc, FileName, SourceMap},
+    css::{
+        ast::Stylesheet,
+        codegen::{
+            writer::basic::{BasicCssWriter, BasicCssWriterConfig},
+            CodeGenerator, CodegenConfig, Emit,
+        },
+        parser::{parse_file, parser::ParserConfig},
+    },
+};
+use turbo_tasks::primitives::{BoolVc, StringVc};
+use turbopack_binding::{
+    turbo::tasks_fs::FileSystemPathVc,
+    turbopack::core::issue::{Issue, IssueSeverity, IssueSeverityVc, IssueVc},
+};
+
+use crate::{
+    mode::NextMode,
+    next_config::{NextConfig, NextConfigVc},
+};
+
+/// Returns whether emitted CSS is minified, which it is in production builds
+/// with `experimental.optimizeCss`. Like in Next.js, the option is either
+/// `true` or the options of critters.
+pub fn is_optimize_css_enabled(next_config: &NextConfig, mode: NextMode) -> bool {
+    mode == NextMode::Build
+        && match &next_config.experimental.optimize_css {
+            Some(JsonValue::Bool(enabled)) => *enabled,
+            Some(JsonValue::Object(_)) => true,
+            _ => false,
+        }
+}
+
+/// Returns whether emitted CSS is minified, see [is_optimize_css_enabled].
+/// Emits an issue if critters options are configured, because critical CSS
+/// inlining isn't supported.
+#[turbo_tasks::function]
+pub async fn optimize_css(
+    project_path: FileSystemPathVc,
+    next_config: NextConfigVc,
+    mode: NextMode,
+) -> Result<BoolVc> {
+    let next_config = next_config.await?;
+    let enabled = is_optimize_css_enabled(&next_config, mode);
+    if enabled
+        && matches!(
+            next_config.experimental.optimize_css,
+            Some(JsonValue::Object(_))
+        )
+    {
+        CrittersOptionsIgnoredIssue { path: project_path }
+            .cell()
+            .as_issue()
+            .emit();
+    }
+    Ok(BoolVc::cell(enabled))
+}
+
+/// A stylesheet minified by [minify_css].
+pub struct MinifiedCss {
+    pub code: String,
+    /// The JSON source map of the minified code, which maps to the sources of
+    /// the input source map if there was one.
+    pub source_map: String,
+}
+
+/// Minifies `css` with the code generator of the CSS parser used by Turbopack.
+/// `input_source_map` is the JSON source map of `css`, which the returned one
+/// is composed with.
+pub fn minify_css(
+    file_name: &str,
+    css: &str,
+    input_source_map: Option<&[u8]>,
+) -> Result<MinifiedCss> {
+    let input_source_map = input_source_map
+        .map(RegularSourceMap::from_slice)
+        .transpose()?;
+    let cm: Lrc<SourceMap> = Default::default();
+    let fm = cm.new_source_file(FileName::Custom(file_name.to_string()), css.to_string());
+    let mut errors = vec![];
+    let stylesheet: Stylesheet = parse_file(&fm, None, ParserConfig::default(), &mut errors)
+        .map_err(|err| anyhow!("failed to parse {}: {}", file_name, err.message()))?;
+    if let Some(err) = errors.first() {
+        return Err(anyhow!("failed to parse {}: {}", file_name, err.message()));
+    }
+
+    let mut code = String::new();
+    let mut mappings = vec![];
+    let writer = BasicCssWriter::new(
+        &mut code,
+        Some(&mut mappings),
+        BasicCssWriterConfig::default(),
+    );
+    CodeGenerator::new(writer, CodegenConfig { minify: true }).emit(&stylesheet)?;
+
+    let mut source_map = vec![];
+    cm.build_source_map_from(&mut mappings, input_source_map.as_ref())
+        .to_writer(&mut source_map)?;
+    Ok(MinifiedCss {
+        code,
+        source_map: String::from_utf8(source_map)?,
+    })
+}
+
+#[turbo_tasks::value(shared)]
+struct CrittersOptionsIgnoredIssue {
+    path: FileSystemPathVc,
+}
+
+#[turbo_tasks::value_impl]
+impl Issue for CrittersOptionsIgnoredIssue {
+    #[turbo_tasks::function]
+    fn severity(&self) -> IssueSeverityVc {
+        IssueSeverity::Warning.into()
+    }
+
+    #[turbo_tasks::function]
+    fn category(&self) -> StringVc {
+        StringVc::cell("config".to_string())
+    }
+
+    #[turbo_tasks::function]
+    fn context(&self) -> FileSystemPathVc {
+        self.path
+    }
+
+    #[turbo_tasks::function]
+    fn title(&self) -> StringVc {
+        StringVc::cell("The options of \"experimental.optimizeCss\" are ignored".to_string())
+    }
+
+    #[turbo_tasks::function]
+    fn description(&self) -> StringVc {
+        StringVc::cell(
+            "Inlining critical CSS with critters isn't supported yet, so its options have no \
+             effect. Stylesheets are still minified. Set \"experimental.optimizeCss\" to true to \
+             hide this warning."
+                .to_string(),
+        )
+    }
+}
+
+/// Reported for a stylesheet which is emitted as is, because it can't be
+/// minified.
+#[turbo_tasks::value(shared)]
+pub struct CssNotMinifiedIssue {
+    pub path: FileSystemPathVc,
+    /// Why the stylesheet can't be minified.
+    pub reason: String,
+}
+
+#[turbo_tasks::value_impl]
+impl Issue for CssNotMinifiedIssue {
+    #[turbo_tasks::function]
+    fn severity(&self) -> IssueSeverityVc {
+        IssueSeverity::Warning.into()
+    }
+
+    #[turbo_tasks::function]
+    fn category(&self) -> StringVc {
+        StringVc::cell("build".to_string())
+    }
+
+    #[turbo_tasks::function]
+    fn context(&self) -> FileSystemPathVc {
+        self.path
+    }
+
+    #[turbo_tasks::function]
+    fn title(&self) -> StringVc {
+        StringVc::cell("The stylesheet couldn't be minified".to_string())
+    }
+
+    #[turbo_tasks::function]
+    fn description(&self) -> StringVc {
+        StringVc::cell(format!(
+            "The stylesheet is emitted unminified: {}",
+            self.reason
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use sourcemap::{SourceMap, SourceMapBuilder};
+    use turbopack_binding::{
+        turbo::{
+            tasks::TurboTasks,
+            tasks_fs::{FileSystem, VirtualFileSystemVc},
+            tasks_memory::MemoryBackend,
+        },
+        turbopack::core::issue::IssueVc,
+    };
+
+    use super::{is_optimize_css_enabled, minify_css, optimize_css};
+    use crate::{mode::NextMode, next_config::NextConfig};
+
+    #[test]
+    fn test_optimize_css_enabled() {
+        let mut next_config = NextConfig::default();
+        assert!(!is_optimize_css_enabled(&next_config, NextMode::Build));
+
+        next_config.experimental.optimize_css = Some(json!(true));
+        assert!(is_optimize_css_enabled(&next_config, NextMode::Build));
+        assert!(!is_optimize_css_enabled(
+            &next_config,
+            NextMode::Development
+        ));
+
+        next_config.experimental.optimize_css = Some(json!({ "preload": "swap" }));
+        assert!(is_optimize_css_enabled(&next_config, NextMode::Build));
+    }
+
+    #[tokio::test]
+    async fn test_critters_options_issue() -> anyhow::Result<()> {
+        crate::register();
+        let tt = TurboTasks::new(MemoryBackend::default());
+        tt.run_once(async move {
+            let root = VirtualFileSystemVc::new().as_file_system().root();
+            let mut titles = Vec::new();
+            for optimize_css_config in [json!(true), json!({ "preload": "swap" })] {
+                let mut next_config = NextConfig::default();
+                next_config.experimental.optimize_css = Some(optimize_css_config);
+                let enabled = optimize_css(root, next_config.cell(), NextMode::Build);
+                assert!(*enabled.await?);
+
+                let issues = IssueVc::peek_issues_with_path(enabled)
+                    .await?
+                    .strongly_consistent()
+                    .await?;
+                let mut config_titles = Vec::new();
+                for issue in issues.iter() {
+                    config_titles.push(issue.title().await?.clone_value());
+                }
+                titles.push(config_titles);
+            }
+            assert_eq!(
+                titles,
+                [
+                    vec![],
+                    vec!["The options of \"experimental.optimizeCss\" are ignored".to_string()]
+                ]
+            );
+            Ok(())
+        })
+        .await
+    }
+
+    #[test]
+    fn test_minify_css() -> anyhow::Result<()> {
+        let css = "/* comment */\n.a, .b > .c {\n  color: red;\n  content: \"a  ;  b\";\n}\n";
+        // The input source map maps the `color` declaration to its original file.
+        let mut builder = SourceMapBuilder::new(None);
+        let source = builder.add_source("styles/a.css");
+        builder.add_raw(2, 2, 10, 4, Some(source), None);
+        let mut input_source_map = vec![];
+        builder.into_sourcemap().to_writer(&mut input_source_map)?;
+
+        let minified = minify_css("a.css", css, Some(&input_source_map))?;
+        assert!(!minified.code.contains('\n'));
+        assert!(!minified.code.contains("comment"));
+        assert!(minified.code.contains("color:red"));
+        assert!(minified.code.contains("content:\"a  ;  b\""));
+
+        let color = minified.code.find("color").unwrap() as u32;
+        let source_map = SourceMap::from_slice(minified.source_map.as_bytes())?;
+        let token = source_map.lookup_token(0, color).unwrap();
+        assert_eq!(token.get_source(), Some("styles/a.css"));
+        assert_eq!(token.get_src(), (10, 4));
+        Ok(())
+    }
+}
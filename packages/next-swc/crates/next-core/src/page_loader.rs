@@ -25,6 +25,15 @@ use turbopack_binding::{
 
 use crate::{embed_js::next_js_file_path, util::get_asset_path_from_pathname};
 
+/// Returns the path the page loader of the page at `pathname` is served at,
+/// relative to the client root.
+pub fn page_loader_path(pathname: &str) -> String {
+    format!(
+        "_next/static/chunks/pages{}",
+        get_asset_path_from_pathname(pathname, ".js")
+    )
+}
+
 #[turbo_tasks::function]
 pub async fn create_page_loader(
     server_root: FileSystemPathVc,
@@ -127,10 +136,10 @@ fn page_loader_chunk_reference_description() -> StringVc {
 impl Asset for PageLoaderAsset {
     #[turbo_tasks::function]
     async fn ident(&self) -> Result<AssetIdentVc> {
-        Ok(AssetIdentVc::from_path(self.server_root.join(&format!(
-            "_next/static/chunks/pages{}",
-            get_asset_path_from_pathname(&self.pathname.await?, ".js")
-        ))))
+        Ok(AssetIdentVc::from_path(
+            self.server_root
+                .join(&page_loader_path(&self.pathname.await?)),
+        ))
     }
 
     #[turbo_tasks::function]
@@ -30,7 +30,6 @@ use turbopack_binding::{
                 ContentSourceData, ContentSourceVc,
             },
         },
-        env::ProcessEnvAssetVc,
         node::{
             debug::should_debug,
             execution_context::ExecutionContextVc,
@@ -70,7 +69,8 @@ use crate::{
     },
     next_server::context::{
         get_server_compile_time_info, get_server_module_options_context,
-        get_server_resolve_options_context, ServerContextType,
+        get_server_resolve_options_context, get_server_runtime_entries, ResolvePluginsVc,
+        ServerContextType,
     },
     page_loader::create_page_loader,
     pages_structure::{
@@ -171,17 +171,20 @@ pub async fn create_page_source(
         base_path: project_root,
         bootstrap_asset: next_asset("entry/edge-bootstrap.ts"),
         entry_name: "edge".to_string(),
+        polyfills: AssetsVc::cell(vec![]),
     }
     .cell()
     .into();
 
-    let server_compile_time_info = get_server_compile_time_info(mode, env, server_addr);
+    let server_compile_time_info =
+        get_server_compile_time_info(project_root, mode, env, server_addr, next_config);
     let server_resolve_options_context = get_server_resolve_options_context(
         project_root,
         server_ty,
         mode,
         next_config,
         execution_context,
+        ResolvePluginsVc::empty(),
     );
 
     let server_module_options_context = get_server_module_options_context(
@@ -248,9 +251,7 @@ pub async fn create_page_source(
     let env = CustomProcessEnvVc::new(env, next_config.env()).as_process_env();
 
     let server_runtime_entries =
-        AssetsVc::cell(vec![
-            ProcessEnvAssetVc::new(project_root, injected_env).into()
-        ]);
+        get_server_runtime_entries(project_root, injected_env, next_config);
     let fallback_runtime_entries = AssetsVc::cell(vec![]);
 
     let fallback_page = get_fallback_page(
@@ -372,13 +372,17 @@ async fn get_pages_structure_for_directory(
     .cell())
 }
 
-fn page_basename<'a>(name: &'a str, page_extensions: &'a [String]) -> Option<&'a str> {
-    if let Some((basename, extension)) = name.rsplit_once('.') {
-        if page_extensions.iter().any(|allowed| allowed == extension) {
-            return Some(basename);
-        }
-    }
-    None
+/// Returns the file `name` without its page extension, or `None` if it isn't
+/// a page. Page extensions can contain dots, like `page.tsx`, and only the
+/// longest one matching is stripped, so `feed.js.tsx` is the page `feed.js`.
+fn page_basename<'a>(name: &'a str, page_extensions: &[String]) -> Option<&'a str> {
+    page_extensions
+        .iter()
+        .filter_map(|extension| {
+            let basename = name.strip_suffix(extension.as_str())?.strip_suffix('.')?;
+            (!basename.is_empty()).then_some(basename)
+        })
+        .min_by_key(|basename| basename.len())
 }
 
 fn next_router_path_for_basename(
@@ -391,3 +395,27 @@ fn next_router_path_for_basename(
         next_router_path.join(basename)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::page_basename;
+
+    #[test]
+    fn test_page_basename() {
+        let page_extensions = ["tsx", "js", "mdx", "page.tsx"].map(ToString::to_string);
+
+        assert_eq!(page_basename("intro.mdx", &page_extensions), Some("intro"));
+        assert_eq!(
+            page_basename("about.page.tsx", &page_extensions),
+            Some("about")
+        );
+        assert_eq!(page_basename("about.tsx", &page_extensions), Some("about"));
+        // Only the page extension is stripped, the route keeps the rest.
+        assert_eq!(
+            page_basename("feed.js.tsx", &page_extensions),
+            Some("feed.js")
+        );
+        assert_eq!(page_basename("notes.md", &page_extensions), None);
+        assert_eq!(page_basename(".mdx", &page_extensions), None);
+    }
+}
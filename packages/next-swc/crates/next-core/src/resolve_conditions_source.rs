@@ -0,0 +1,116 @@
+use anyhow::{bail, Result};
+use indexmap::IndexMap;
+use mime::APPLICATION_JSON;
+use turbo_tasks::{primitives::StringVc, Value};
+use turbopack_binding::{
+    turbo::tasks_fs::File,
+    turbopack::{
+        core::{
+            asset::AssetContentVc,
+            introspect::{Introspectable, IntrospectableVc},
+        },
+        dev_server::source::{
+            route_tree::{BaseSegment, RouteTreeVc, RouteType},
+            ContentSource, ContentSourceContentVc, ContentSourceData, GetContentSourceContent,
+            GetContentSourceContentVc,
+        },
+    },
+};
+
+use crate::{mode::NextMode, next_server::ServerContextKind};
+
+pub const RESOLVE_CONDITIONS_PATHNAME: &str = "_next/debug/resolve-conditions.json";
+
+/// A content source which serves `_next/debug/resolve-conditions.json`,
+/// listing the custom resolve conditions active in each server context. This
+/// helps debugging which package export was picked for a context.
+#[turbo_tasks::value(shared)]
+pub struct ResolveConditionsContentSource;
+
+#[turbo_tasks::value_impl]
+impl ResolveConditionsContentSourceVc {
+    #[turbo_tasks::function]
+    pub fn new() -> Self {
+        ResolveConditionsContentSource.cell()
+    }
+}
+
+/// Maps every server context to its resolve conditions in the given mode.
+fn resolve_conditions(mode: NextMode) -> IndexMap<&'static str, Vec<String>> {
+    ServerContextKind::ALL
+        .iter()
+        .map(|kind| (kind.as_str(), kind.resolve_conditions(mode)))
+        .collect()
+}
+
+#[turbo_tasks::value_impl]
+impl ContentSource for ResolveConditionsContentSource {
+    #[turbo_tasks::function]
+    fn get_routes(self_vc: ResolveConditionsContentSourceVc) -> RouteTreeVc {
+        RouteTreeVc::new_route(
+            BaseSegment::from_static_pathname(RESOLVE_CONDITIONS_PATHNAME).collect(),
+            RouteType::Exact,
+            self_vc.into(),
+        )
+    }
+}
+
+#[turbo_tasks::value_impl]
+impl GetContentSourceContent for ResolveConditionsContentSource {
+    #[turbo_tasks::function]
+    async fn get(
+        &self,
+        path: &str,
+        _data: Value<ContentSourceData>,
+    ) -> Result<ContentSourceContentVc> {
+        if path != RESOLVE_CONDITIONS_PATHNAME {
+            bail!("unknown path: {}", path);
+        }
+
+        // This source is only served by the dev server.
+        let file = File::from(serde_json::to_string(&resolve_conditions(
+            NextMode::Development,
+        ))?)
+        .with_content_type(APPLICATION_JSON);
+
+        Ok(ContentSourceContentVc::static_content(
+            AssetContentVc::from(file).into(),
+        ))
+    }
+}
+
+#[turbo_tasks::value_impl]
+impl Introspectable for ResolveConditionsContentSource {
+    #[turbo_tasks::function]
+    fn ty(&self) -> StringVc {
+        StringVc::cell("resolve conditions source".to_string())
+    }
+
+    #[turbo_tasks::function]
+    fn details(&self) -> StringVc {
+        let details = resolve_conditions(NextMode::Development)
+            .into_iter()
+            .map(|(context, conditions)| format!("{}: {}", context, conditions.join(", ")))
+            .collect::<Vec<_>>()
+            .join("\n");
+        StringVc::cell(details)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::resolve_conditions;
+    use crate::mode::NextMode;
+
+    #[test]
+    fn test_app_rsc_lists_react_server() {
+        let conditions = resolve_conditions(NextMode::Development);
+
+        assert_eq!(
+            conditions["AppRSC"],
+            vec!["development", "react-server", "node"]
+        );
+        assert!(!conditions["AppSSR"].contains(&"react-server".to_string()));
+        assert_eq!(conditions["Middleware"], vec!["development"]);
+    }
+}
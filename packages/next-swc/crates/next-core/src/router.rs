@@ -50,7 +50,9 @@ use crate::{
         route_transition::NextEdgeRouteTransition,
     },
     next_import_map::get_next_build_import_map,
-    next_server::context::{get_server_module_options_context, ServerContextType},
+    next_server::context::{
+        get_edge_polyfills, get_server_module_options_context, ServerContextType,
+    },
     util::{parse_config_from_source, NextSourceConfigVc},
 };
 
@@ -276,6 +278,11 @@ fn edge_transition_map(
         base_path: project_path,
         bootstrap_asset: next_asset("entry/edge-bootstrap.ts"),
         entry_name: "middleware".to_string(),
+        polyfills: get_edge_polyfills(
+            project_path,
+            Value::new(ServerContextType::Middleware),
+            next_config,
+        ),
     }
     .cell()
     .into();
@@ -0,0 +1,68 @@
+//! Helpers for tests which run turbo-tasks functions against a project on
+//! disk.
+
+use std::{fs, future::Future, path::Path};
+
+use anyhow::Result;
+use tempfile::TempDir;
+use turbopack_binding::{
+    turbo::{
+        tasks::TurboTasks,
+        tasks_env::EnvMapVc,
+        tasks_fs::{DiskFileSystemVc, FileSystem, FileSystemPathVc, VirtualFileSystemVc},
+        tasks_memory::MemoryBackend,
+    },
+    turbopack::{
+        dev::DevChunkingContextVc, node::execution_context::ExecutionContextVc,
+        turbopack::evaluate_context::node_build_environment,
+    },
+};
+
+/// Writes `files`, pairs of a path relative to the project directory and its
+/// content, to a new temporary project directory.
+pub fn write_project(files: &[(&str, &str)]) -> Result<TempDir> {
+    let dir = tempfile::tempdir()?;
+    for (path, content) in files {
+        let path = dir.path().join(path);
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(path, content)?;
+    }
+    Ok(dir)
+}
+
+/// Returns the root of the project directory `dir`.
+pub fn project_root(dir: &Path) -> FileSystemPathVc {
+    DiskFileSystemVc::new("project".to_string(), dir.to_string_lossy().to_string()).root()
+}
+
+/// Writes `files` to a new temporary project directory and runs `test` with
+/// the root of the project in a fresh turbo-tasks instance, so no task is
+/// cached across tests.
+pub async fn run_in_project<T, F, Fut>(files: &[(&str, &str)], test: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce(FileSystemPathVc) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<T>> + Send + 'static,
+{
+    crate::register();
+    let project = write_project(files)?;
+    let project_dir = project.path().to_path_buf();
+    let tt = TurboTasks::new(MemoryBackend::default());
+    tt.run_once(async move { test(project_root(&project_dir)).await })
+        .await
+}
+
+/// Returns an execution context for the project at `project_path`, whose
+/// output is never written.
+pub fn execution_context(project_path: FileSystemPathVc) -> ExecutionContextVc {
+    let output_root = VirtualFileSystemVc::new().as_file_system().root();
+    let chunking_context = DevChunkingContextVc::builder(
+        project_path,
+        output_root,
+        output_root.join("chunks"),
+        output_root.join("assets"),
+        node_build_environment(),
+    )
+    .build();
+    ExecutionContextVc::new(project_path, chunking_context, EnvMapVc::empty().into())
+}
@@ -1,9 +1,17 @@
 use anyhow::Result;
+use indexmap::IndexMap;
+use serde_json::Value as JsonValue;
+use turbo_tasks::{
+    primitives::{BoolVc, JsonValueVc, StringVc},
+    Value,
+};
 use turbopack_binding::{
-    turbo::tasks_fs::{FileJsonContentVc, FileSystemPathVc},
+    turbo::tasks_fs::{FileContent, FileJsonContentVc, FileSystemPathVc},
     turbopack::{
         core::{
             asset::AssetVc,
+            environment::{BrowserEnvironment, EnvironmentVc, ExecutionEnvironment},
+            issue::{Issue, IssueSeverity, IssueSeverityVc, IssueVc},
             resolve::{find_context_file, node::node_cjs_resolve_options, FindContextFileResult},
             source_asset::SourceAssetVc,
         },
@@ -19,7 +27,29 @@ use turbopack_binding::{
     },
 };
 
-use crate::mode::NextMode;
+use crate::{
+    mode::NextMode,
+    next_config::{merged_resolve_alias, NextConfigVc},
+};
+
+/// Returns the JSX import source matching the `react` alias in
+/// `experimental.turbo.resolveAlias`, so JSX compiles to the `jsx-runtime` of
+/// the aliased package. This is only done for Preact (`preact/compat`), other
+/// aliases keep using React's runtime.
+fn jsx_import_source_for_alias(
+    resolve_alias: Option<&IndexMap<String, JsonValue>>,
+) -> Option<String> {
+    let react_alias = resolve_alias?.get("react")?.as_str()?;
+    (react_alias.split('/').next() == Some("preact")).then(|| "preact".to_string())
+}
+
+/// Returns whether `react` is aliased to Preact, see
+/// [jsx_import_source_for_alias]. React Refresh only works with React, so it's
+/// disabled then.
+pub async fn is_react_aliased_to_preact(next_config: NextConfigVc) -> Result<bool> {
+    let resolve_alias = merged_resolve_alias(&*next_config.await?);
+    Ok(jsx_import_source_for_alias(resolve_alias.as_ref()).is_some())
+}
 
 async fn get_typescript_options(
     project_path: FileSystemPathVc,
@@ -39,26 +69,272 @@ async fn get_typescript_options(
     }
 }
 
-/// Build the transform options for specifically for the typescript's runtime
-/// outputs
+/// Returns whether class fields are compiled with `[[Define]]` semantics
+/// according to a `.swcrc`. An explicit `jsc.transform.useDefineForClassFields`
+/// wins, otherwise it follows `jsc.target` like TypeScript does: targets from
+/// ES2022 on have native class fields.
+fn swcrc_use_define_for_class_fields(swcrc: &JsonValue) -> Option<bool> {
+    let jsc = &swcrc["jsc"];
+    if let Some(value) = jsc["transform"]["useDefineForClassFields"].as_bool() {
+        return Some(value);
+    }
+    match jsc["target"].as_str()?.to_ascii_lowercase().as_str() {
+        "es2022" | "esnext" => Some(true),
+        "es3" | "es5" | "es2015" | "es2016" | "es2017" | "es2018" | "es2019" | "es2020"
+        | "es2021" => Some(false),
+        _ => None,
+    }
+}
+
+/// Returns the browserslist query of the oldest browsers supporting all
+/// features of the `jsc.target` of a `.swcrc`, so code can be compiled down
+/// to it with preset-env. Targets from ES2022 on don't need to be compiled.
+fn swcrc_target_query(swcrc: &JsonValue) -> Option<&'static str> {
+    Some(
+        match swcrc["jsc"]["target"]
+            .as_str()?
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "es3" | "es5" => "ie 11",
+            "es2015" => "chrome 51, edge 15, firefox 54, safari 10",
+            "es2016" => "chrome 52, edge 15, firefox 52, safari 10.1",
+            "es2017" => "chrome 58, edge 16, firefox 53, safari 11",
+            "es2018" => "chrome 64, edge 79, firefox 58, safari 11.1",
+            "es2019" => "chrome 73, edge 79, firefox 64, safari 12.1",
+            "es2020" => "chrome 80, edge 80, firefox 80, safari 14.1",
+            "es2021" => "chrome 85, edge 85, firefox 79, safari 14.1",
+            _ => return None,
+        },
+    )
+}
+
+/// Removes the `//` and `/* */` comments from the JSON `content` of a
+/// `.swcrc`, which swc allows. Comment markers in strings are kept.
+fn strip_json_comments(content: &str) -> String {
+    let mut stripped = String::with_capacity(content.len());
+    let mut chars = content.chars().peekable();
+    let mut in_string = false;
+    while let Some(c) = chars.next() {
+        if in_string {
+            stripped.push(c);
+            match c {
+                '\\' => stripped.extend(chars.next()),
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match (c, chars.peek()) {
+            ('"', _) => {
+                in_string = true;
+                stripped.push(c);
+            }
+            ('/', Some('/')) => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        stripped.push(c);
+                        break;
+                    }
+                }
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                let mut previous = None;
+                for c in chars.by_ref() {
+                    if previous == Some('*') && c == '/' {
+                        break;
+                    }
+                    previous = Some(c);
+                }
+                stripped.push(' ');
+            }
+            _ => stripped.push(c),
+        }
+    }
+    stripped
+}
+
+/// Reads the `.swcrc` in the project root, if `experimental.swcrc` is enabled
+/// and there is one, and `null` otherwise. Like swc, comments are allowed. An
+/// issue is emitted if it can't be parsed, and it's ignored then.
 #[turbo_tasks::function]
-pub async fn get_typescript_transform_options(
+async fn read_swcrc(
     project_path: FileSystemPathVc,
-) -> Result<TypescriptTransformOptionsVc> {
-    let tsconfig = get_typescript_options(project_path).await;
+    next_config: NextConfigVc,
+) -> Result<JsonValueVc> {
+    if !*next_config.swcrc().await? {
+        return Ok(JsonValueVc::cell(JsonValue::Null));
+    }
+    let path = project_path.join(".swcrc");
+    let FileContent::Content(file) = &*path.read().await? else {
+        return Ok(JsonValueVc::cell(JsonValue::Null));
+    };
+    match serde_json::from_str(&strip_json_comments(&file.content().to_str()?)) {
+        Ok(swcrc) => Ok(JsonValueVc::cell(swcrc)),
+        Err(err) => {
+            SwcrcParseIssue {
+                path,
+                error: err.to_string(),
+            }
+            .cell()
+            .as_issue()
+            .emit();
+            Ok(JsonValueVc::cell(JsonValue::Null))
+        }
+    }
+}
 
-    let use_define_for_class_fields = if let Some(tsconfig) = tsconfig {
+#[turbo_tasks::value(transparent)]
+pub struct OptionEnvironment(Option<EnvironmentVc>);
+
+/// Returns the environment app code is compiled down to according to the
+/// `jsc.target` of the `.swcrc`, if it sets one before ES2022.
+#[turbo_tasks::function]
+pub async fn get_swcrc_target_environment(
+    project_path: FileSystemPathVc,
+    next_config: NextConfigVc,
+) -> Result<OptionEnvironmentVc> {
+    let Some(query) = swcrc_target_query(&*read_swcrc(project_path, next_config).await?) else {
+        return Ok(OptionEnvironmentVc::cell(None));
+    };
+    Ok(OptionEnvironmentVc::cell(Some(EnvironmentVc::new(
+        Value::new(ExecutionEnvironment::Browser(
+            BrowserEnvironment {
+                dom: false,
+                web_worker: false,
+                service_worker: false,
+                browserslist_query: query.to_string(),
+            }
+            .into(),
+        )),
+    ))))
+}
+
+/// Returns whether class fields are compiled with `[[Define]]` semantics. The
+/// `tsconfig.json` takes precedence over the `.swcrc`, and an issue is emitted
+/// when both set it differently.
+#[turbo_tasks::function]
+async fn use_define_for_class_fields(
+    project_path: FileSystemPathVc,
+    next_config: NextConfigVc,
+) -> Result<BoolVc> {
+    let tsconfig_value = if let Some(tsconfig) = get_typescript_options(project_path).await {
         read_from_tsconfigs(&tsconfig, |json, _| {
             json["compilerOptions"]["useDefineForClassFields"].as_bool()
         })
         .await?
-        .unwrap_or(false)
     } else {
-        false
+        None
     };
+    let swcrc_value =
+        swcrc_use_define_for_class_fields(&*read_swcrc(project_path, next_config).await?);
+
+    if let (Some(tsconfig_value), Some(swcrc_value)) = (tsconfig_value, swcrc_value) {
+        if tsconfig_value != swcrc_value {
+            SwcrcConflictIssue {
+                path: project_path.join(".swcrc"),
+                setting: "useDefineForClassFields".to_string(),
+            }
+            .cell()
+            .as_issue()
+            .emit();
+        }
+    }
+
+    Ok(BoolVc::cell(
+        tsconfig_value.or(swcrc_value).unwrap_or(false),
+    ))
+}
+
+#[turbo_tasks::value(shared)]
+struct SwcrcConflictIssue {
+    path: FileSystemPathVc,
+    setting: String,
+}
+
+#[turbo_tasks::value_impl]
+impl Issue for SwcrcConflictIssue {
+    #[turbo_tasks::function]
+    fn severity(&self) -> IssueSeverityVc {
+        IssueSeverity::Warning.into()
+    }
+
+    #[turbo_tasks::function]
+    fn category(&self) -> StringVc {
+        StringVc::cell("config".to_string())
+    }
+
+    #[turbo_tasks::function]
+    fn context(&self) -> FileSystemPathVc {
+        self.path
+    }
+
+    #[turbo_tasks::function]
+    fn title(&self) -> StringVc {
+        StringVc::cell(format!(
+            "\"{}\" in .swcrc conflicts with tsconfig.json",
+            self.setting
+        ))
+    }
+
+    #[turbo_tasks::function]
+    fn description(&self) -> StringVc {
+        StringVc::cell(
+            "The setting of tsconfig.json is used. Remove it from one of the files to silence \
+             this warning."
+                .to_string(),
+        )
+    }
+}
+
+#[turbo_tasks::value(shared)]
+struct SwcrcParseIssue {
+    path: FileSystemPathVc,
+    error: String,
+}
+
+#[turbo_tasks::value_impl]
+impl Issue for SwcrcParseIssue {
+    #[turbo_tasks::function]
+    fn severity(&self) -> IssueSeverityVc {
+        IssueSeverity::Warning.into()
+    }
 
+    #[turbo_tasks::function]
+    fn category(&self) -> StringVc {
+        StringVc::cell("config".to_string())
+    }
+
+    #[turbo_tasks::function]
+    fn context(&self) -> FileSystemPathVc {
+        self.path
+    }
+
+    #[turbo_tasks::function]
+    fn title(&self) -> StringVc {
+        StringVc::cell(".swcrc can't be parsed".to_string())
+    }
+
+    #[turbo_tasks::function]
+    fn description(&self) -> StringVc {
+        StringVc::cell(format!(
+            "Its settings are ignored: {}. \".swcrc\" must be JSON, optionally with comments.",
+            self.error
+        ))
+    }
+}
+
+/// Build the transform options for specifically for the typescript's runtime
+/// outputs
+#[turbo_tasks::function]
+pub async fn get_typescript_transform_options(
+    project_path: FileSystemPathVc,
+    next_config: NextConfigVc,
+) -> Result<TypescriptTransformOptionsVc> {
     let ts_transform_options = TypescriptTransformOptions {
-        use_define_for_class_fields,
+        use_define_for_class_fields: *use_define_for_class_fields(project_path, next_config)
+            .await?,
     };
 
     Ok(ts_transform_options.cell())
@@ -69,8 +345,11 @@ pub async fn get_typescript_transform_options(
 #[turbo_tasks::function]
 pub async fn get_decorators_transform_options(
     project_path: FileSystemPathVc,
+    next_config: NextConfigVc,
 ) -> Result<DecoratorsOptionsVc> {
     let tsconfig = get_typescript_options(project_path).await;
+    let use_define_for_class_fields =
+        *use_define_for_class_fields(project_path, next_config).await?;
 
     let decorators_transform_options = if let Some(tsconfig) = tsconfig {
         read_from_tsconfigs(&tsconfig, |json, _| {
@@ -107,16 +386,17 @@ pub async fn get_decorators_transform_options(
             Some(DecoratorsOptions {
                 decorators_kind,
                 emit_decorators_metadata,
-                use_define_for_class_fields: json["compilerOptions"]["useDefineForClassFields"]
-                    .as_bool()
-                    .unwrap_or(false),
+                use_define_for_class_fields,
                 ..Default::default()
             })
         })
         .await?
         .unwrap_or_default()
     } else {
-        Default::default()
+        DecoratorsOptions {
+            use_define_for_class_fields,
+            ..Default::default()
+        }
     };
 
     Ok(decorators_transform_options.cell())
@@ -127,15 +407,19 @@ pub async fn get_jsx_transform_options(
     project_path: FileSystemPathVc,
     mode: NextMode,
     resolve_options_context: Option<ResolveOptionsContextVc>,
+    next_config: NextConfigVc,
 ) -> Result<JsxTransformOptionsVc> {
     let tsconfig = get_typescript_options(project_path).await;
+    let resolve_alias = merged_resolve_alias(&*next_config.await?);
+    let import_source = jsx_import_source_for_alias(resolve_alias.as_ref());
 
-    let enable_react_refresh = if let Some(resolve_options_context) = resolve_options_context {
-        assert_can_resolve_react_refresh(project_path, resolve_options_context)
-            .await?
-            .is_found()
-    } else {
-        false
+    let enable_react_refresh = match resolve_options_context {
+        Some(resolve_options_context) if import_source.is_none() => {
+            assert_can_resolve_react_refresh(project_path, resolve_options_context)
+                .await?
+                .is_found()
+        }
+        _ => false,
     };
 
     // [NOTE]: ref: WEB-901
@@ -144,7 +428,7 @@ pub async fn get_jsx_transform_options(
     // [TODO]: we need to emit / validate config message like next.js devserver does
     let react_transform_options = JsxTransformOptions {
         development: mode.is_react_development(),
-        import_source: None,
+        import_source,
         runtime: Some("automatic".to_string()),
         react_refresh: enable_react_refresh,
     };
@@ -156,7 +440,8 @@ pub async fn get_jsx_transform_options(
                 .map(|s| s.to_string());
 
             Some(JsxTransformOptions {
-                import_source: jsx_import_source,
+                import_source: jsx_import_source
+                    .or_else(|| react_transform_options.import_source.clone()),
                 ..react_transform_options.clone()
             })
         })
@@ -168,3 +453,223 @@ pub async fn get_jsx_transform_options(
 
     Ok(react_transform_options.cell())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use anyhow::Result;
+    use indexmap::IndexMap;
+    use serde_json::json;
+    use swc_core::ecma::{
+        ast::ArrowExpr,
+        visit::{Visit, VisitWith},
+    };
+    use turbo_tasks::Value;
+    use turbopack_binding::turbopack::{
+        core::{
+            compile_time_info::CompileTimeInfo, context::AssetContext, issue::IssueVc,
+            reference_type::ReferenceType, source_asset::SourceAssetVc,
+        },
+        ecmascript::{parse::ParseResult, EcmascriptModuleAssetVc},
+        turbopack::{
+            evaluate_context::node_build_environment, module_options::ModuleOptionsContext,
+            resolve_options_context::ResolveOptionsContext, transition::TransitionsByNameVc,
+            ModuleAssetContextVc,
+        },
+    };
+
+    use super::{
+        get_jsx_transform_options, get_swcrc_target_environment, jsx_import_source_for_alias,
+        strip_json_comments, swcrc_target_query, swcrc_use_define_for_class_fields,
+    };
+    use crate::{
+        mode::NextMode, next_config::NextConfig, next_shared::transforms::get_swcrc_target_rule,
+        test_util::run_in_project,
+    };
+
+    #[derive(Default)]
+    struct ArrowCount(usize);
+
+    impl Visit for ArrowCount {
+        fn visit_arrow_expr(&mut self, arrow: &ArrowExpr) {
+            self.0 += 1;
+            arrow.visit_children_with(self);
+        }
+    }
+
+    /// Compiles `index.js` of a project of `files` with the `.swcrc` target
+    /// rule, and returns the number of arrow functions left and the titles of
+    /// the issues emitted while reading the `.swcrc`.
+    async fn compile_with_swcrc(files: &[(&str, &str)]) -> Result<(usize, Vec<String>)> {
+        run_in_project(files, |root| async move {
+            let mut next_config = NextConfig::default();
+            next_config.experimental.swcrc = Some(true);
+            let next_config = next_config.cell();
+            let issues =
+                IssueVc::peek_issues_with_path(get_swcrc_target_environment(root, next_config))
+                    .await?
+                    .strongly_consistent()
+                    .await?;
+            let mut titles = Vec::new();
+            for issue in issues.iter() {
+                titles.push(issue.title().await?.clone_value());
+            }
+
+            let custom_rules = get_swcrc_target_rule(root, next_config)
+                .await?
+                .into_iter()
+                .collect();
+            let context = ModuleAssetContextVc::new(
+                TransitionsByNameVc::cell(HashMap::new()),
+                CompileTimeInfo::builder(node_build_environment()).cell(),
+                ModuleOptionsContext {
+                    custom_rules,
+                    ..Default::default()
+                }
+                .cell(),
+                ResolveOptionsContext::default().cell(),
+            );
+            let module = context.process(
+                SourceAssetVc::new(root.join("index.js")).into(),
+                Value::new(ReferenceType::Undefined),
+            );
+            let module = EcmascriptModuleAssetVc::resolve_from(module)
+                .await?
+                .unwrap();
+            let ParseResult::Ok { program, .. } = &*module.parse().await? else {
+                anyhow::bail!("index.js can't be parsed");
+            };
+            let mut arrows = ArrowCount::default();
+            program.visit_with(&mut arrows);
+            Ok((arrows.0, titles))
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_swcrc_target_compiles_down() -> Result<()> {
+        let index = ("index.js", "export const double = (n) => n * 2\n");
+        assert_eq!(compile_with_swcrc(&[index]).await?, (1, vec![]));
+
+        // Like swc, the .swcrc may contain comments.
+        let swcrc = (
+            ".swcrc",
+            "{\n  // Compiled for old browsers\n  \"jsc\": { \"target\": \"es5\" }\n}\n",
+        );
+        assert_eq!(compile_with_swcrc(&[index, swcrc]).await?, (0, vec![]));
+
+        assert_eq!(
+            compile_with_swcrc(&[index, (".swcrc", "{ \"jsc\": ")]).await?,
+            (1, vec![".swcrc can't be parsed".to_string()])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_strip_json_comments() {
+        let stripped = strip_json_comments(
+            "{\n  // target\n  \"jsc\": { /* es5 */ \"target\": \"es5\", \"url\": \"http://a/*b*/\" }\n}",
+        );
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&stripped).unwrap(),
+            json!({ "jsc": { "target": "es5", "url": "http://a/*b*/" } })
+        );
+    }
+
+    #[test]
+    fn test_swcrc_target_query() {
+        assert_eq!(
+            swcrc_target_query(&json!({ "jsc": { "target": "ES5" } })),
+            Some("ie 11")
+        );
+        assert_eq!(
+            swcrc_target_query(&json!({ "jsc": { "target": "es2022" } })),
+            None
+        );
+        assert_eq!(swcrc_target_query(&json!({})), None);
+    }
+
+    #[test]
+    fn test_swcrc_target() {
+        assert_eq!(
+            swcrc_use_define_for_class_fields(&json!({ "jsc": { "target": "es2022" } })),
+            Some(true)
+        );
+        assert_eq!(
+            swcrc_use_define_for_class_fields(&json!({ "jsc": { "target": "es2017" } })),
+            Some(false)
+        );
+        assert_eq!(
+            swcrc_use_define_for_class_fields(&json!({
+                "jsc": {
+                    "target": "es2017",
+                    "transform": { "useDefineForClassFields": true }
+                }
+            })),
+            Some(true)
+        );
+        assert_eq!(swcrc_use_define_for_class_fields(&json!({})), None);
+    }
+
+    #[test]
+    fn test_preact_jsx_import_source() {
+        let resolve_alias = IndexMap::from([("react".to_string(), json!("preact/compat"))]);
+        let import_source = jsx_import_source_for_alias(Some(&resolve_alias)).unwrap();
+        assert_eq!(
+            format!("{}/jsx-runtime", import_source),
+            "preact/jsx-runtime"
+        );
+
+        let resolve_alias = IndexMap::from([("react".to_string(), json!("./vendor/react"))]);
+        assert_eq!(jsx_import_source_for_alias(Some(&resolve_alias)), None);
+        assert_eq!(jsx_import_source_for_alias(None), None);
+    }
+
+    #[tokio::test]
+    async fn test_preact_jsx_transform_options() -> Result<()> {
+        run_in_project(
+            &[
+                (
+                    "node_modules/@next/react-refresh-utils/package.json",
+                    r#"{ "name": "@next/react-refresh-utils" }"#,
+                ),
+                (
+                    "node_modules/@next/react-refresh-utils/dist/runtime.js",
+                    "export {}\n",
+                ),
+            ],
+            |root| async move {
+                let resolve_options_context = ResolveOptionsContext {
+                    enable_node_modules: Some(root),
+                    ..Default::default()
+                }
+                .cell();
+                let jsx_options = |next_config: NextConfig| {
+                    get_jsx_transform_options(
+                        root,
+                        NextMode::Development,
+                        Some(resolve_options_context),
+                        next_config.cell(),
+                    )
+                };
+
+                let options = jsx_options(NextConfig::default()).await?;
+                assert_eq!(options.import_source, None);
+                assert!(options.react_refresh);
+
+                let next_config = NextConfig {
+                    experimental: serde_json::from_value(json!({
+                        "turbo": { "resolveAlias": { "react": "preact/compat" } },
+                    }))?,
+                    ..Default::default()
+                };
+                let options = jsx_options(next_config).await?;
+                assert_eq!(options.import_source.as_deref(), Some("preact"));
+                assert!(!options.react_refresh);
+                Ok(())
+            },
+        )
+        .await
+    }
+}
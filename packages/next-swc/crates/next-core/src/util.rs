@@ -13,7 +13,9 @@ use turbopack_binding::{
             asset::{Asset, AssetVc},
             environment::{ServerAddrVc, ServerInfo},
             ident::AssetIdentVc,
-            issue::{Issue, IssueSeverity, IssueSeverityVc, IssueVc, OptionIssueSourceVc},
+            issue::{
+                Issue, IssueSeverity, IssueSeverityVc, IssueVc, IssuesVc, OptionIssueSourceVc,
+            },
             reference_type::{EcmaScriptModulesReferenceSubType, ReferenceType},
             resolve::{
                 self, handle_resolve_error, node::node_cjs_resolve_options, parse::RequestVc,
@@ -25,7 +27,13 @@ use turbopack_binding::{
             parse::ParseResult,
             EcmascriptModuleAssetVc,
         },
-        turbopack::condition::ContextCondition,
+        turbopack::{
+            condition::ContextCondition,
+            module_options::{
+                JsxTransformOptions, ModuleOptionsContext, ModuleOptionsContextVc,
+                TypescriptTransformOptions,
+            },
+        },
     },
 };
 
@@ -76,6 +84,36 @@ pub fn get_asset_path_from_pathname(pathname: &str, ext: &str) -> String {
     }
 }
 
+/// Joins `path` onto the URL prefix assets are served from. Like Next.js, the
+/// `asset_prefix` (which may be an absolute CDN URL) takes precedence over the
+/// `base_path`. Exactly one `/` separates the prefix from `path`, regardless
+/// of trailing or leading slashes. Without either prefix, `path` is returned
+/// as is.
+pub fn join_asset_url(asset_prefix: &str, base_path: &str, path: &str) -> String {
+    let prefix = if asset_prefix.is_empty() {
+        base_path
+    } else {
+        asset_prefix
+    };
+    if prefix.is_empty() {
+        return path.to_string();
+    }
+    format!(
+        "{}/{}",
+        prefix.trim_end_matches('/'),
+        path.trim_start_matches('/')
+    )
+}
+
+/// Returns the directory of the packages matched by a `transpilePackages`
+/// entry. An `@scope/*` entry matches every package of the scope.
+fn transpile_package_directory(package: &str) -> String {
+    match package.strip_suffix("/*") {
+        Some(scope) if scope.starts_with('@') => format!("node_modules/{scope}"),
+        _ => format!("node_modules/{package}"),
+    }
+}
+
 pub async fn foreign_code_context_condition(next_config: NextConfigVc) -> Result<ContextCondition> {
     let transpile_packages = next_config.transpile_packages().await?;
     let result = if transpile_packages.is_empty() {
@@ -86,7 +124,9 @@ pub async fn foreign_code_context_condition(next_config: NextConfigVc) -> Result
             ContextCondition::not(ContextCondition::any(
                 transpile_packages
                     .iter()
-                    .map(|package| ContextCondition::InDirectory(format!("node_modules/{package}")))
+                    .map(|package| {
+                        ContextCondition::InDirectory(transpile_package_directory(package))
+                    })
                     .collect(),
             )),
         ])
@@ -94,6 +134,44 @@ pub async fn foreign_code_context_condition(next_config: NextConfigVc) -> Result
     Ok(result)
 }
 
+/// Returns the module options rules for foreign code: the packages configured
+/// in `experimental.turbo.foreignCodeTransforms` get their transforms enabled
+/// on top of `module_options_context`, all other foreign code uses
+/// `module_options_context` as is.
+pub async fn foreign_code_module_options_rules(
+    next_config: NextConfigVc,
+    foreign_code_context_condition: ContextCondition,
+    module_options_context: &ModuleOptionsContext,
+) -> Result<Vec<(ContextCondition, ModuleOptionsContextVc)>> {
+    let mut rules: Vec<_> = next_config
+        .foreign_code_transforms()
+        .await?
+        .iter()
+        .map(|(package, transforms)| {
+            let enable_typescript_transform = transforms
+                .typescript
+                .then(|| TypescriptTransformOptions::default().cell());
+            let enable_jsx = transforms
+                .jsx
+                .then(|| JsxTransformOptions::default().cell());
+            (
+                ContextCondition::InDirectory(format!("node_modules/{package}")),
+                ModuleOptionsContext {
+                    enable_typescript_transform,
+                    enable_jsx,
+                    ..module_options_context.clone()
+                }
+                .cell(),
+            )
+        })
+        .collect();
+    rules.push((
+        foreign_code_context_condition,
+        module_options_context.clone().cell(),
+    ));
+    Ok(rules)
+}
+
 #[derive(Default, PartialEq, Eq, Clone, Copy, Debug, TraceRawVcs, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum NextRuntime {
@@ -387,3 +465,174 @@ pub async fn render_data(
     })?;
     Ok(JsonValue(value).cell())
 }
+
+/// Returns the major version of a semver `version`, like `18` for `18.2.0`.
+pub fn major_version(version: &str) -> Option<&str> {
+    let major = version.split('.').next()?;
+    (!major.is_empty() && major.chars().all(|c| c.is_ascii_digit())).then_some(major)
+}
+
+/// Appends the context type an issue was emitted from (like `AppRSC`) to its
+/// `title`, so issues from multi-context builds can be told apart.
+pub fn issue_title_with_context_type(title: &str, context_type: Option<&str>) -> String {
+    match context_type {
+        Some(context_type) => format!("{title} [{context_type}]"),
+        None => title.to_string(),
+    }
+}
+
+/// Returns `issue` with the context type it was emitted from appended to its
+/// title, see [issue_title_with_context_type].
+pub fn issue_with_context_type(issue: IssueVc, context_type: Option<&str>) -> IssueVc {
+    match context_type {
+        Some(context_type) => ContextTypeIssue {
+            issue,
+            context_type: context_type.to_string(),
+        }
+        .cell()
+        .into(),
+        None => issue,
+    }
+}
+
+#[turbo_tasks::value(shared)]
+struct ContextTypeIssue {
+    issue: IssueVc,
+    context_type: String,
+}
+
+#[turbo_tasks::value_impl]
+impl Issue for ContextTypeIssue {
+    #[turbo_tasks::function]
+    fn severity(&self) -> IssueSeverityVc {
+        self.issue.severity()
+    }
+
+    #[turbo_tasks::function]
+    fn context(&self) -> FileSystemPathVc {
+        self.issue.context()
+    }
+
+    #[turbo_tasks::function]
+    fn category(&self) -> StringVc {
+        self.issue.category()
+    }
+
+    #[turbo_tasks::function]
+    async fn title(&self) -> Result<StringVc> {
+        Ok(StringVc::cell(issue_title_with_context_type(
+            &self.issue.title().await?,
+            Some(&self.context_type),
+        )))
+    }
+
+    #[turbo_tasks::function]
+    fn description(&self) -> StringVc {
+        self.issue.description()
+    }
+
+    #[turbo_tasks::function]
+    fn detail(&self) -> StringVc {
+        self.issue.detail()
+    }
+
+    #[turbo_tasks::function]
+    fn documentation_link(&self) -> StringVc {
+        self.issue.documentation_link()
+    }
+
+    #[turbo_tasks::function]
+    fn source(&self) -> OptionIssueSourceVc {
+        self.issue.source()
+    }
+
+    #[turbo_tasks::function]
+    fn sub_issues(&self) -> IssuesVc {
+        self.issue.sub_issues()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{join_asset_url, transpile_package_directory};
+
+    #[test]
+    fn test_transpile_package_directory() {
+        assert_eq!(transpile_package_directory("@acme/*"), "node_modules/@acme");
+        assert_eq!(
+            transpile_package_directory("@acme/button"),
+            "node_modules/@acme/button"
+        );
+        assert_eq!(transpile_package_directory("lodash"), "node_modules/lodash");
+    }
+
+    #[test]
+    fn test_join_asset_url_without_prefix() {
+        assert_eq!(
+            join_asset_url("", "", "_next/static/a.js"),
+            "_next/static/a.js"
+        );
+        assert_eq!(
+            join_asset_url("", "", "/_next/static/a.js"),
+            "/_next/static/a.js"
+        );
+    }
+
+    #[test]
+    fn test_join_asset_url_slashes() {
+        for prefix in ["/docs", "/docs/", "/docs//"] {
+            for path in ["_next/static/a.js", "/_next/static/a.js"] {
+                assert_eq!(
+                    join_asset_url("", prefix, path),
+                    "/docs/_next/static/a.js",
+                    "base path {prefix:?}, path {path:?}"
+                );
+                assert_eq!(
+                    join_asset_url(prefix, "", path),
+                    "/docs/_next/static/a.js",
+                    "asset prefix {prefix:?}, path {path:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_join_asset_url_root_prefix() {
+        assert_eq!(
+            join_asset_url("/", "", "_next/static/a.js"),
+            "/_next/static/a.js"
+        );
+        assert_eq!(
+            join_asset_url("", "/", "/_next/static/a.js"),
+            "/_next/static/a.js"
+        );
+    }
+
+    #[test]
+    fn test_join_asset_url_absolute_asset_prefix() {
+        for prefix in ["https://cdn.example.com", "https://cdn.example.com/"] {
+            for path in ["_next/static/a.js", "/_next/static/a.js"] {
+                assert_eq!(
+                    join_asset_url(prefix, "/docs", path),
+                    "https://cdn.example.com/_next/static/a.js"
+                );
+            }
+        }
+        assert_eq!(
+            join_asset_url("https://cdn.example.com/assets/", "", "/_next/static/a.js"),
+            "https://cdn.example.com/assets/_next/static/a.js"
+        );
+    }
+
+    #[test]
+    fn test_join_asset_url_asset_prefix_precedence() {
+        assert_eq!(
+            join_asset_url("/assets", "/docs", "_next/static/a.js"),
+            "/assets/_next/static/a.js"
+        );
+        assert_eq!(
+            join_asset_url("", "/docs", "_next/static/a.js"),
+            "/docs/_next/static/a.js"
+        );
+    }
+}
@@ -0,0 +1,6 @@
+import { NextResponse } from 'next/server'
+import { format } from 'cjs-first'
+
+export async function GET(): Promise<Response> {
+  return NextResponse.json({ format })
+}
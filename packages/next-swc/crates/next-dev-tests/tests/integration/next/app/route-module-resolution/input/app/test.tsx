@@ -0,0 +1,15 @@
+'use client'
+
+import { useTestHarness } from '@turbo/pack-test-harness'
+
+function test() {
+  it('should resolve the main entry of a dependency without module resolution', async () => {
+    const res = await fetch('/api/format')
+    const text = await res.text()
+    expect(text).toEqual('{"format":"cjs"}')
+  }, 20000)
+}
+
+export default function Test() {
+  useTestHarness(() => test())
+}
@@ -0,0 +1,3 @@
+module.exports = {
+  format: typeof module.exports === 'object' ? 'cjs' : 'unknown',
+}
@@ -0,0 +1 @@
+export const format: string = typeof import.meta === 'object' ? 'esm' : 'unknown'
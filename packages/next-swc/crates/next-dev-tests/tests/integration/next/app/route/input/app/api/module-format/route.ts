@@ -0,0 +1,7 @@
+import { NextResponse } from 'next/server'
+import { format as esmFormat } from './esm.mts'
+import cjs from './cjs.cts'
+
+export async function GET(): Promise<Response> {
+  return NextResponse.json({ esm: esmFormat, cjs: cjs.format })
+}
@@ -0,0 +1,6 @@
+import { NextResponse } from 'next/server'
+import { add } from './add.wasm'
+
+export async function GET(): Promise<Response> {
+  return NextResponse.json({ data: add(1, 2) })
+}
@@ -24,6 +24,18 @@ function test() {
     const text = await res.text()
     expect(text).toEqual('{"data":"secret"}')
   }, 20000)
+
+  it('should make a GET request to /api/wasm which imports a .wasm file', async () => {
+    const res = await fetch('/api/wasm')
+    const text = await res.text()
+    expect(text).toEqual('{"data":3}')
+  }, 20000)
+
+  it('should compile .mts files as ESM and .cts files as CommonJS', async () => {
+    const res = await fetch('/api/module-format')
+    const text = await res.text()
+    expect(text).toEqual('{"esm":"esm","cjs":"cjs"}')
+  }, 20000)
 }
 
 export default function Test() {
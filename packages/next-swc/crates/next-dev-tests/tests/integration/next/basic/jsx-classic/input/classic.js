@@ -0,0 +1,2 @@
+/** @jsxRuntime classic */
+export const Classic = ({ children }) => <div>{children}</div>
@@ -0,0 +1,14 @@
+import { useTestHarness } from '@turbo/pack-test-harness'
+import { Classic } from '../classic'
+
+export default function Page() {
+  useTestHarness(runTests)
+
+  return <Classic>Hello Classic</Classic>
+}
+
+function runTests() {
+  it('should render a classic runtime element without importing React', () => {
+    expect(document.body.innerHTML).toContain('<div>Hello Classic</div>')
+  })
+}
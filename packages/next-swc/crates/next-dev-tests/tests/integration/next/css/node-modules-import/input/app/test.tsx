@@ -0,0 +1,27 @@
+'use client'
+
+import 'dependency/styles/index.css'
+
+import { useTestHarness } from '@turbo/pack-test-harness'
+
+export default function Test() {
+  useTestHarness(runTests)
+
+  return <div id="test">Test</div>
+}
+
+function runTests() {
+  it("should apply the styles of a dependency's CSS", () => {
+    const el = document.getElementById('test')
+    expect(el).not.toBeNull()
+
+    expect(getComputedStyle(el!).fontWeight).toBe('700')
+  })
+
+  it("should resolve @import within a dependency's CSS", () => {
+    const el = document.getElementById('test')
+    expect(el).not.toBeNull()
+
+    expect(getComputedStyle(el!).color).toBe('rgb(255, 0, 0)')
+  })
+}
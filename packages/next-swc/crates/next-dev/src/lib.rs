@@ -12,7 +12,7 @@ use std::{
     net::{IpAddr, SocketAddr},
     path::{Path, PathBuf, MAIN_SEPARATOR},
     sync::Arc,
-    time::{Duration, Instant},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 use anyhow::{Context, Result};
@@ -21,8 +21,9 @@ use dunce::canonicalize;
 use indexmap::IndexMap;
 use next_core::{
     app_structure::find_app_dir_if_enabled, create_app_source, create_page_source,
-    create_web_entry_source, manifest::DevManifestContentSource, next_config::load_next_config,
+    create_web_entry_source, manifest::DevManifestContentSourceVc, next_config::load_next_config,
     next_image::NextImageContentSourceVc, pages_structure::find_pages_structure,
+    resolve_conditions_source::ResolveConditionsContentSourceVc,
     router_source::NextRouterContentSourceVc, source_map::NextSourceMapTraceContentSourceVc,
 };
 use once_cell::sync::Lazy;
@@ -235,6 +236,16 @@ impl NextDevServerBuilder {
         });
         let entry_requests = Arc::new(self.entry_requests);
         let server_addr = Arc::new(server.addr);
+        // Identifies this server session, so manifests are never served from the
+        // cache of an earlier session.
+        let session = format!(
+            "{:x}-{:x}",
+            std::process::id(),
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos()
+        );
         let tasks = turbo_tasks.clone();
         let issue_provider = self.issue_reporter.unwrap_or_else(|| {
             // Initialize a ConsoleUi reporter if no custom reporter was provided
@@ -250,6 +261,7 @@ impl NextDevServerBuilder {
                 turbo_tasks.clone().into(),
                 browserslist_query.clone(),
                 server_addr.clone().into(),
+                session.clone(),
             )
         };
 
@@ -296,6 +308,7 @@ async fn source(
     turbo_tasks: TransientInstance<TurboTasks<MemoryBackend>>,
     browserslist_query: String,
     server_addr: TransientInstance<SocketAddr>,
+    session: String,
 ) -> Result<ContentSourceVc> {
     let output_fs = output_fs(&project_dir);
     let fs = project_fs(&root_dir);
@@ -383,14 +396,17 @@ async fn source(
     .into();
     let static_source =
         StaticAssetsContentSourceVc::new(String::new(), project_path.join("public")).into();
-    let manifest_source = DevManifestContentSource {
-        page_roots: vec![page_source],
-        next_config,
-    }
-    .cell()
-    .into();
+    let manifest_source =
+        DevManifestContentSourceVc::builder(project_path, pages_structure, app_dir, next_config)
+            .page_roots(vec![page_source])
+            .app_roots(vec![app_source])
+            .session(session)
+            .build()?
+            .into();
+    let resolve_conditions_source = ResolveConditionsContentSourceVc::new().into();
     let main_source = CombinedContentSourceVc::new(vec![
         manifest_source,
+        resolve_conditions_source,
         static_source,
         app_source,
         page_source,